@@ -0,0 +1,69 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use gamestreaming_native::crypto::MsSrtpCryptoContext;
+
+const SRTP_KEY: &str = "RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+9SRf";
+const PACKET_COUNT: usize = 1000;
+const PAYLOAD_LEN: usize = 1200;
+
+/// Encrypted RTP packets with consecutive sequence numbers
+fn encrypted_packets() -> Vec<Vec<u8>> {
+    let mut context = MsSrtpCryptoContext::from_base64(SRTP_KEY).unwrap();
+
+    (0..PACKET_COUNT)
+        .map(|seq| {
+            let mut packet = vec![0x80, 0x60];
+            packet.extend_from_slice(&(seq as u16).to_be_bytes());
+            // Timestamp
+            packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x64]);
+            // SSRC
+            packet.extend_from_slice(&[0x00, 0x00, 0x12, 0x34]);
+            packet.resize(packet.len() + PAYLOAD_LEN, 0xAB);
+
+            context.encrypt_rtp(&packet).unwrap()
+        })
+        .collect()
+}
+
+fn decrypt(c: &mut Criterion) {
+    let encrypted = encrypted_packets();
+    let packets: Vec<&[u8]> = encrypted.iter().map(|packet| &packet[..]).collect();
+
+    let mut group = c.benchmark_group("decrypt_rtp");
+    group.throughput(Throughput::Bytes(
+        encrypted.iter().map(|packet| packet.len() as u64).sum(),
+    ));
+
+    // One output Vec per packet, on top of srtp's own buffer
+    group.bench_function("single", |b| {
+        b.iter_batched(
+            || MsSrtpCryptoContext::from_base64(SRTP_KEY).unwrap(),
+            |mut context| {
+                packets
+                    .iter()
+                    .map(|packet| context.decrypt_rtp(packet).unwrap())
+                    .collect::<Vec<Vec<u8>>>()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    // Output buffer reused across iterations, packets are still
+    // copied out of srtp's buffer
+    let mut output = Vec::new();
+    group.bench_function("batch", |b| {
+        b.iter_batched(
+            || MsSrtpCryptoContext::from_base64(SRTP_KEY).unwrap(),
+            |mut context| {
+                for result in context.decrypt_rtp_batch(&packets, &mut output) {
+                    result.unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decrypt);
+criterion_main!(benches);
@@ -6,16 +6,13 @@ use gamestreaming_native::pnet::packet::ipv6::Ipv6Packet;
 use gamestreaming_native::pnet::packet::udp::UdpPacket;
 use gamestreaming_native::pnet::packet::Packet;
 use gamestreaming_native::pnet::util::MacAddr;
-use gamestreaming_native::teredo::{Teredo, TeredoEndpoint};
+use gamestreaming_native::teredo;
 use gamestreaming_native::webrtc::rtp;
 use gamestreaming_native::webrtc::stun;
 use gamestreaming_native::webrtc::util::Unmarshal;
 use pcap::{Capture, Linktype};
 
-use std::io::prelude::*;
-
 /// Based on libpnet sample: https://github.com/libpnet/libpnet/blob/master/examples/packetdump.rs
-use std::convert::TryInto;
 use std::net::IpAddr;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -23,8 +20,6 @@ use structopt::StructOpt;
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
-const AUTH_TAG_LEN: usize = 16;
-
 #[derive(Debug)]
 struct RtpPacketResult {
     is_client: bool,
@@ -74,22 +69,18 @@ impl PcapParser {
                         udp.get_length()
                     );
                 }
-            } else if let Some(teredo) = Ipv6Packet::new(payload) {
-                if teredo.is_teredo() {
-                    let teredo_src: TeredoEndpoint = teredo.get_source().try_into()?;
-                    let teredo_dst: TeredoEndpoint = teredo.get_destination().try_into()?;
-
-                    //println!("TEREDO Packet {:?}", teredo);
-                    if self.xbox_mac == None && udp.get_source() == 3074 {
-                        self.xbox_mac.replace(source.1);
-                    }
-                    return self.handle_udp_packet(
-                        (IpAddr::V4(teredo_src.teredo_client_ipv4), source.1),
-                        (IpAddr::V4(teredo_dst.teredo_client_ipv4), destination.1),
-                        teredo.payload(),
-                        true,
-                    );
+            } else if let Some((teredo_src, teredo_dst, teredo_payload)) =
+                teredo::unwrap_teredo(payload)
+            {
+                if self.xbox_mac == None && udp.get_source() == 3074 {
+                    self.xbox_mac.replace(source.1);
                 }
+                return self.handle_udp_packet(
+                    (IpAddr::V4(teredo_src.teredo_client_ipv4), source.1),
+                    (IpAddr::V4(teredo_dst.teredo_client_ipv4), destination.1),
+                    teredo_payload,
+                    true,
+                );
             }
         }
 
@@ -189,6 +180,10 @@ struct Opt {
     #[structopt(short, long)]
     srtp_key: Option<String>,
 
+    /// Use the AES_CM_128_HMAC_SHA1_80 SRTP profile (older captures)
+    #[structopt(long)]
+    legacy_srtp: bool,
+
     #[structopt(long)]
     decrypt_pcap: Option<PathBuf>,
 }
@@ -205,12 +200,18 @@ fn main() {
 
     // Initialize Crypto context
     // If no key is provided, use dummy key
+    let protection_profile = if opt.legacy_srtp {
+        crypto::ProtectionProfile::Aes128CmHmacSha1_80
+    } else {
+        crypto::ProtectionProfile::AeadAes128Gcm
+    };
     let mut crypto_context: crypto::MsSrtpCryptoContext = {
         if let Some(key) = opt.srtp_key {
-            crypto::MsSrtpCryptoContext::from_base64(&key).expect("Failed to init crypto context")
+            crypto::MsSrtpCryptoContext::from_base64_with_profile(&key, protection_profile)
+                .expect("Failed to init crypto context")
         } else {
             let dummy_key = "RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+9SRf";
-            crypto::MsSrtpCryptoContext::from_base64(dummy_key)
+            crypto::MsSrtpCryptoContext::from_base64_with_profile(dummy_key, protection_profile)
                 .expect("Failed to init dummy crypto context")
         }
     };
@@ -250,16 +251,9 @@ fn main() {
             match pcap_out_handle.as_mut() {
                 Some(savefile) => {
                     // Assemble plaintext packet payload
-                    let datasize_until_ciphertext =
-                        pcap_packet.data.len() - (plaintext.len() + AUTH_TAG_LEN);
-
-                    let mut plaintext_eth_data: Vec<u8> = vec![];
-                    plaintext_eth_data
-                        .write_all(&pcap_packet.data[..datasize_until_ciphertext])
-                        .expect("Failed to write packet data until ciphertext");
-                    plaintext_eth_data
-                        .write_all(&plaintext)
-                        .expect("Failed to write decrypted ciphertext portion");
+                    let plaintext_eth_data = crypto_context
+                        .rewrite_decrypted(pcap_packet.data, &plaintext)
+                        .expect("Failed to assemble decrypted packet");
 
                     // Save decrypted RTP packet to pcap out
                     savefile.write(&pcap::Packet::new(pcap_packet.header, &plaintext_eth_data));
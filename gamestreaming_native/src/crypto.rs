@@ -2,7 +2,10 @@ use hmac::{digest, Hmac, Mac};
 use pbkdf2::pbkdf2;
 use sha2::Sha256;
 use std::convert::TryInto;
+use std::ops::Range;
+use thiserror::Error;
 use webrtc::rtp::header::Header;
+use webrtc::rtp::packet::Packet;
 /// Implementation of MS-SRTP
 /// Source: https://docs.microsoft.com/en-us/openspecs/office_protocols/ms-srtp/bf622cc1-9fb5-4fa2-b18d-239a84dcca65
 ///
@@ -21,11 +24,49 @@ use webrtc::rtp::header::Header;
 /// When sending or receiving an SRTP packet, this protocol first uses the SRTP session and direction to identify
 /// the cryptographic context, then uses the SSRC in the packet to decide the per SSRC transform independent
 /// parameters in the cryptographic context.
-use webrtc::srtp::{context, protection_profile};
+use webrtc::srtp::context;
+pub use webrtc::srtp::protection_profile::ProtectionProfile;
+use webrtc::util::{Marshal, MarshalSize, Unmarshal};
+use zeroize::Zeroizing;
+
+use crate::packets::Direction;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
+/// Length of base64-decoded SRTP master bytes (key + salt),
+/// the same for all supported protection profiles
+const SRTP_MASTER_BYTES_LEN: usize = 30;
+/// Length of the unencrypted SRTCP header (RTCP header + sender SSRC)
+const SRTCP_HEADER_LEN: usize = 8;
+/// Length of the E flag + SRTCP index trailer
+const SRTCP_INDEX_LEN: usize = 4;
+/// PBKDF2 iterations used to derive the ping signing key
+pub const PING_KEY_ITERATIONS: u32 = 100000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Invalid key length, expected {expected} bytes, got {actual}")]
+    InvalidKeyLength { expected: usize, actual: usize },
+    #[error("Invalid salt length, expected {expected} bytes, got {actual}")]
+    InvalidSaltLength { expected: usize, actual: usize },
+    #[error("Packet too short, expected at least {expected} bytes, got {actual}")]
+    PacketTooShort { expected: usize, actual: usize },
+}
+
+/// Parts of an encrypted SRTP / SRTCP packet, see
+/// `MsSrtpCryptoContext::split_rtp` and `MsSrtpCryptoContext::split_rtcp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrtpPacketParts<'a> {
+    /// Unencrypted header
+    pub header: &'a [u8],
+    /// Encrypted payload, without authentication tag
+    pub ciphertext: &'a [u8],
+    pub auth_tag: &'a [u8],
+    /// E flag + SRTCP index, only present for SRTCP
+    pub srtcp_index: Option<&'a [u8]>,
+}
+
 pub trait OneShotHasher {
     fn hash_oneshot(&mut self, data: &[u8]) -> Result<Vec<u8>>;
 }
@@ -39,43 +80,196 @@ impl OneShotHasher for Hmac<Sha256> {
     }
 }
 
+/// MS-SRTP context for both directions
+///
+/// The master key and salt are wiped from memory when the context
+/// is dropped.
 pub struct MsSrtpCryptoContext {
     crypto_ctx_in: context::Context,
     crypto_ctx_out: context::Context,
-    master_key: Vec<u8>,
-    master_salt: Vec<u8>,
+    master_key: Zeroizing<Vec<u8>>,
+    master_salt: Zeroizing<Vec<u8>>,
+    protection_profile: ProtectionProfile,
 }
 
 impl MsSrtpCryptoContext {
     pub fn new(master_key: [u8; 16], master_salt: [u8; 12]) -> Result<Self> {
+        Self::new_with_profile(&master_key, &master_salt, ProtectionProfile::AeadAes128Gcm)
+    }
+
+    /// Create a context for a specific protection profile, e.g.
+    /// `ProtectionProfile::Aes128CmHmacSha1_80` for older captures.
+    ///
+    /// Key and salt lengths have to match the profile.
+    pub fn new_with_profile(
+        master_key: &[u8],
+        master_salt: &[u8],
+        protection_profile: ProtectionProfile,
+    ) -> Result<Self> {
+        if master_key.len() != protection_profile.key_len() {
+            Err(CryptoError::InvalidKeyLength {
+                expected: protection_profile.key_len(),
+                actual: master_key.len(),
+            })?
+        }
+        if master_salt.len() != protection_profile.salt_len() {
+            Err(CryptoError::InvalidSaltLength {
+                expected: protection_profile.salt_len(),
+                actual: master_salt.len(),
+            })?
+        }
+
         Ok(Self {
             crypto_ctx_in: context::Context::new(
-                &master_key,
-                &master_salt,
-                protection_profile::ProtectionProfile::AeadAes128Gcm,
+                master_key,
+                master_salt,
+                protection_profile,
                 None,
                 None,
             )?,
             crypto_ctx_out: context::Context::new(
-                &master_key,
-                &master_salt,
-                protection_profile::ProtectionProfile::AeadAes128Gcm,
+                master_key,
+                master_salt,
+                protection_profile,
                 None,
                 None,
             )?,
-            master_key: master_key.to_vec(),
-            master_salt: master_salt.to_vec(),
+            master_key: Zeroizing::new(master_key.to_vec()),
+            master_salt: Zeroizing::new(master_salt.to_vec()),
+            protection_profile,
         })
     }
 
     pub fn from_base64(master_bytes: &str) -> Result<Self> {
-        let master_bytes = base64::decode(master_bytes)?;
-        Self::new(
-            master_bytes[..16].try_into()?,
-            master_bytes[16..28].try_into()?,
+        Self::from_base64_with_profile(master_bytes, ProtectionProfile::AeadAes128Gcm)
+    }
+
+    /// Create a context from base64-encoded master bytes (key + salt),
+    /// split according to `protection_profile`.
+    pub fn from_base64_with_profile(
+        master_bytes: &str,
+        protection_profile: ProtectionProfile,
+    ) -> Result<Self> {
+        let master_bytes = Zeroizing::new(base64::decode(master_bytes)?);
+        if master_bytes.len() != SRTP_MASTER_BYTES_LEN {
+            Err(CryptoError::InvalidKeyLength {
+                expected: SRTP_MASTER_BYTES_LEN,
+                actual: master_bytes.len(),
+            })?
+        }
+
+        let (master_key, rest) = master_bytes.split_at(protection_profile.key_len());
+        Self::new_with_profile(
+            master_key,
+            &rest[..protection_profile.salt_len()],
+            protection_profile,
         )
     }
 
+    pub fn protection_profile(&self) -> ProtectionProfile {
+        self.protection_profile
+    }
+
+    /// Length of the authentication tag appended to each SRTP packet,
+    /// depending on the protection profile in use.
+    pub fn auth_tag_len(&self) -> usize {
+        self.protection_profile.auth_tag_len() + self.protection_profile.aead_auth_tag_len()
+    }
+
+    /// Split an encrypted SRTP packet into header, ciphertext and
+    /// authentication tag, according to the protection profile in use.
+    pub fn split_rtp<'a>(&self, packet: &'a [u8]) -> Result<SrtpPacketParts<'a>> {
+        let mut buf = packet;
+        let header_len = Header::unmarshal(&mut buf)?.marshal_size();
+        let tag_len = self.auth_tag_len();
+
+        let payload_end = packet
+            .len()
+            .checked_sub(tag_len)
+            .filter(|end| *end >= header_len)
+            .ok_or(CryptoError::PacketTooShort {
+                expected: header_len + tag_len,
+                actual: packet.len(),
+            })?;
+
+        Ok(SrtpPacketParts {
+            header: &packet[..header_len],
+            ciphertext: &packet[header_len..payload_end],
+            auth_tag: &packet[payload_end..],
+            srtcp_index: None,
+        })
+    }
+
+    /// Split an encrypted SRTCP packet into header, ciphertext,
+    /// authentication tag and SRTCP index.
+    ///
+    /// The trailer layout depends on the protection profile: AES-CM
+    /// appends the tag after the index (RFC 3711), AEAD puts the tag
+    /// in front of it (RFC 7714).
+    pub fn split_rtcp<'a>(&self, packet: &'a [u8]) -> Result<SrtpPacketParts<'a>> {
+        let tag_len = self.auth_tag_len();
+        let min_len = SRTCP_HEADER_LEN + tag_len + SRTCP_INDEX_LEN;
+        if packet.len() < min_len {
+            Err(CryptoError::PacketTooShort {
+                expected: min_len,
+                actual: packet.len(),
+            })?
+        }
+
+        let payload_end = packet.len() - tag_len - SRTCP_INDEX_LEN;
+        let (auth_tag, srtcp_index) = if self.protection_profile.aead_auth_tag_len() > 0 {
+            packet[payload_end..].split_at(tag_len)
+        } else {
+            let (index, tag) = packet[payload_end..].split_at(SRTCP_INDEX_LEN);
+            (tag, index)
+        };
+
+        Ok(SrtpPacketParts {
+            header: &packet[..SRTCP_HEADER_LEN],
+            ciphertext: &packet[SRTCP_HEADER_LEN..payload_end],
+            auth_tag,
+            srtcp_index: Some(srtcp_index),
+        })
+    }
+
+    /// Replace the encrypted SRTP packet at the end of `original` (e.g. a
+    /// whole captured frame) with its decrypted form `plaintext`, as
+    /// returned by the `decrypt_rtp*` methods.
+    ///
+    /// Everything in front of the SRTP packet is preserved as-is.
+    pub fn rewrite_decrypted(&self, original: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.rewrite_with_trailer(original, plaintext, self.auth_tag_len())
+    }
+
+    /// Like `rewrite_decrypted`, for a SRTCP packet, which additionally
+    /// carries the SRTCP index.
+    pub fn rewrite_decrypted_rtcp(&self, original: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.rewrite_with_trailer(original, plaintext, self.auth_tag_len() + SRTCP_INDEX_LEN)
+    }
+
+    fn rewrite_with_trailer(
+        &self,
+        original: &[u8],
+        plaintext: &[u8],
+        trailer_len: usize,
+    ) -> Result<Vec<u8>> {
+        let encrypted_len = plaintext.len() + trailer_len;
+        let prefix_len =
+            original
+                .len()
+                .checked_sub(encrypted_len)
+                .ok_or(CryptoError::PacketTooShort {
+                    expected: encrypted_len,
+                    actual: original.len(),
+                })?;
+
+        let mut rewritten = Vec::with_capacity(prefix_len + plaintext.len());
+        rewritten.extend_from_slice(&original[..prefix_len]);
+        rewritten.extend_from_slice(plaintext);
+
+        Ok(rewritten)
+    }
+
     fn derive_hmac_key<T>(
         master_key: &[u8],
         salt: &[u8],
@@ -95,19 +289,29 @@ impl MsSrtpCryptoContext {
     }
 
     pub fn get_ping_signing_ctx(&self, salt: &[u8]) -> Result<Hmac<Sha256>> {
+        self.get_ping_signing_ctx_with_iterations(salt, PING_KEY_ITERATIONS)
+    }
+
+    /// Like `get_ping_signing_ctx`, deriving the key with `iterations`
+    /// PBKDF2 rounds instead of `PING_KEY_ITERATIONS`
+    pub fn get_ping_signing_ctx_with_iterations(
+        &self,
+        salt: &[u8],
+        iterations: u32,
+    ) -> Result<Hmac<Sha256>> {
         if salt.len() != 2 {
             Err("Salt has invalid length, expected 2 bytes")?
         }
 
-        let mut hmac_key: [u8; 0x20] = [0; 0x20];
+        let mut hmac_key = Zeroizing::new([0u8; 0x20]);
         MsSrtpCryptoContext::derive_hmac_key::<Sha256>(
             &self.master_key,
             salt,
-            100000,
-            &mut hmac_key,
+            iterations,
+            &mut hmac_key[..],
         )?;
 
-        MsSrtpCryptoContext::get_keyed_hasher(&hmac_key)
+        MsSrtpCryptoContext::get_keyed_hasher(&hmac_key[..])
     }
 
     pub fn decrypt_rtp_with_header(
@@ -125,6 +329,72 @@ impl MsSrtpCryptoContext {
         Ok(self.crypto_ctx_in.decrypt_rtp(encrypted)?.to_vec())
     }
 
+    /// Decrypt a SRTP packet sent in `direction`, e.g. as determined by
+    /// `DirectionClassifier` when there is no addressing to go by
+    pub fn decrypt_rtp_directed(
+        &mut self,
+        encrypted: &[u8],
+        direction: Direction,
+    ) -> Result<Vec<u8>> {
+        match direction {
+            Direction::ClientToHost => self.decrypt_rtp(encrypted),
+            Direction::HostToClient => self.decrypt_rtp_as_host(encrypted),
+        }
+    }
+
+    /// Decrypt a SRTP packet, appending the plaintext to `output`
+    ///
+    /// Returns the length of the decrypted packet.
+    ///
+    /// NOTE: The srtp crate still allocates a buffer for every
+    /// decrypted packet, which is then copied into `output`. This only
+    /// saves the `Vec` that `decrypt_rtp` returns per packet.
+    pub fn decrypt_rtp_into(&mut self, encrypted: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+        let decrypted = self.crypto_ctx_in.decrypt_rtp(encrypted)?;
+        output.extend_from_slice(&decrypted);
+        Ok(decrypted.len())
+    }
+
+    /// Decrypt a batch of SRTP packets, e.g. read from a capture
+    ///
+    /// Decrypted packets are written back to back into `output`, which
+    /// is cleared first, and returned as ranges into it. Passing the
+    /// same buffer for every batch reuses its capacity, but each packet
+    /// is still decrypted into a temporary buffer by srtp and copied
+    /// over, see `decrypt_rtp_into`.
+    ///
+    /// Packets are decrypted in order with the same context, so
+    /// rollover counters are tracked as with `decrypt_rtp`. A packet
+    /// failing to decrypt does not abort the batch.
+    pub fn decrypt_rtp_batch(
+        &mut self,
+        packets: &[&[u8]],
+        output: &mut Vec<u8>,
+    ) -> Vec<Result<Range<usize>>> {
+        output.clear();
+        output.reserve(packets.iter().map(|packet| packet.len()).sum());
+
+        packets
+            .iter()
+            .map(|packet| {
+                let start = output.len();
+                let len = self.decrypt_rtp_into(packet, output)?;
+                Ok(start..start + len)
+            })
+            .collect()
+    }
+
+    /// Decrypt a whole, already unmarshalled SRTP packet
+    ///
+    /// The packet is marshalled, decrypted and unmarshalled again.
+    pub fn decrypt_packet(&mut self, packet: &Packet) -> Result<Packet> {
+        let encrypted = packet.marshal()?;
+        let decrypted = self.decrypt_rtp(&encrypted)?;
+
+        let mut buf = &decrypted[..];
+        Ok(Packet::unmarshal(&mut buf)?)
+    }
+
     pub fn encrypt_rtp_with_header(
         &mut self,
         plaintext: &[u8],
@@ -144,8 +414,8 @@ impl MsSrtpCryptoContext {
         Ok(self.crypto_ctx_out.decrypt_rtp(encrypted)?.to_vec())
     }
 
-    pub fn encrypt_rtp_as_host(&mut self, encrypted: &[u8]) -> Result<Vec<u8>> {
-        Ok(self.crypto_ctx_in.decrypt_rtp(encrypted)?.to_vec())
+    pub fn encrypt_rtp_as_host(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.crypto_ctx_in.encrypt_rtp(plaintext)?.to_vec())
     }
 }
 
@@ -171,13 +441,336 @@ mod test {
         assert_eq!(decrypted.len(), 1348);
     }
 
+    #[test]
+    fn test_from_base64_invalid_length() {
+        for key in [
+            "RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+",
+            "RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+9SRfAAAA",
+            "",
+        ] {
+            let err = match MsSrtpCryptoContext::from_base64(key) {
+                Ok(_) => panic!("Context created from invalid key: {}", key),
+                Err(err) => err,
+            };
+
+            match err.downcast_ref::<CryptoError>() {
+                Some(CryptoError::InvalidKeyLength { expected, .. }) => {
+                    assert_eq!(*expected, 30)
+                }
+                _ => panic!("Unexpected error: {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_auth_tag_len() {
+        let context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        assert_eq!(context.auth_tag_len(), 16);
+
+        let context = MsSrtpCryptoContext::from_base64_with_profile(
+            SRTP_KEY,
+            ProtectionProfile::Aes128CmHmacSha1_80,
+        )
+        .expect("Failed to initialize crypto context");
+
+        assert_eq!(context.auth_tag_len(), 10);
+    }
+
+    #[test]
+    fn test_sha1_profile_round_trip() {
+        let mut context = MsSrtpCryptoContext::from_base64_with_profile(
+            SRTP_KEY,
+            ProtectionProfile::Aes128CmHmacSha1_80,
+        )
+        .expect("Failed to initialize crypto context");
+        assert_eq!(
+            context.protection_profile(),
+            ProtectionProfile::Aes128CmHmacSha1_80
+        );
+
+        let rtp_packet = hex::decode("80600001000000640000123401020304050607").unwrap();
+        let encrypted = context
+            .encrypt_rtp(&rtp_packet)
+            .expect("Failed to encrypt packet");
+        assert_eq!(encrypted.len(), rtp_packet.len() + 10);
+
+        let decrypted = context
+            .decrypt_rtp(&encrypted)
+            .expect("Failed to decrypt packet");
+        assert_eq!(decrypted, rtp_packet);
+    }
+
+    /// RTP packet with a one-byte header extension, so the
+    /// payload starts at offset 20
+    const RTP_PACKET_WITH_EXTENSION: &str =
+        "90e301020000006400001234bede00011005000000000102030405060708090a0b";
+
+    #[test]
+    fn test_rtp_round_trip() {
+        let mut client = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+        let mut host = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let rtp_packet = hex::decode(RTP_PACKET_WITH_EXTENSION).unwrap();
+
+        // Client -> host
+        let encrypted = client
+            .encrypt_rtp(&rtp_packet)
+            .expect("Failed to encrypt packet");
+        assert_eq!(encrypted.len(), rtp_packet.len() + client.auth_tag_len());
+        assert_eq!(encrypted[..20], rtp_packet[..20]);
+        assert_ne!(encrypted[20..rtp_packet.len()], rtp_packet[20..]);
+
+        let decrypted = host
+            .decrypt_rtp_as_host(&encrypted)
+            .expect("Failed to decrypt packet");
+        assert_eq!(decrypted, rtp_packet);
+
+        // Host -> client
+        let encrypted = host
+            .encrypt_rtp_as_host(&rtp_packet)
+            .expect("Failed to encrypt packet");
+        let decrypted = client
+            .decrypt_rtp(&encrypted)
+            .expect("Failed to decrypt packet");
+        assert_eq!(decrypted, rtp_packet);
+    }
+
+    #[test]
+    fn test_rtp_round_trip_with_header() {
+        let mut client = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+        let mut host = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let rtp_packet = hex::decode(RTP_PACKET_WITH_EXTENSION).unwrap();
+        let mut buf = &rtp_packet[..];
+        let header = Header::unmarshal(&mut buf).expect("Failed to unmarshal header");
+        assert_eq!(header.marshal_size(), 20);
+        assert_eq!(header.sequence_number, 0x0102);
+        assert_eq!(header.ssrc, 0x1234);
+
+        let encrypted = client
+            .encrypt_rtp_with_header(&rtp_packet, &header)
+            .expect("Failed to encrypt packet");
+        assert_eq!(encrypted[..20], rtp_packet[..20]);
+
+        // The header-aware variants use the same context, whereas
+        // `decrypt_rtp` is the receive direction
+        let decrypted = host
+            .decrypt_rtp_with_header(&encrypted, &header)
+            .expect("Failed to decrypt packet");
+        assert_eq!(decrypted, rtp_packet);
+
+        // A tampered payload fails authentication
+        let mut tampered = encrypted.clone();
+        tampered[20] ^= 0xFF;
+        assert!(client.decrypt_rtp_with_header(&tampered, &header).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_rtp_batch() {
+        let mut context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let plaintexts: Vec<Vec<u8>> = (1..=3)
+            .map(|seq| hex::decode(format!("806000{:02x}000000640000123401020304", seq)).unwrap())
+            .collect();
+        let mut encrypted: Vec<Vec<u8>> = plaintexts
+            .iter()
+            .map(|packet| {
+                context
+                    .encrypt_rtp(packet)
+                    .expect("Failed to encrypt packet")
+            })
+            .collect();
+        // Corrupt the auth tag of the second packet
+        let last = encrypted[1].len() - 1;
+        encrypted[1][last] ^= 0xFF;
+
+        let packets: Vec<&[u8]> = encrypted.iter().map(|packet| &packet[..]).collect();
+        let mut output = vec![0xFF; 4];
+        let results = context.decrypt_rtp_batch(&packets, &mut output);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(output[results[0].as_ref().unwrap().clone()], plaintexts[0]);
+        assert!(results[1].is_err());
+        assert_eq!(output[results[2].as_ref().unwrap().clone()], plaintexts[2]);
+        // Previous content is discarded, failed packets take no space
+        assert_eq!(output.len(), plaintexts[0].len() + plaintexts[2].len());
+    }
+
+    #[test]
+    fn test_new_with_profile_invalid_salt() {
+        let err = MsSrtpCryptoContext::new_with_profile(
+            &[0u8; 16],
+            &[0u8; 12],
+            ProtectionProfile::Aes128CmHmacSha1_80,
+        )
+        .err()
+        .expect("Context created from invalid salt");
+
+        assert_eq!(
+            err.downcast_ref::<CryptoError>(),
+            Some(&CryptoError::InvalidSaltLength {
+                expected: 14,
+                actual: 12
+            })
+        );
+    }
+
+    #[test]
+    fn test_split_rtp() {
+        let mut context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let rtp_packet = hex::decode("80600001000000640000123401020304050607").unwrap();
+        let encrypted = context
+            .encrypt_rtp(&rtp_packet)
+            .expect("Failed to encrypt packet");
+
+        let parts = context
+            .split_rtp(&encrypted)
+            .expect("Failed to split packet");
+        assert_eq!(parts.header, &rtp_packet[..12]);
+        assert_eq!(parts.ciphertext.len(), 7);
+        assert_eq!(parts.auth_tag.len(), 16);
+        assert_eq!(parts.srtcp_index, None);
+
+        assert!(context.split_rtp(&encrypted[..20]).is_err());
+    }
+
+    #[test]
+    fn test_split_rtcp() {
+        // Header (8) | ciphertext (4) | trailer
+        let header = [0x81, 0xc8, 0x00, 0x06, 0x00, 0x00, 0x12, 0x34];
+        let ciphertext = [0x01, 0x02, 0x03, 0x04];
+        let index = [0x80, 0x00, 0x00, 0x01];
+
+        let gcm = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+        let tag = [0xAA; 16];
+        let packet = [&header[..], &ciphertext, &tag, &index].concat();
+        assert_eq!(
+            gcm.split_rtcp(&packet).expect("Failed to split packet"),
+            SrtpPacketParts {
+                header: &header,
+                ciphertext: &ciphertext,
+                auth_tag: &tag,
+                srtcp_index: Some(&index),
+            }
+        );
+
+        let sha1 = MsSrtpCryptoContext::from_base64_with_profile(
+            SRTP_KEY,
+            ProtectionProfile::Aes128CmHmacSha1_80,
+        )
+        .expect("Failed to initialize crypto context");
+        let tag = [0xBB; 10];
+        let packet = [&header[..], &ciphertext, &index, &tag].concat();
+        assert_eq!(
+            sha1.split_rtcp(&packet).expect("Failed to split packet"),
+            SrtpPacketParts {
+                header: &header,
+                ciphertext: &ciphertext,
+                auth_tag: &tag,
+                srtcp_index: Some(&index),
+            }
+        );
+
+        let err = sha1
+            .split_rtcp(&packet[..20])
+            .err()
+            .expect("Split too short packet");
+        assert_eq!(
+            err.downcast_ref::<CryptoError>(),
+            Some(&CryptoError::PacketTooShort {
+                expected: 22,
+                actual: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_rewrite_decrypted() {
+        let mut context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let rtp_packet = hex::decode("80600001000000640000123401020304050607").unwrap();
+        let frame_header = [0xAA; 42];
+
+        let encrypted = context
+            .encrypt_rtp(&rtp_packet)
+            .expect("Failed to encrypt packet");
+        assert_eq!(encrypted.len(), rtp_packet.len() + context.auth_tag_len());
+
+        let original = [&frame_header[..], &encrypted[..]].concat();
+        let plaintext = context
+            .decrypt_rtp(&encrypted)
+            .expect("Failed to decrypt packet");
+
+        let rewritten = context
+            .rewrite_decrypted(&original, &plaintext)
+            .expect("Failed to rewrite decrypted packet");
+
+        assert_eq!(rewritten, [&frame_header[..], &rtp_packet[..]].concat());
+    }
+
+    #[test]
+    fn test_rewrite_decrypted_too_short() {
+        let context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let err = context
+            .rewrite_decrypted(&[0u8; 20], &[0u8; 12])
+            .expect_err("Rewrite of too short packet succeeded");
+
+        assert_eq!(
+            err.downcast_ref::<CryptoError>(),
+            Some(&CryptoError::PacketTooShort {
+                expected: 28,
+                actual: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_decrypt_packet() {
+        let mut context = MsSrtpCryptoContext::from_base64(SRTP_KEY)
+            .expect("Failed to initialize crypto context");
+
+        let rtp_packet = hex::decode("80600001000000640000123401020304050607").unwrap();
+        let encrypted = context
+            .encrypt_rtp(&rtp_packet)
+            .expect("Failed to encrypt packet");
+
+        let mut buf = &encrypted[..];
+        let encrypted_packet =
+            Packet::unmarshal(&mut buf).expect("Failed to unmarshal encrypted packet");
+
+        let packet = context
+            .decrypt_packet(&encrypted_packet)
+            .expect("Failed to decrypt packet");
+
+        assert_eq!(packet.header.sequence_number, 1);
+        assert_eq!(packet.header.timestamp, 100);
+        assert_eq!(packet.header.ssrc, 0x1234);
+        assert_eq!(
+            &packet.payload[..],
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07]
+        );
+    }
+
     #[test]
     fn test_ping_key_derivation() {
         let mut hmac_key: [u8; 0x20] = [0; 0x20];
         MsSrtpCryptoContext::derive_hmac_key::<Sha256>(
             &hex::decode("d7d27ce7dfc3ef499935fbbdb4451dc6").unwrap(),
             &hex::decode("ffff").unwrap(),
-            100000,
+            PING_KEY_ITERATIONS,
             &mut hmac_key,
         )
         .expect("Failed to derive hmac key");
@@ -222,4 +815,75 @@ mod test {
             "d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5"
         );
     }
+
+    #[test]
+    fn test_get_ping_key_context_iterations() {
+        let ctx = MsSrtpCryptoContext::from_base64("19J859/D70mZNfu9tEUdxgUVVMbRDkV/L2LavviX")
+            .expect("Failed to create MS-SRTP context");
+
+        let sign = |iterations: u32| {
+            let mut ping_signing_ctx = ctx
+                .get_ping_signing_ctx_with_iterations(&hex::decode("ffff").unwrap(), iterations)
+                .expect("Failed to create ping signing context");
+            ping_signing_ctx.update(&hex::decode("00000000").unwrap());
+            hex::encode(ping_signing_ctx.finalize().into_bytes())
+        };
+
+        assert_eq!(
+            sign(PING_KEY_ITERATIONS),
+            "d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5"
+        );
+        assert_eq!(
+            sign(1000),
+            "b05eb013880997dc75a8e8bf3a0370f4562b4ee18a80dafdec4a74ceab661817"
+        );
+    }
+
+    /// The ping salt is the first two bytes of the UDP payload, passed to
+    /// `get_ping_signing_ctx` verbatim. No port (de)obfuscation or byte
+    /// swapping is done on it.
+    ///
+    /// If a capture carries the Teredo-obfuscated port (`port ^ 0xFFFF`)
+    /// in network byte order, those bytes are the salt as-is. The salt
+    /// `ffff` used by the vectors above is the obfuscated form of port 0.
+    /// Signatures for the other byte forms are pinned to make a mixup
+    /// easy to spot.
+    #[test]
+    fn test_ping_salt_byte_order() {
+        let ctx = MsSrtpCryptoContext::from_base64("19J859/D70mZNfu9tEUdxgUVVMbRDkV/L2LavviX")
+            .expect("Failed to create MS-SRTP context");
+
+        let port: u16 = 3074;
+        let obfuscated = port ^ 0xFFFF;
+        assert_eq!(obfuscated, 0xF3FD);
+
+        let sign = |salt: [u8; 2]| {
+            let mut ping_signing_ctx = ctx
+                .get_ping_signing_ctx(&salt)
+                .expect("Failed to create ping signing context");
+            ping_signing_ctx.update(&0u32.to_le_bytes());
+            hex::encode(ping_signing_ctx.finalize().into_bytes())
+        };
+
+        // Obfuscated, big endian: f3fd
+        assert_eq!(
+            sign(obfuscated.to_be_bytes()),
+            "ba9c5e6e45464cc3887bb3256f9473595cf36c3ff3ddfee2d30c98f5e3daa2e5"
+        );
+        // Deobfuscated, big endian: 0c02
+        assert_eq!(
+            sign(port.to_be_bytes()),
+            "c2dfe772c4dcb1632a5be3c087ccc73ecc2bdd384d404994a5fe96c6f1ccf4d3"
+        );
+        // Obfuscated, little endian: fdf3
+        assert_eq!(
+            sign(obfuscated.to_le_bytes()),
+            "66dff490fdb563e54e7de36d5c37785402e2fae628d1770fcc8eeb401c681884"
+        );
+        // Deobfuscated, little endian: 020c
+        assert_eq!(
+            sign(port.to_le_bytes()),
+            "8a47aeefdd6f31e4bf3e3df8c9a1431559abf3ceeba9a7a937adef13826ae9a8"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+#![forbid(unsafe_code)]
 #![allow(dead_code)]
 pub extern crate pnet;
 pub extern crate teredo;
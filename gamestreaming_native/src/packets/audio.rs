@@ -1,6 +1,9 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum AudioPacketType {
     ServerHandshake = 1,
@@ -10,6 +13,7 @@ pub enum AudioPacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum AudioCodec {
     Opus = 0,
@@ -21,6 +25,7 @@ pub enum AudioCodec {
 /// or reinit the audio stream.
 /// Total bits: 32
 #[derive(Debug, Clone, DekuRead, DekuWrite, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioControlFlags {
     /// Reinit audio stream
     /// Bit 30 / Mask LE 0x40000000 BE 0x40
@@ -40,18 +45,21 @@ pub struct AudioControlFlags {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioDataFlags {
     // TODO: Found out what these are
     pub unknown: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct PCMAudioFormat {
     pub bits: u32,
     pub is_float: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioFormat {
     pub channels: u32,
     pub frequency: u32,
@@ -61,6 +69,7 @@ pub struct AudioFormat {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioServerHandshake {
     pub protocol_version: u32,
     pub reference_timestamp: u64,
@@ -71,17 +80,20 @@ pub struct AudioServerHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioClientHandshake {
     pub initial_frame_id: u32,
     pub requested_format: AudioFormat,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioControl {
     pub flags: AudioControlFlags,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct AudioData {
     pub flags: AudioDataFlags,
     pub frame_id: u32,
@@ -93,6 +105,7 @@ pub struct AudioData {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum AudioPacket {
     ServerHandshake(AudioServerHandshake),
     ClientHandshake(AudioClientHandshake),
@@ -161,4 +174,75 @@ mod tests {
         assert_eq!(reinit_flag.to_bytes().unwrap(), vec![0x40, 0, 0, 0]);
         assert_eq!(start_reinit_flag.to_bytes().unwrap(), vec![0x50, 0, 0, 0]);
     }
+
+    #[test]
+    fn audio_server_handshake_little_endian() {
+        let mut data = vec![];
+        data.extend_from_slice(&4u32.to_le_bytes()); // protocol_version
+        data.extend_from_slice(&1613399625116u64.to_le_bytes()); // reference_timestamp
+        data.extend_from_slice(&1u32.to_le_bytes()); // format_count
+        data.extend_from_slice(&2u32.to_le_bytes()); // channels
+        data.extend_from_slice(&48000u32.to_le_bytes()); // frequency
+        data.extend_from_slice(&1u32.to_le_bytes()); // codec: PCM
+        data.extend_from_slice(&16u32.to_le_bytes()); // bits
+        data.extend_from_slice(&0u32.to_le_bytes()); // is_float
+
+        assert_eq!(&data[4..12], &[0x9c, 0x8d, 0x1c, 0xa6, 0x77, 0x01, 0, 0]);
+
+        let (_, handshake) =
+            AudioServerHandshake::from_bytes((&data, 0)).expect("Failed to parse handshake");
+
+        assert_eq!(handshake.protocol_version, 4);
+        assert_eq!(handshake.reference_timestamp, 1613399625116);
+        assert_eq!(handshake.format_count, 1);
+        assert_eq!(
+            handshake.formats[0],
+            AudioFormat {
+                channels: 2,
+                frequency: 48000,
+                codec: AudioCodec::Pcm,
+                pcm_format: Some(PCMAudioFormat {
+                    bits: 16,
+                    is_float: 0,
+                }),
+            }
+        );
+        assert_eq!(handshake.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn audio_client_handshake_little_endian() {
+        let handshake = AudioClientHandshake {
+            initial_frame_id: 0x0102_0304,
+            requested_format: AudioFormat {
+                channels: 2,
+                frequency: 48000,
+                codec: AudioCodec::Opus,
+                pcm_format: None,
+            },
+        };
+
+        assert_eq!(
+            handshake.to_bytes().unwrap(),
+            vec![0x04, 0x03, 0x02, 0x01, 2, 0, 0, 0, 0x80, 0xbb, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn audio_data_little_endian() {
+        let mut data = vec![];
+        data.extend_from_slice(&0xdead_beefu32.to_le_bytes()); // flags
+        data.extend_from_slice(&0x0102u32.to_le_bytes()); // frame_id
+        data.extend_from_slice(&1613399625116u64.to_le_bytes()); // timestamp
+        data.extend_from_slice(&3u32.to_le_bytes()); // data_size
+        data.extend_from_slice(&[0xaa, 0xbb, 0xcc]);
+
+        let (_, audio) = AudioData::from_bytes((&data, 0)).expect("Failed to parse data");
+
+        assert_eq!(audio.flags.unknown, 0xdead_beef);
+        assert_eq!(audio.frame_id, 0x0102);
+        assert_eq!(audio.timestamp, 1613399625116);
+        assert_eq!(audio.data, vec![0xaa, 0xbb, 0xcc]);
+        assert_eq!(audio.to_bytes().unwrap(), data);
+    }
 }
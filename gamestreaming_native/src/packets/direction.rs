@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use webrtc::rtp;
+use webrtc::util::Unmarshal;
+
+use super::udp_connection_probing::ConnectionProbingType;
+use super::ParsedPacket;
+
+/// Direction a packet travels in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by the client (e.g. the app), use `decrypt_rtp`
+    ClientToHost,
+    /// Sent by the host (console / cloud), use `decrypt_rtp_as_host`
+    HostToClient,
+}
+
+/// Lowest and highest SSRC seen for a direction
+#[derive(Debug, Clone, Copy)]
+struct SsrcRange {
+    min: u32,
+    max: u32,
+}
+
+impl SsrcRange {
+    fn extend(range: Option<Self>, ssrc: u32) -> Self {
+        match range {
+            Some(range) => Self {
+                min: range.min.min(ssrc),
+                max: range.max.max(ssrc),
+            },
+            None => Self {
+                min: ssrc,
+                max: ssrc,
+            },
+        }
+    }
+
+    /// Distance of `ssrc` to the range, 0 if it lies within
+    fn distance(&self, ssrc: u32) -> u32 {
+        if ssrc < self.min {
+            self.min - ssrc
+        } else {
+            ssrc.saturating_sub(self.max)
+        }
+    }
+}
+
+/// Infers the direction of RTP packets from their SSRC, for inputs
+/// without addressing (e.g. payloads replayed from a webrtc track)
+///
+/// Each side uses its own set of SSRCs, one per channel. The classifier
+/// learns them while the direction is known - from handshake packets
+/// (`observe`) or explicitly (`learn`) - and afterwards assigns unseen
+/// SSRCs to the direction whose SSRC range is closest.
+#[derive(Debug, Default)]
+pub struct DirectionClassifier {
+    known: HashMap<u32, Direction>,
+    client: Option<SsrcRange>,
+    host: Option<SsrcRange>,
+}
+
+impl DirectionClassifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `ssrc` as used in `direction`
+    pub fn learn(&mut self, ssrc: u32, direction: Direction) {
+        self.known.insert(ssrc, direction);
+        match direction {
+            Direction::ClientToHost => self.client = Some(SsrcRange::extend(self.client, ssrc)),
+            Direction::HostToClient => self.host = Some(SsrcRange::extend(self.host, ssrc)),
+        }
+    }
+
+    /// Learn the direction from a decrypted handshake packet:
+    /// connection probing requests are sent by the client, the
+    /// acknowledgements by the host.
+    ///
+    /// Returns the direction if `packet` revealed it.
+    pub fn observe(&mut self, ssrc: u32, packet: &ParsedPacket) -> Option<Direction> {
+        let direction = match packet {
+            ParsedPacket::UDPConnectionProbing(probing) => match probing.packet_type {
+                ConnectionProbingType::Syn => Direction::ClientToHost,
+                ConnectionProbingType::Ack => Direction::HostToClient,
+            },
+            _ => return None,
+        };
+
+        self.learn(ssrc, direction);
+        Some(direction)
+    }
+
+    /// Direction of `ssrc`, `None` until SSRCs of at least one direction
+    /// were learned, or if it is equally close to both ranges
+    pub fn classify(&self, ssrc: u32) -> Option<Direction> {
+        if let Some(direction) = self.known.get(&ssrc) {
+            return Some(*direction);
+        }
+
+        match (self.client, self.host) {
+            (Some(_), None) => Some(Direction::ClientToHost),
+            (None, Some(_)) => Some(Direction::HostToClient),
+            (Some(client), Some(host)) => {
+                let (client, host) = (client.distance(ssrc), host.distance(ssrc));
+                if client < host {
+                    Some(Direction::ClientToHost)
+                } else if host < client {
+                    Some(Direction::HostToClient)
+                } else {
+                    None
+                }
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Like `classify`, reading the SSRC from a raw (possibly encrypted)
+    /// RTP packet, the header is never encrypted.
+    pub fn classify_rtp(&self, packet: &[u8]) -> Option<Direction> {
+        let mut buf = packet;
+        let header = rtp::header::Header::unmarshal(&mut buf).ok()?;
+
+        self.classify(header.ssrc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packets::decode_rtp_payload;
+
+    #[test]
+    fn classify_by_range() {
+        let mut classifier = DirectionClassifier::new();
+        assert_eq!(classifier.classify(1), None);
+
+        classifier.learn(1000, Direction::ClientToHost);
+        assert_eq!(classifier.classify(5000), Some(Direction::ClientToHost));
+
+        classifier.learn(1003, Direction::ClientToHost);
+        classifier.learn(2000, Direction::HostToClient);
+        classifier.learn(2002, Direction::HostToClient);
+
+        assert_eq!(classifier.classify(1001), Some(Direction::ClientToHost));
+        assert_eq!(classifier.classify(1200), Some(Direction::ClientToHost));
+        assert_eq!(classifier.classify(2001), Some(Direction::HostToClient));
+        assert_eq!(classifier.classify(u32::MAX), Some(Direction::HostToClient));
+        // Halfway in between
+        assert_eq!(classifier.classify(1502), None);
+    }
+
+    #[test]
+    fn observe_handshake() {
+        let mut classifier = DirectionClassifier::new();
+
+        let syn = decode_rtp_payload(&[0x66, 1, 0, 2, 3, 4, 5, 6]).unwrap();
+        let ack = decode_rtp_payload(&[0x66, 2, 0, 0x9a, 0x05, 0, 0]).unwrap();
+        let keepalive = decode_rtp_payload(&[0x65, 0xAA]).unwrap();
+
+        assert_eq!(
+            classifier.observe(0x1234, &syn),
+            Some(Direction::ClientToHost)
+        );
+        assert_eq!(
+            classifier.observe(0x5678, &ack),
+            Some(Direction::HostToClient)
+        );
+        assert_eq!(classifier.observe(0x9999, &keepalive), None);
+
+        let packet = hex::decode("80600001000000640000123501020304").unwrap();
+        assert_eq!(
+            classifier.classify_rtp(&packet),
+            Some(Direction::ClientToHost)
+        );
+        assert_eq!(classifier.classify_rtp(&packet[..4]), None);
+    }
+}
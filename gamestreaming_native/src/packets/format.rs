@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use super::udp_connection_probing::ConnectionProbingType;
+use super::ParsedPacket;
+
+/// Render a decoded packet as an annotated, human-readable block
+///
+/// Each field is printed on its own line, prefixed by its byte offset
+/// in the RTP payload (the payload type byte is at offset 0).
+///
+/// ```text
+/// UDPConnectionProbing(0x66)
+///   [0x00] payload_type: UDPConnectionProbing(0x66)
+///   [0x01] packet_type: Ack (2)
+///   [0x03] accepted_packet_size: 1434
+///   [0x05] appendix: 0
+/// ```
+pub fn format_packet(packet: &ParsedPacket) -> String {
+    let mut out = String::new();
+    let payload_type = packet.payload_type();
+
+    // Writing into a String can't fail
+    let _ = writeln!(out, "{}", payload_type);
+    field(&mut out, 0, "payload_type", payload_type);
+
+    match packet {
+        ParsedPacket::MuxDCTControl(header) => {
+            field(&mut out, 1, "bla", format!("0x{:04x}", header.bla));
+            field(&mut out, 3, "bla2", format!("0x{:04x}", header.bla2));
+            field(&mut out, 5, "woop", format!("0x{:04x}", header.woop));
+            field(&mut out, 7, "woop2", format!("0x{:04x}", header.woop2));
+        }
+        ParsedPacket::UDPConnectionProbing(probing) => {
+            let type_value = match probing.packet_type {
+                ConnectionProbingType::Syn => 1,
+                ConnectionProbingType::Ack => 2,
+            };
+            field(
+                &mut out,
+                1,
+                "packet_type",
+                format!("{:?} ({})", probing.packet_type, type_value),
+            );
+            if let Some(syn) = &probing.syn {
+                field(&mut out, 3, "probe_data", bytes(&syn.probe_data));
+            }
+            if let Some(ack) = &probing.ack {
+                field(
+                    &mut out,
+                    3,
+                    "accepted_packet_size",
+                    ack.accepted_packet_size,
+                );
+                field(&mut out, 5, "appendix", ack.appendix);
+            }
+        }
+        ParsedPacket::URCPDummy(dummy) => {
+            field(&mut out, 1, "padding", bytes(&dummy.padding));
+        }
+        ParsedPacket::Unhandled(_, payload) => {
+            field(
+                &mut out,
+                1,
+                "data",
+                bytes(payload.get(1..).unwrap_or_default()),
+            );
+        }
+    }
+
+    out
+}
+
+fn field(out: &mut String, offset: usize, name: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "  [0x{:02x}] {}: {}", offset, name, value);
+}
+
+/// Length and the leading bytes in hex
+fn bytes(data: &[u8]) -> String {
+    const PREVIEW_LEN: usize = 16;
+
+    let preview: Vec<String> = data
+        .iter()
+        .take(PREVIEW_LEN)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let ellipsis = if data.len() > PREVIEW_LEN { " .." } else { "" };
+
+    format!("{} bytes [{}{}]", data.len(), preview.join(" "), ellipsis)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packets::decode_rtp_payload;
+
+    #[test]
+    fn format_connection_probing_ack() {
+        let packet = decode_rtp_payload(&[0x66, 2, 0, 0x9a, 0x05, 0, 0]).unwrap();
+
+        assert_eq!(
+            format_packet(&packet),
+            "UDPConnectionProbing(0x66)\n\
+             \x20 [0x00] payload_type: UDPConnectionProbing(0x66)\n\
+             \x20 [0x01] packet_type: Ack (2)\n\
+             \x20 [0x03] accepted_packet_size: 1434\n\
+             \x20 [0x05] appendix: 0\n"
+        );
+    }
+
+    #[test]
+    fn format_connection_probing_syn() {
+        let packet = decode_rtp_payload(&[0x66, 1, 0, 2, 3, 4, 5, 6]).unwrap();
+
+        assert!(format_packet(&packet).ends_with("[0x03] probe_data: 5 bytes [02 03 04 05 06]\n"));
+    }
+
+    #[test]
+    fn format_unhandled() {
+        let mut payload = vec![0x65];
+        payload.extend_from_slice(&[0xAB; 20]);
+        let packet = decode_rtp_payload(&payload).unwrap();
+
+        let formatted = format_packet(&packet);
+        assert!(formatted.starts_with("UDPKeepAlive(0x65)\n"));
+        assert!(formatted.contains("[0x01] data: 20 bytes [ab ab"));
+        assert!(formatted.ends_with(" ..]\n"));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::video::{VideoData, VideoDataFlags};
+use super::Result;
+
+/// Largest frame accepted for reassembly, guards against allocating
+/// huge buffers for a bogus `total_size`
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+/// Incomplete frames kept at once, older ones are dropped first
+const MAX_PENDING_FRAMES: usize = 8;
+
+/// Video frame reassembled from its `VideoData` fragments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoFrame {
+    pub frame_id: u32,
+    pub timestamp: u64,
+    pub flags: VideoDataFlags,
+    pub metadata: Vec<u8>,
+    /// Encoded frame, e.g. H.264 Annex B
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct PendingFrame {
+    timestamp: u64,
+    flags: VideoDataFlags,
+    metadata_size: usize,
+    buffer: Vec<u8>,
+    /// Offsets of the fragments received so far
+    offsets: HashSet<u32>,
+    received: usize,
+}
+
+/// Reassembles video frames from `VideoData` fragments
+///
+/// Each fragment carries its offset into the frame, which is made up of
+/// the metadata followed by the frame data. Fragments may arrive in any
+/// order, duplicates are ignored. Once a frame completes, older
+/// incomplete frames are dropped as they can't be displayed anymore.
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    /// Keyed by frame id, relative to the first frame seen
+    /// to handle wrap around
+    pending: BTreeMap<u32, PendingFrame>,
+    first_frame_id: Option<u32>,
+    dropped: u64,
+}
+
+impl FrameAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of frames dropped before they were complete
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Number of frames waiting for further fragments
+    pub fn pending_frames(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Add a fragment, returns the frame once all its fragments arrived
+    ///
+    /// Fails for fragments exceeding the frame they belong to.
+    pub fn push(&mut self, fragment: &VideoData) -> Result<Option<VideoFrame>> {
+        let total_size = fragment.total_size as usize;
+        let metadata_size = fragment.metadata_size as usize;
+        let offset = fragment.offset as usize;
+        let len = fragment.metadata.len() + fragment.data.len();
+
+        if total_size > MAX_FRAME_SIZE {
+            Err(format!(
+                "Frame {} of {} bytes exceeds maximum size",
+                fragment.frame_id, total_size
+            ))?
+        }
+        if metadata_size > total_size || offset + len > total_size {
+            Err(format!(
+                "Fragment at offset {} ({} bytes) exceeds frame {} of {} bytes",
+                offset, len, fragment.frame_id, total_size
+            ))?
+        }
+
+        let first_frame_id = *self.first_frame_id.get_or_insert(fragment.frame_id);
+        let key = fragment.frame_id.wrapping_sub(first_frame_id);
+
+        let frame = self.pending.entry(key).or_insert_with(|| PendingFrame {
+            timestamp: fragment.timestamp,
+            flags: fragment.flags,
+            metadata_size,
+            buffer: vec![0u8; total_size],
+            offsets: HashSet::new(),
+            received: 0,
+        });
+        if frame.buffer.len() != total_size || frame.metadata_size != metadata_size {
+            Err(format!(
+                "Fragment sizes of frame {} are inconsistent",
+                fragment.frame_id
+            ))?
+        }
+        if !frame.offsets.insert(fragment.offset) {
+            return Ok(None);
+        }
+
+        let buffer = &mut frame.buffer[offset..offset + len];
+        let (metadata, data) = buffer.split_at_mut(fragment.metadata.len());
+        metadata.copy_from_slice(&fragment.metadata);
+        data.copy_from_slice(&fragment.data);
+        frame.received += len;
+
+        if frame.received < total_size {
+            if self.pending.len() > MAX_PENDING_FRAMES {
+                let oldest = *self.pending.keys().next().expect("Frames are pending");
+                self.pending.remove(&oldest);
+                self.dropped += 1;
+            }
+            return Ok(None);
+        }
+
+        let mut frame = self.pending.remove(&key).expect("Frame is pending");
+        // Older frames can't be completed in time anymore
+        let newer = self.pending.split_off(&key);
+        self.dropped += self.pending.len() as u64;
+        self.pending = newer;
+
+        let data = frame.buffer.split_off(frame.metadata_size);
+        Ok(Some(VideoFrame {
+            frame_id: fragment.frame_id,
+            timestamp: frame.timestamp,
+            flags: frame.flags,
+            metadata: frame.buffer,
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use deku::DekuContainerRead;
+
+    use super::*;
+    use crate::packets::video::VideoPacket;
+
+    /// Split a frame of `metadata` + `data` into fragments of `chunk` bytes
+    fn fragments(frame_id: u32, metadata: &[u8], data: &[u8], chunk: usize) -> Vec<VideoData> {
+        let mut frame = metadata.to_vec();
+        frame.extend_from_slice(data);
+        let packet_count = ((frame.len() + chunk - 1) / chunk) as u32;
+
+        frame
+            .chunks(chunk)
+            .enumerate()
+            .map(|(index, bytes)| {
+                let offset = index * chunk;
+                // Only the first fragment carries the metadata
+                let metadata_len = metadata.len().saturating_sub(offset).min(bytes.len());
+                VideoData {
+                    unknown1: 1,
+                    unknown2: 0,
+                    flags: VideoDataFlags(0x04),
+                    frame_id,
+                    timestamp: 1000 + frame_id as u64,
+                    packet_count,
+                    total_size: frame.len() as u32,
+                    metadata_size: metadata.len() as u32,
+                    offset: offset as u32,
+                    unknown3: metadata_len as u32,
+                    data_size: (bytes.len() - metadata_len) as u32,
+                    metadata: bytes[..metadata_len].to_vec(),
+                    data: bytes[metadata_len..].to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut parts = fragments(7, &[0xAA, 0xBB], &data, 100);
+        assert_eq!(parts.len(), 3);
+        parts.swap(0, 2);
+
+        let mut assembler = FrameAssembler::new();
+        assert_eq!(assembler.push(&parts[0]).unwrap(), None);
+        assert_eq!(assembler.push(&parts[1]).unwrap(), None);
+        // Duplicate
+        assert_eq!(assembler.push(&parts[1]).unwrap(), None);
+        assert_eq!(assembler.pending_frames(), 1);
+
+        let frame = assembler
+            .push(&parts[2])
+            .unwrap()
+            .expect("Frame not complete");
+        assert_eq!(frame.frame_id, 7);
+        assert_eq!(frame.timestamp, 1007);
+        assert_eq!(frame.metadata, vec![0xAA, 0xBB]);
+        assert_eq!(frame.data, data);
+        assert_eq!(assembler.pending_frames(), 0);
+        assert_eq!(assembler.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn drop_incomplete_frames() {
+        let mut assembler = FrameAssembler::new();
+
+        let incomplete = fragments(u32::MAX, &[], &[1, 2, 3, 4], 2);
+        assert_eq!(assembler.push(&incomplete[0]).unwrap(), None);
+
+        // Frame id wrapped around
+        let complete = fragments(0, &[], &[5, 6], 2);
+        let frame = assembler
+            .push(&complete[0])
+            .unwrap()
+            .expect("Frame not complete");
+        assert_eq!(frame.data, vec![5, 6]);
+
+        assert_eq!(assembler.dropped_frames(), 1);
+        assert_eq!(assembler.pending_frames(), 0);
+    }
+
+    #[test]
+    fn reject_oversized_fragment() {
+        let mut fragment = fragments(1, &[], &[1, 2, 3, 4], 2).remove(1);
+        fragment.offset = 3;
+
+        assert!(FrameAssembler::new().push(&fragment).is_err());
+    }
+
+    #[test]
+    fn captured_first_fragment() {
+        let data = include_bytes!("../../testdata/video_data.bin");
+        let (_, packet) = VideoPacket::from_bytes((&data[12..data.len() - 2], 0))
+            .expect("Failed to parse VideoData packet");
+        let fragment = packet.data.expect("Data not parsed");
+
+        let mut assembler = FrameAssembler::new();
+        assert_eq!(assembler.push(&fragment).unwrap(), None);
+        assert_eq!(assembler.pending_frames(), 1);
+    }
+}
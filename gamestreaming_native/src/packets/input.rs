@@ -1,6 +1,9 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum InputPacketType {
     ServerHandshakeV3 = 1,
@@ -14,6 +17,7 @@ pub enum InputPacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InputServerHandshake {
     pub min_protocol_version: u32,
     pub max_protocol_version: u32,
@@ -24,6 +28,7 @@ pub struct InputServerHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InputClientHandshake {
     pub min_protocol_version: u32,
     pub max_protocol_version: u32,
@@ -32,11 +37,13 @@ pub struct InputClientHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InputFrameAck {
     pub acked_frame_id: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InputFrameV3 {
     pub frame_id: u32,
     pub timestamp: i64,
@@ -44,9 +51,11 @@ pub struct InputFrameV3 {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct UnknownInputFramev3Data(u32, u32, u32);
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct FrameV3Data {
     pub input_count: u32,
     // FIXME
@@ -61,15 +70,19 @@ pub struct FrameV3Data {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MouseData {}
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct GamepadData {}
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct KeyboardData {}
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InputFrameV4 {
     pub frame_id: u32,
     pub timestamp: i64,
@@ -77,12 +90,77 @@ pub struct InputFrameV4 {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct FrameChanges {}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum InputPacket {
     ServerHandshake(InputServerHandshake),
     ClientHandshake(InputClientHandshake),
     FrameAck,
     Frame,
 }
+
+// MS-RTP payload fields are little endian, unlike the big endian RTP header.
+// These tests pin the byte order of every multi-byte field.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_packet_type_little_endian() {
+        let (_, packet_type) =
+            InputPacketType::from_bytes((&[0x07, 0, 0, 0], 0)).expect("Failed to parse type");
+        assert_eq!(packet_type, InputPacketType::FrameV4);
+        assert!(InputPacketType::from_bytes((&[0, 0, 0, 0x07], 0)).is_err());
+    }
+
+    #[test]
+    fn input_server_handshake_little_endian() {
+        let mut data = vec![];
+        data.extend_from_slice(&3u32.to_le_bytes()); // min_protocol_version
+        data.extend_from_slice(&4u32.to_le_bytes()); // max_protocol_version
+        data.extend_from_slice(&1920u32.to_le_bytes()); // desktop_width
+        data.extend_from_slice(&1080u32.to_le_bytes()); // desktop_height
+        data.extend_from_slice(&10u32.to_le_bytes()); // maximum_touches
+        data.extend_from_slice(&0x0102_0304u32.to_le_bytes()); // initial_frame_id
+
+        let (_, handshake) =
+            InputServerHandshake::from_bytes((&data, 0)).expect("Failed to parse handshake");
+
+        assert_eq!(handshake.min_protocol_version, 3);
+        assert_eq!(handshake.max_protocol_version, 4);
+        assert_eq!(handshake.desktop_width, 1920);
+        assert_eq!(handshake.desktop_height, 1080);
+        assert_eq!(handshake.maximum_touches, 10);
+        assert_eq!(handshake.initial_frame_id, 0x0102_0304);
+        assert_eq!(handshake.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn input_client_handshake_little_endian() {
+        let handshake = InputClientHandshake {
+            min_protocol_version: 3,
+            max_protocol_version: 4,
+            maximum_touches: 10,
+            reference_timestamp: 1613399625116,
+        };
+        let data = handshake.to_bytes().unwrap();
+
+        assert_eq!(data.len(), 20);
+        assert_eq!(&data[..4], &[0x03, 0, 0, 0]);
+        assert_eq!(&data[12..], &[0x9c, 0x8d, 0x1c, 0xa6, 0x77, 0x01, 0, 0]);
+
+        let (_, parsed) =
+            InputClientHandshake::from_bytes((&data, 0)).expect("Failed to parse handshake");
+        assert_eq!(parsed, handshake);
+    }
+
+    #[test]
+    fn input_frame_ack_little_endian() {
+        let (_, ack) =
+            InputFrameAck::from_bytes((&[0x34, 0x12, 0, 0], 0)).expect("Failed to parse ack");
+        assert_eq!(ack.acked_frame_id, 0x1234);
+    }
+}
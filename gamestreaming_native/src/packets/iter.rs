@@ -0,0 +1,97 @@
+use super::{decode_packet, ParsedPacket, Result};
+
+/// Iterates over packets concatenated in a single buffer
+///
+/// Each packet is decoded from the bytes left over by the previous
+/// one, iteration ends with the buffer or after the first error.
+///
+/// NOTE: Payload types without dedicated parsing have no known
+/// length, the `ParsedPacket::Unhandled` packet holds all
+/// remaining bytes and ends the iteration. The same applies to
+/// the padding of `ParsedPacket::URCPDummy`.
+pub struct PacketIter<'a> {
+    buf: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> PacketIter<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, failed: false }
+    }
+
+    /// Bytes not consumed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+impl<'a> Iterator for PacketIter<'a> {
+    type Item = Result<ParsedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() || self.failed {
+            return None;
+        }
+
+        match decode_packet(self.buf) {
+            Ok((packet, len)) => {
+                self.buf = &self.buf[len..];
+                Some(Ok(packet))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packets::PayloadType;
+
+    #[test]
+    fn iterate_concatenated_packets() {
+        // UDPConnectionProbing Ack
+        let mut buf = vec![0x66, 0x02, 0x00, 0x9a, 0x05, 0x00, 0x00];
+        // MuxDCTControl
+        buf.extend_from_slice(&[0x61, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04, 0x00]);
+        // Unhandled, consumes the rest
+        buf.extend_from_slice(&[0x65, 0xAA, 0xBB]);
+
+        let packets: Vec<ParsedPacket> = PacketIter::new(&buf)
+            .collect::<Result<_>>()
+            .expect("Failed to parse packets");
+
+        assert_eq!(packets.len(), 3);
+        assert!(matches!(packets[0], ParsedPacket::UDPConnectionProbing(_)));
+        match &packets[1] {
+            ParsedPacket::MuxDCTControl(header) => {
+                assert_eq!(header.bla, 1);
+                assert_eq!(header.woop2, 4);
+            }
+            other => panic!("Unexpected packet {:?}", other),
+        }
+        assert_eq!(
+            packets[2],
+            ParsedPacket::Unhandled(PayloadType::UDPKeepAlive, vec![0x65, 0xAA, 0xBB])
+        );
+    }
+
+    #[test]
+    fn stop_after_error() {
+        // Truncated UDPConnectionProbing Ack
+        let buf = [0x66, 0x02, 0x00, 0x9a];
+        let mut iter = PacketIter::new(&buf);
+
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+        assert_eq!(iter.remaining(), &buf);
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(PacketIter::new(&[]).next().is_none());
+    }
+}
@@ -1,6 +1,9 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum MessagePacketType {
     Handshake = 1,
@@ -9,11 +12,13 @@ pub enum MessagePacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MessageHandshake {
     pub unknown: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MessageData {
     pub unknown1: u32,
     pub unknown2: u32,
@@ -24,6 +29,7 @@ pub struct MessageData {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MessageCancelRequest {
     pub unknown: u32,
 }
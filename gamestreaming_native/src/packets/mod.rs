@@ -1,22 +1,51 @@
 mod audio;
+mod direction;
+mod format;
+mod frame;
 mod input;
+mod iter;
 mod message;
 mod mux_dct_channel;
 mod mux_dct_control;
+mod mux_demuxer;
 mod ping;
 mod qos;
+mod reader;
+mod sequence;
 mod udp_connection_probing;
+mod urcp;
 pub mod video;
 
+use std::fmt;
+
 use deku::prelude::*;
 use hexdump;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 use webrtc::rtp;
+use webrtc::util::Unmarshal;
 
-use mux_dct_control::MuxDCTControlHeader;
-use udp_connection_probing::ConnectionProbingPacket;
+pub use direction::{Direction, DirectionClassifier};
+pub use format::format_packet;
+pub use frame::{FrameAssembler, VideoFrame};
+pub use iter::PacketIter;
+pub use mux_dct_channel::ChannelType;
+pub use mux_dct_control::{
+    ChannelControlMessage, ChannelCreate, ControlProtocolPacketType, MuxDCTControlHeader,
+};
+pub use mux_demuxer::{MuxDemuxer, MuxEvent, MuxPacket};
+pub use ping::{build_ping_response, PingFlag, PingPayload};
+pub use reader::{PacketReader, ReadPacket};
+pub use sequence::{SequenceEvent, SequenceTracker};
+pub use udp_connection_probing::ConnectionProbingPacket;
+pub use urcp::{URCPControlPacket, URCPDummyPacket};
 
-#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u8")]
 pub enum PayloadType {
     Unknown = 0x0,
@@ -33,7 +62,21 @@ pub enum PayloadType {
     MockUDPDctCtrl = 0x7f,
 }
 
+impl PayloadType {
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Name and hex value, e.g. `MuxDCTControl(0x61)`
+impl fmt::Display for PayloadType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}(0x{:02x})", self, self.as_u8())
+    }
+}
+
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u8")]
 pub enum ControlProtocolMessageOpCode {
     Auth = 0x1,
@@ -43,11 +86,78 @@ pub enum ControlProtocolMessageOpCode {
     Config2 = 0x6,
 }
 
-pub fn parse_rtp_packet(packet: &rtp::packet::Packet) {
-    let (_, payload_type) =
-        PayloadType::from_bytes((&packet.payload[..1], 0)).expect("Failed to parse PayloadType");
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ParsedPacket {
+    MuxDCTControl(MuxDCTControlHeader),
+    UDPConnectionProbing(ConnectionProbingPacket),
+    URCPDummy(URCPDummyPacket),
+    /// Payload type without dedicated parsing (yet), holds the whole RTP payload
+    Unhandled(PayloadType, Vec<u8>),
+}
+
+impl ParsedPacket {
+    pub fn payload_type(&self) -> PayloadType {
+        match self {
+            ParsedPacket::MuxDCTControl(_) => PayloadType::MuxDCTControl,
+            ParsedPacket::UDPConnectionProbing(_) => PayloadType::UDPConnectionProbing,
+            ParsedPacket::URCPDummy(_) => PayloadType::URCPDummyPacket,
+            ParsedPacket::Unhandled(payload_type, _) => *payload_type,
+        }
+    }
+
+    /// Serialize the packet, including the payload type
+    ///
+    /// The result is the RTP payload to send for this packet, the
+    /// counterpart of `decode_rtp_payload`.
+    ///
+    /// An unmodified packet serializes to exactly the bytes it was
+    /// decoded from, so packets can be parsed, selectively rewritten
+    /// and forwarded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let body = match self {
+            ParsedPacket::MuxDCTControl(header) => header.to_bytes()?,
+            ParsedPacket::UDPConnectionProbing(probing) => probing.to_bytes()?,
+            ParsedPacket::URCPDummy(dummy) => dummy.padding.clone(),
+            // Holds the whole payload, including the payload type
+            ParsedPacket::Unhandled(_, payload) => return Ok(payload.clone()),
+        };
+
+        let mut bytes = vec![self.payload_type().as_u8()];
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+}
+
+/// Decode the payload of an RTP packet, dispatching by payload type
+///
+/// Bytes trailing the first packet are ignored, see `PacketIter`.
+pub fn decode_rtp_payload(payload: &[u8]) -> Result<ParsedPacket> {
+    decode_packet(payload).map(|(packet, _)| packet)
+}
+
+/// Consumed bytes of a deku parse of `input`, which started at byte 1 of
+/// the payload (after the payload type)
+fn consumed_len(input: &[u8], rest: (&[u8], usize)) -> Result<usize> {
+    if rest.1 != 0 {
+        Err("Packet does not end on a byte boundary")?
+    }
+
+    Ok(1 + input.len() - rest.0.len())
+}
 
-    match payload_type {
+/// Decode the packet at the start of `payload`, returning it along with
+/// the number of bytes it occupies
+///
+/// Packets without dedicated parsing consume all remaining bytes.
+fn decode_packet(payload: &[u8]) -> Result<(ParsedPacket, usize)> {
+    if payload.is_empty() {
+        Err("Empty RTP payload")?
+    }
+
+    let (_, payload_type) = PayloadType::from_bytes((&payload[..1], 0))?;
+
+    let packet = match payload_type {
         /*
         PayloadType::MuxDCTChannelRangeDefault => {
 
@@ -60,17 +170,11 @@ pub fn parse_rtp_packet(packet: &rtp::packet::Packet) {
         },
         */
         PayloadType::MuxDCTControl => {
-            println!(
-                "RTP: {:?} Seq: {}, ts: {}, ssrc: {}",
-                payload_type,
-                packet.header.sequence_number,
-                packet.header.timestamp,
-                packet.header.ssrc
-            );
-            hexdump::hexdump(&packet.payload);
-            let (_, packet) = MuxDCTControlHeader::from_bytes((&packet.payload[1..], 0))
-                .expect("Failed to parse MuxDCTControlPacket");
-            println!("{:?}", packet);
+            let (rest, packet) = MuxDCTControlHeader::from_bytes((&payload[1..], 0))?;
+            (
+                ParsedPacket::MuxDCTControl(packet),
+                consumed_len(&payload[1..], rest)?,
+            )
         }
         /*
         PayloadType::FECControl => {
@@ -80,33 +184,261 @@ pub fn parse_rtp_packet(packet: &rtp::packet::Packet) {
 
         },
         PayloadType::URCPControl => {
+            Layout unverified, see `URCPControlPacket`
         },
         PayloadType::UDPKeepAlive => {
         },
         */
         PayloadType::UDPConnectionProbing => {
-            let (_, packet) = ConnectionProbingPacket::from_bytes((&packet.payload[1..], 0))
-                .expect("Failed to parse UDPConnectionProbingPacket");
-
-            println!("{:?}", packet);
+            let (rest, packet) = ConnectionProbingPacket::from_bytes((&payload[1..], 0))?;
+            (
+                ParsedPacket::UDPConnectionProbing(packet),
+                consumed_len(&payload[1..], rest)?,
+            )
         }
+        PayloadType::URCPDummyPacket => (
+            ParsedPacket::URCPDummy(URCPDummyPacket {
+                padding: payload[1..].to_vec(),
+            }),
+            payload.len(),
+        ),
         /*
-        PayloadType::URCPDummyPacket => {
-
-        },
         PayloadType::MockUDPDctCtrl => {
 
         },
         */
-        _ => {
-            println!(
-                "RTP: {:?} Seq: {}, ts: {}, ssrc: {}",
-                payload_type,
-                packet.header.sequence_number,
-                packet.header.timestamp,
-                packet.header.ssrc
-            );
-            hexdump::hexdump(&packet.payload);
+        _ => (
+            ParsedPacket::Unhandled(payload_type, payload.to_vec()),
+            payload.len(),
+        ),
+    };
+
+    Ok(packet)
+}
+
+/// Decode a raw RTP packet, returning the RTP header and the parsed payload
+pub fn decode_rtp(data: &[u8]) -> Result<(rtp::header::Header, ParsedPacket)> {
+    let mut buf = data;
+    let packet = rtp::packet::Packet::unmarshal(&mut buf)?;
+    let parsed = decode_rtp_payload(&packet.payload)?;
+
+    Ok((packet.header, parsed))
+}
+
+/// Print the RTP header and all packets contained in the payload
+pub fn parse_rtp_packet(packet: &rtp::packet::Packet) {
+    println!(
+        "RTP: Seq: {}, ts: {}, ssrc: {}",
+        packet.header.sequence_number, packet.header.timestamp, packet.header.ssrc
+    );
+
+    let parsed: Vec<Result<ParsedPacket>> = PacketIter::new(&packet.payload).collect();
+    if !matches!(
+        parsed.first(),
+        Some(Ok(ParsedPacket::UDPConnectionProbing(_)))
+    ) {
+        hexdump::hexdump(&packet.payload);
+    }
+
+    for parsed in parsed {
+        match parsed {
+            Ok(parsed) => print!("{}", format_packet(&parsed)),
+            Err(err) => println!("Failed to decode: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use webrtc::util::Marshal;
+
+    use super::*;
+    use crate::packets::video::VideoPacket;
+
+    /// Parser to apply to a captured fixture
+    enum Fixture {
+        /// Whole (encrypted) RTP packet
+        Rtp,
+        /// Channel control message, starting at the given offset
+        ChannelControl(usize),
+        /// Video channel packet, starting at the given offset
+        Video(usize),
+    }
+
+    /// Parse `data` with `T`, returning the serialized packet along with
+    /// the bytes it was parsed from
+    fn reserialize<'a, T>(data: &'a [u8]) -> (Vec<u8>, &'a [u8])
+    where
+        T: DekuContainerRead<'a> + DekuContainerWrite,
+    {
+        let (rest, packet) = T::from_bytes((data, 0)).expect("Failed to parse fixture");
+        assert_eq!(rest.1, 0, "Packet does not end on a byte boundary");
+
+        (
+            packet.to_bytes().unwrap(),
+            &data[..data.len() - rest.0.len()],
+        )
+    }
+
+    #[test]
+    fn reserialize_fixtures() {
+        let fixtures: [(&str, &[u8], Fixture); 13] = [
+            (
+                "channel_control_create_control.bin",
+                include_bytes!("../../testdata/channel_control_create_control.bin"),
+                Fixture::ChannelControl(9),
+            ),
+            (
+                "channel_control_create_qos.bin",
+                include_bytes!("../../testdata/channel_control_create_qos.bin"),
+                Fixture::ChannelControl(12),
+            ),
+            (
+                "channel_control_create_video.bin",
+                include_bytes!("../../testdata/channel_control_create_video.bin"),
+                Fixture::ChannelControl(4),
+            ),
+            (
+                "channel_control_open_video.bin",
+                include_bytes!("../../testdata/channel_control_open_video.bin"),
+                Fixture::ChannelControl(4),
+            ),
+            (
+                "rtp_connection_probing.bin",
+                include_bytes!("../../testdata/rtp_connection_probing.bin"),
+                Fixture::Rtp,
+            ),
+            (
+                "video_client_handshake.bin",
+                include_bytes!("../../testdata/video_client_handshake.bin"),
+                Fixture::Video(12),
+            ),
+            (
+                "video_control.bin",
+                include_bytes!("../../testdata/video_control.bin"),
+                Fixture::Video(20),
+            ),
+            (
+                "video_control2.bin",
+                include_bytes!("../../testdata/video_control2.bin"),
+                Fixture::Video(12),
+            ),
+            (
+                "video_data.bin",
+                include_bytes!("../../testdata/video_data.bin"),
+                Fixture::Video(12),
+            ),
+            (
+                "video_data2.bin",
+                include_bytes!("../../testdata/video_data2.bin"),
+                Fixture::Video(12),
+            ),
+            (
+                "video_data3.bin",
+                include_bytes!("../../testdata/video_data3.bin"),
+                Fixture::Video(12),
+            ),
+            (
+                "video_data_last.bin",
+                include_bytes!("../../testdata/video_data_last.bin"),
+                Fixture::Video(27),
+            ),
+            (
+                "video_server_handshake.bin",
+                include_bytes!("../../testdata/video_server_handshake.bin"),
+                Fixture::Video(20),
+            ),
+        ];
+
+        // Every captured fixture is covered
+        let mut files: Vec<String> =
+            std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| name.ends_with(".bin"))
+                .collect();
+        files.sort();
+        let names: Vec<&str> = fixtures.iter().map(|(name, _, _)| *name).collect();
+        assert_eq!(files, names);
+
+        for (name, data, fixture) in fixtures {
+            let (serialized, parsed) = match fixture {
+                Fixture::Rtp => {
+                    let mut buf = data;
+                    let packet = rtp::packet::Packet::unmarshal(&mut buf).unwrap();
+                    (packet.marshal().unwrap().to_vec(), data)
+                }
+                Fixture::ChannelControl(offset) => {
+                    reserialize::<ChannelControlMessage>(&data[offset..])
+                }
+                Fixture::Video(offset) => reserialize::<VideoPacket>(&data[offset..]),
+            };
+
+            assert_eq!(serialized, parsed, "{} did not re-serialize", name);
+        }
+    }
+
+    #[test]
+    fn payload_type_display() {
+        assert_eq!(PayloadType::MuxDCTControl.as_u8(), 0x61);
+        assert_eq!(
+            PayloadType::MuxDCTControl.to_string(),
+            "MuxDCTControl(0x61)"
+        );
+        assert_eq!(
+            PayloadType::UDPConnectionProbing.to_string(),
+            "UDPConnectionProbing(0x66)"
+        );
+        assert_eq!(PayloadType::Unknown.to_string(), "Unknown(0x00)");
+    }
+
+    #[test]
+    fn reserialize_packets() {
+        let mut urcp_control = vec![0x64];
+        urcp_control.extend_from_slice(&[0xAB; 16]);
+        let payloads: Vec<Vec<u8>> = vec![
+            vec![0x61, 0x01, 0x64, 0x00, 0x64, 0x00, 0x02, 0x00, 0x00],
+            vec![0x66, 1, 0, 2, 3, 4, 5, 6],
+            vec![0x66, 2, 0, 0x9a, 0x05, 0, 0],
+            urcp_control,
+            vec![0x68, 0xDE, 0xAD],
+            vec![0x65, 0xAA, 0xBB],
+        ];
+
+        for payload in &payloads {
+            let packet = decode_rtp_payload(payload).expect("Failed to decode packet");
+            assert_eq!(&packet.to_bytes().unwrap(), payload);
+            assert_eq!(packet.payload_type().as_u8(), payload[0]);
         }
+
+        // Concatenated packets re-serialize to the whole payload
+        let concatenated = payloads[..4].concat();
+        let reserialized: Vec<u8> = PacketIter::new(&concatenated)
+            .map(|packet| packet.unwrap().to_bytes().unwrap())
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+        assert_eq!(reserialized, concatenated);
+    }
+
+    #[test]
+    fn decode_urcp() {
+        // Control packets are not parsed, trailing bytes stay with the packet
+        let mut payload = vec![0x64, 0x02, 0x01, 0x01, 0x00];
+        payload.extend_from_slice(&[0; 12]);
+        let (packet, len) = decode_packet(&payload).expect("Failed to decode URCP control");
+        assert_eq!(len, 17);
+        assert_eq!(
+            packet,
+            ParsedPacket::Unhandled(PayloadType::URCPControl, payload.clone())
+        );
+
+        let (packet, len) = decode_packet(&[0x68, 0, 0, 0]).expect("Failed to decode dummy");
+        assert_eq!(len, 4);
+        assert_eq!(
+            packet,
+            ParsedPacket::URCPDummy(URCPDummyPacket {
+                padding: vec![0, 0, 0]
+            })
+        );
     }
 }
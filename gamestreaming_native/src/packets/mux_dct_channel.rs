@@ -1,4 +1,6 @@
 use crate::packets::{audio, input, qos, video};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 /// Following channel classes exist:
 ///
@@ -12,6 +14,7 @@ use crate::packets::{audio, input, qos, video};
 /// Microsoft::Basix::Dct::Channel::Class::Messaging
 /// Microsoft::Basix::Dct::Channel::Class::QoS
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ChannelType {
     Base,
 
@@ -26,10 +29,56 @@ pub enum ChannelType {
     QoS,
 }
 
+impl ChannelType {
+    const CLASS_PREFIX: &'static str = "Microsoft::Basix::Dct::Channel::Class::";
+
+    /// Get channel type from its class name, as sent in
+    /// the mux-dct channel creation.
+    pub fn from_class_name(class_name: &str) -> Option<Self> {
+        let channel_type = match class_name.strip_prefix(Self::CLASS_PREFIX)? {
+            "Audio" => Self::Audio,
+            "Video" => Self::Video,
+            "Input" => Self::Input,
+            "InputV2" => Self::InputV2,
+            "Input Feedback" => Self::InputFeedback,
+            "ChatAudio" => Self::ChatAudio,
+            "Control" => Self::Control,
+            "Messaging" => Self::Messaging,
+            "QoS" => Self::QoS,
+            _ => return None,
+        };
+
+        Some(channel_type)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ChannelPacket {
     Audio(audio::AudioPacket),
     Video(video::VideoPacket),
     Input(input::InputPacket),
     Qos(qos::QosPacket),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn channel_type_from_class_name() {
+        assert_eq!(
+            ChannelType::from_class_name("Microsoft::Basix::Dct::Channel::Class::Video"),
+            Some(ChannelType::Video)
+        );
+        assert_eq!(
+            ChannelType::from_class_name("Microsoft::Basix::Dct::Channel::Class::Input Feedback"),
+            Some(ChannelType::InputFeedback)
+        );
+        assert_eq!(
+            ChannelType::from_class_name("Microsoft::Basix::Dct::Channel::Class::Unknown"),
+            None
+        );
+        assert_eq!(ChannelType::from_class_name("Video"), None);
+    }
+}
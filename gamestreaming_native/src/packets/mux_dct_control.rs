@@ -1,4 +1,8 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use super::mux_dct_channel::ChannelType;
 
 /*
 RTP: MuxDCTControl Seq: 5, ts: 0, ssrc: 1024
@@ -38,7 +42,9 @@ RTP: MuxDCTControl Seq: 11, ts: 0, ssrc: 1026
                                                        0000000e
 */
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[deku(type = "u32")]
 pub enum ControlProtocolPacketType {
     Create = 2,
     Open = 3,
@@ -46,9 +52,36 @@ pub enum ControlProtocolPacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MuxDCTControlHeader {
     pub bla: u16,
     pub bla2: u16,
     pub woop: u16,
     pub woop2: u16,
 }
+
+/// Channel creation, assigns a channel class to the channel
+#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ChannelCreate {
+    #[deku(update = "self.class_name.len()")]
+    pub class_name_len: u16,
+    /// e.g. `Microsoft::Basix::Dct::Channel::Class::Video`
+    #[deku(count = "class_name_len")]
+    pub class_name: Vec<u8>,
+}
+
+impl ChannelCreate {
+    pub fn channel_type(&self) -> Option<ChannelType> {
+        ChannelType::from_class_name(std::str::from_utf8(&self.class_name).ok()?)
+    }
+}
+
+/// Channel control message, following the mux-dct framing
+#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ChannelControlMessage {
+    pub packet_type: ControlProtocolPacketType,
+    #[deku(cond = "*packet_type == ControlProtocolPacketType::Create")]
+    pub create: Option<ChannelCreate>,
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use super::mux_dct_channel::ChannelType;
+use super::Result;
+
+/// Decoded mux-dct packet, as fed into `MuxDemuxer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MuxPacket {
+    /// Channel id got assigned to a channel class
+    Create {
+        channel_id: u16,
+        channel_type: ChannelType,
+    },
+    Open {
+        channel_id: u16,
+    },
+    Close {
+        channel_id: u16,
+    },
+    /// Payload sent on a channel
+    Data {
+        channel_id: u16,
+        payload: Vec<u8>,
+    },
+}
+
+/// Channel state change, surfaced by `MuxDemuxer::feed`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MuxEvent {
+    ChannelOpened {
+        channel_id: u16,
+        channel_type: ChannelType,
+    },
+    ChannelClosed {
+        channel_id: u16,
+        channel_type: ChannelType,
+    },
+}
+
+#[derive(Debug)]
+struct MuxChannel {
+    channel_type: ChannelType,
+    is_open: bool,
+    buffer: Vec<u8>,
+}
+
+/// Demultiplexes mux-dct packets into per-channel byte streams
+///
+/// Channels have to be created and opened before payloads
+/// are accepted, received payloads are buffered until taken
+/// via `take_data`.
+#[derive(Debug, Default)]
+pub struct MuxDemuxer {
+    channels: HashMap<u16, MuxChannel>,
+}
+
+impl MuxDemuxer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a decoded packet, returns an event if the
+    /// state of a channel changed.
+    pub fn feed(&mut self, packet: MuxPacket) -> Result<Option<MuxEvent>> {
+        match packet {
+            MuxPacket::Create {
+                channel_id,
+                channel_type,
+            } => {
+                if self.channels.contains_key(&channel_id) {
+                    Err(format!("Channel {} already exists", channel_id))?
+                }
+
+                self.channels.insert(
+                    channel_id,
+                    MuxChannel {
+                        channel_type,
+                        is_open: false,
+                        buffer: vec![],
+                    },
+                );
+
+                Ok(None)
+            }
+            MuxPacket::Open { channel_id } => {
+                let channel = self.channel_mut(channel_id)?;
+                if channel.is_open {
+                    return Ok(None);
+                }
+                channel.is_open = true;
+
+                Ok(Some(MuxEvent::ChannelOpened {
+                    channel_id,
+                    channel_type: channel.channel_type.clone(),
+                }))
+            }
+            MuxPacket::Close { channel_id } => {
+                let channel = self
+                    .channels
+                    .remove(&channel_id)
+                    .ok_or_else(|| format!("Unknown channel {}", channel_id))?;
+
+                Ok(Some(MuxEvent::ChannelClosed {
+                    channel_id,
+                    channel_type: channel.channel_type,
+                }))
+            }
+            MuxPacket::Data {
+                channel_id,
+                payload,
+            } => {
+                let channel = self.channel_mut(channel_id)?;
+                if !channel.is_open {
+                    Err(format!("Received data on unopened channel {}", channel_id))?
+                }
+                channel.buffer.extend_from_slice(&payload);
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Take all data buffered for a channel
+    pub fn take_data(&mut self, channel_id: u16) -> Option<Vec<u8>> {
+        self.channels
+            .get_mut(&channel_id)
+            .map(|channel| std::mem::take(&mut channel.buffer))
+    }
+
+    pub fn channel_type(&self, channel_id: u16) -> Option<&ChannelType> {
+        self.channels
+            .get(&channel_id)
+            .map(|channel| &channel.channel_type)
+    }
+
+    pub fn is_open(&self, channel_id: u16) -> bool {
+        matches!(self.channels.get(&channel_id), Some(channel) if channel.is_open)
+    }
+
+    fn channel_mut(&mut self, channel_id: u16) -> Result<&mut MuxChannel> {
+        Ok(self
+            .channels
+            .get_mut(&channel_id)
+            .ok_or_else(|| format!("Unknown channel {}", channel_id))?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn open_channel(demuxer: &mut MuxDemuxer, channel_id: u16, channel_type: ChannelType) {
+        demuxer
+            .feed(MuxPacket::Create {
+                channel_id,
+                channel_type,
+            })
+            .expect("Failed to create channel");
+        demuxer
+            .feed(MuxPacket::Open { channel_id })
+            .expect("Failed to open channel");
+    }
+
+    #[test]
+    fn demux_channels() {
+        let mut demuxer = MuxDemuxer::new();
+
+        demuxer
+            .feed(MuxPacket::Create {
+                channel_id: 2,
+                channel_type: ChannelType::Control,
+            })
+            .expect("Failed to create channel");
+        assert!(!demuxer.is_open(2));
+
+        let event = demuxer
+            .feed(MuxPacket::Open { channel_id: 2 })
+            .expect("Failed to open channel");
+        assert_eq!(
+            event,
+            Some(MuxEvent::ChannelOpened {
+                channel_id: 2,
+                channel_type: ChannelType::Control
+            })
+        );
+
+        open_channel(&mut demuxer, 3, ChannelType::Video);
+
+        for (channel_id, payload) in [(2, [0x01, 0x02]), (3, [0xAA, 0xBB]), (2, [0x03, 0x04])] {
+            demuxer
+                .feed(MuxPacket::Data {
+                    channel_id,
+                    payload: payload.to_vec(),
+                })
+                .expect("Failed to feed data");
+        }
+
+        assert_eq!(demuxer.take_data(2), Some(vec![0x01, 0x02, 0x03, 0x04]));
+        assert_eq!(demuxer.take_data(2), Some(vec![]));
+        assert_eq!(demuxer.take_data(3), Some(vec![0xAA, 0xBB]));
+        assert_eq!(demuxer.channel_type(3), Some(&ChannelType::Video));
+
+        let event = demuxer
+            .feed(MuxPacket::Close { channel_id: 3 })
+            .expect("Failed to close channel");
+        assert_eq!(
+            event,
+            Some(MuxEvent::ChannelClosed {
+                channel_id: 3,
+                channel_type: ChannelType::Video
+            })
+        );
+        assert_eq!(demuxer.take_data(3), None);
+    }
+
+    #[test]
+    fn demux_invalid_packets() {
+        let mut demuxer = MuxDemuxer::new();
+
+        // Unknown channel
+        assert!(demuxer.feed(MuxPacket::Open { channel_id: 1 }).is_err());
+        assert!(demuxer.feed(MuxPacket::Close { channel_id: 1 }).is_err());
+
+        // Data on unopened channel
+        demuxer
+            .feed(MuxPacket::Create {
+                channel_id: 1,
+                channel_type: ChannelType::Audio,
+            })
+            .expect("Failed to create channel");
+        assert!(demuxer
+            .feed(MuxPacket::Data {
+                channel_id: 1,
+                payload: vec![0x00],
+            })
+            .is_err());
+
+        // Duplicate channel id
+        assert!(demuxer
+            .feed(MuxPacket::Create {
+                channel_id: 1,
+                channel_type: ChannelType::Input,
+            })
+            .is_err());
+    }
+}
@@ -1,9 +1,16 @@
-use crate::crypto::OneShotHasher;
+use super::Result;
+use crate::crypto::{MsSrtpCryptoContext, OneShotHasher};
 use deku::prelude::*;
-use hmac::Hmac;
+use hmac::{Hmac, Mac};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 
+/// Length of the salt / connection id preceding the ping payload
+const PING_SALT_LEN: usize = 2;
+
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u8")]
 pub enum PingFlag {
     Request = 0x00,
@@ -11,6 +18,7 @@ pub enum PingFlag {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct PingPayload {
     pub ping_type: u8,
     pub flags: PingFlag,
@@ -42,16 +50,38 @@ impl PingPayload {
         }
     }
 
-    /*
-    fn is_signature_valid(&self, mut signing_context: Hmac<Sha256>) -> Result<()> {
+    fn is_signature_valid(&self, signing_context: &Hmac<Sha256>) -> bool {
+        let mut signing_context = signing_context.clone();
         signing_context.update(&self.sequence_num.to_le_bytes());
 
-        let result = signing_context.verify(&self.signature)
-            .expect("Signature verification failed");
+        signing_context.verify_slice(&self.signature).is_ok()
+    }
+}
 
-        Ok(result)
+/// Build the response to a received ping (UDP payload)
+///
+/// The leading salt selects the ping signing key, the request signature
+/// is verified before the signed response, prefixed by the same salt,
+/// is returned.
+pub fn build_ping_response(ctx: &MsSrtpCryptoContext, received: &[u8]) -> Result<Vec<u8>> {
+    if received.len() < PING_SALT_LEN {
+        Err("Ping packet too short")?
     }
-     */
+
+    let (salt, payload) = received.split_at(PING_SALT_LEN);
+    let mut signing_context = ctx.get_ping_signing_ctx(salt)?;
+
+    let (_, request) = PingPayload::from_bytes((payload, 0))?;
+    if request.flags != PingFlag::Request {
+        Err("Ping packet is not a request")?
+    }
+    if !request.is_signature_valid(&signing_context) {
+        Err("Ping request has invalid signature")?
+    }
+
+    let response = PingPayload::new_ack(request.sequence_num, &mut signing_context);
+
+    Ok([salt, &response.to_bytes()?].concat())
 }
 
 #[cfg(test)]
@@ -106,4 +136,47 @@ mod test {
             "d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5"
         );
     }
+
+    #[test]
+    fn ping_response() {
+        let ctx = MsSrtpCryptoContext::from_base64("19J859/D70mZNfu9tEUdxgUVVMbRDkV/L2LavviX")
+            .expect("Failed to create MS-SRTP context");
+
+        let request = hex::decode(
+            "ffff010000000000d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5",
+        )
+        .expect("Failed to hex-decode ping packet");
+
+        let response = build_ping_response(&ctx, &request).expect("Failed to build response");
+
+        assert_eq!(
+            hex::encode(&response),
+            "ffff01ff00000000d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5"
+        );
+    }
+
+    #[test]
+    fn ping_response_invalid_request() {
+        let ctx = MsSrtpCryptoContext::from_base64("19J859/D70mZNfu9tEUdxgUVVMbRDkV/L2LavviX")
+            .expect("Failed to create MS-SRTP context");
+
+        let mut request = hex::decode(
+            "ffff010000000000d0c87bfa07d4e7fc9909d96e3cb3977d5232bbb391932236d56411f82d103bd5",
+        )
+        .expect("Failed to hex-decode ping packet");
+
+        // Too short
+        assert!(build_ping_response(&ctx, &request[..1]).is_err());
+        assert!(build_ping_response(&ctx, &request[..10]).is_err());
+
+        // Tampered signature
+        let last = request.len() - 1;
+        request[last] ^= 0xFF;
+        assert!(build_ping_response(&ctx, &request).is_err());
+
+        // Response instead of request
+        request[last] ^= 0xFF;
+        request[3] = 0xFF;
+        assert!(build_ping_response(&ctx, &request).is_err());
+    }
 }
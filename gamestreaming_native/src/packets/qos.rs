@@ -1,6 +1,9 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum QosPacketType {
     ServerHandshake = 1,
@@ -12,6 +15,7 @@ pub enum QosPacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosControlFlags {
     // Bit 1 / Mask 0x01
     #[deku(pad_bits_before = "7", bits = "1")]
@@ -19,6 +23,7 @@ pub struct QosControlFlags {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosServerPolicy {
     pub schema_version: u32,
     pub policy_length: u32,
@@ -28,6 +33,7 @@ pub struct QosServerPolicy {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosServerHandshake {
     pub protocol_version: u32,
     #[deku(cond = "*protocol_version >= 1")]
@@ -35,28 +41,33 @@ pub struct QosServerHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosClientPolicy {
     pub schema_version: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosClientHandshake {
     pub protocol_version: u32,
     pub initial_frame_id: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosControl {
     pub flags: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosData {
     pub flags: u32,
     pub frame_id: u32,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct QosPacket {
     packet_type: QosPacketType,
     #[deku(cond = "*packet_type == QosPacketType::ServerHandshake")]
@@ -107,4 +118,50 @@ mod tests {
         assert_eq!(get_value(none), 0x00);
         assert_eq!(get_value(reinitialize), 0x01);
     }
+
+    #[test]
+    fn qos_server_policy_little_endian() {
+        let mut data = vec![];
+        data.extend_from_slice(&1u32.to_le_bytes()); // schema_version
+        data.extend_from_slice(&0x0001_0203u32.to_le_bytes()); // policy_length
+        data.extend_from_slice(&2u32.to_le_bytes()); // fragment_count
+        data.extend_from_slice(&0x0400u32.to_le_bytes()); // offset
+        data.extend_from_slice(&0x0500u32.to_le_bytes()); // fragment_size
+
+        let (_, policy) = QosServerPolicy::from_bytes((&data, 0)).expect("Failed to parse policy");
+
+        assert_eq!(policy.schema_version, 1);
+        assert_eq!(policy.policy_length, 0x0001_0203);
+        assert_eq!(policy.fragment_count, 2);
+        assert_eq!(policy.offset, 0x0400);
+        assert_eq!(policy.fragment_size, 0x0500);
+        assert_eq!(policy.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn qos_handshake_little_endian() {
+        let (_, server_hs) = QosServerHandshake::from_bytes((&[1, 0, 0, 0, 0x02, 0x01, 0, 0], 0))
+            .expect("Failed to parse server handshake");
+        assert_eq!(server_hs.protocol_version, 1);
+        assert_eq!(server_hs.min_supported_client_version, Some(0x0102));
+
+        let client_hs = QosClientHandshake {
+            protocol_version: 1,
+            initial_frame_id: 0x0a0b_0c0d,
+        };
+        assert_eq!(
+            client_hs.to_bytes().unwrap(),
+            vec![1, 0, 0, 0, 0x0d, 0x0c, 0x0b, 0x0a]
+        );
+    }
+
+    #[test]
+    fn qos_packet_little_endian() {
+        let data = [4, 0, 0, 0, 0, 0, 0, 0, 0x78, 0x56, 0x34, 0x12];
+        let (_, packet) = QosPacket::from_bytes((&data, 0)).expect("Failed to parse packet");
+
+        assert_eq!(packet.packet_type, QosPacketType::Data);
+        assert_eq!(packet.data.as_ref().unwrap().frame_id, 0x1234_5678);
+        assert_eq!(packet.to_bytes().unwrap(), data);
+    }
 }
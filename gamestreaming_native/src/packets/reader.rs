@@ -0,0 +1,265 @@
+use std::io::{ErrorKind, Read};
+
+use deku::DekuContainerRead;
+use webrtc::rtp;
+use webrtc::util::Unmarshal;
+
+use super::frame::{FrameAssembler, VideoFrame};
+use super::video::VideoPacket;
+use super::{decode_rtp_payload, ParsedPacket, Result};
+
+/// Packet yielded by `PacketReader`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadPacket {
+    /// Packet decoded via `decode_rtp_payload`
+    Packet(ParsedPacket),
+    /// Video channel packet other than frame data, e.g. handshake / control
+    Video(VideoPacket),
+    /// Frame reassembled from all its video data fragments
+    VideoFrame(VideoFrame),
+}
+
+/// Reads RTP packets from a byte stream and decodes them
+///
+/// Packets are expected to be framed as per RFC 4571, e.g. each
+/// RTP packet is prefixed by its length as big-endian u16.
+///
+/// If the SSRC of the video channel is known (`with_video_ssrc`), its
+/// packets are decoded as `VideoPacket`s and the data fragments are
+/// reassembled, complete frames are yielded with the RTP header of the
+/// last fragment received.
+pub struct PacketReader<R: Read> {
+    reader: R,
+    video_ssrc: Option<u32>,
+    assembler: FrameAssembler,
+}
+
+impl<R: Read> PacketReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            video_ssrc: None,
+            assembler: FrameAssembler::new(),
+        }
+    }
+
+    /// Decode packets with `ssrc` as video channel packets
+    pub fn with_video_ssrc(mut self, ssrc: u32) -> Self {
+        self.video_ssrc = Some(ssrc);
+        self
+    }
+
+    /// Assembler of the video frames, e.g. to query dropped frames
+    pub fn assembler(&self) -> &FrameAssembler {
+        &self.assembler
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_frame(&mut self) -> Option<Result<Vec<u8>>> {
+        let mut length = [0u8; 2];
+        match self.reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(err) => return Some(Err(err.into())),
+        }
+
+        let mut frame = vec![0u8; u16::from_be_bytes(length) as usize];
+        Some(
+            self.reader
+                .read_exact(&mut frame)
+                .map(|_| frame)
+                .map_err(|err| err.into()),
+        )
+    }
+
+    /// Decode an RTP packet, `None` if it is a fragment of a frame
+    /// that is not complete yet
+    fn decode(&mut self, data: &[u8]) -> Result<Option<(rtp::header::Header, ReadPacket)>> {
+        let mut buf = data;
+        let packet = rtp::packet::Packet::unmarshal(&mut buf)?;
+
+        if Some(packet.header.ssrc) != self.video_ssrc {
+            let parsed = decode_rtp_payload(&packet.payload)?;
+            return Ok(Some((packet.header, ReadPacket::Packet(parsed))));
+        }
+
+        let (_, video) = VideoPacket::from_bytes((&packet.payload, 0))?;
+        let read = match &video.data {
+            Some(fragment) => match self.assembler.push(fragment)? {
+                Some(frame) => ReadPacket::VideoFrame(frame),
+                None => return Ok(None),
+            },
+            None => ReadPacket::Video(video),
+        };
+
+        Ok(Some((packet.header, read)))
+    }
+}
+
+impl<R: Read> Iterator for PacketReader<R> {
+    type Item = Result<(rtp::header::Header, ReadPacket)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let decoded = self.read_frame()?.and_then(|frame| self.decode(&frame));
+            match decoded {
+                Ok(Some(packet)) => return Some(Ok(packet)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use deku::DekuContainerWrite;
+
+    use super::*;
+    use crate::packets::udp_connection_probing::ConnectionProbingType;
+    use crate::packets::video::{VideoData, VideoDataFlags, VideoPacketType};
+    use crate::packets::PayloadType;
+
+    const VIDEO_SSRC: u32 = 1026;
+
+    fn framed_rtp(sequence_number: u16, payload: &[u8]) -> Vec<u8> {
+        framed_rtp_with_ssrc(sequence_number, 1024, payload)
+    }
+
+    fn framed_rtp_with_ssrc(sequence_number: u16, ssrc: u32, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, 0x61];
+        packet.extend_from_slice(&sequence_number.to_be_bytes());
+        // Timestamp
+        packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.extend_from_slice(payload);
+
+        let mut frame = (packet.len() as u16).to_be_bytes().to_vec();
+        frame.extend_from_slice(&packet);
+
+        frame
+    }
+
+    #[test]
+    fn read_packets() {
+        let mut data = framed_rtp(1, &[0x66, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&framed_rtp(2, &[0x65, 0xAA]));
+
+        let mut reader = PacketReader::new(&data[..]);
+
+        let (header, packet) = reader
+            .next()
+            .expect("No packet read")
+            .expect("Failed to decode packet");
+        assert_eq!(header.sequence_number, 1);
+        assert_eq!(header.ssrc, 1024);
+        match packet {
+            ReadPacket::Packet(ParsedPacket::UDPConnectionProbing(probing)) => {
+                assert_eq!(probing.packet_type, ConnectionProbingType::Ack);
+            }
+            _ => panic!("Unexpected packet: {:?}", packet),
+        }
+
+        let (header, packet) = reader
+            .next()
+            .expect("No packet read")
+            .expect("Failed to decode packet");
+        assert_eq!(header.sequence_number, 2);
+        assert_eq!(
+            packet,
+            ReadPacket::Packet(ParsedPacket::Unhandled(
+                PayloadType::UDPKeepAlive,
+                vec![0x65, 0xAA]
+            ))
+        );
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn read_truncated_packet() {
+        let data = framed_rtp(1, &[0x66, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00]);
+        let mut reader = PacketReader::new(&data[..data.len() - 1]);
+
+        assert!(reader.next().expect("No packet read").is_err());
+    }
+
+    fn video_data_packet(data: VideoData) -> Vec<u8> {
+        VideoPacket {
+            packet_type: VideoPacketType::Data,
+            server_handshake: None,
+            client_handshake: None,
+            control: None,
+            data: Some(data),
+        }
+        .to_bytes()
+        .unwrap()
+    }
+
+    fn fragment(offset: u32, data: &[u8]) -> VideoData {
+        VideoData {
+            unknown1: 1,
+            unknown2: 0,
+            flags: VideoDataFlags(0x04),
+            frame_id: 42,
+            timestamp: 1234,
+            packet_count: 2,
+            total_size: 6,
+            metadata_size: 0,
+            offset,
+            unknown3: 0,
+            data_size: data.len() as u32,
+            metadata: vec![],
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn read_video_frames() {
+        let handshake = include_bytes!("../../testdata/video_client_handshake.bin");
+        let mut data = framed_rtp_with_ssrc(1, VIDEO_SSRC, &handshake[12..]);
+        data.extend_from_slice(&framed_rtp_with_ssrc(
+            2,
+            VIDEO_SSRC,
+            &video_data_packet(fragment(3, &[4, 5, 6])),
+        ));
+        data.extend_from_slice(&framed_rtp(3, &[0x65, 0xAA]));
+        data.extend_from_slice(&framed_rtp_with_ssrc(
+            4,
+            VIDEO_SSRC,
+            &video_data_packet(fragment(0, &[1, 2, 3])),
+        ));
+
+        let mut reader = PacketReader::new(&data[..]).with_video_ssrc(VIDEO_SSRC);
+        let mut next = || reader.next().expect("No packet read").unwrap();
+
+        match next().1 {
+            ReadPacket::Video(packet) => assert!(packet.client_handshake.is_some()),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        // First fragment is held back until the frame is complete
+        let (header, packet) = next();
+        assert_eq!(header.sequence_number, 3);
+        assert!(matches!(
+            packet,
+            ReadPacket::Packet(ParsedPacket::Unhandled(..))
+        ));
+
+        let (header, packet) = next();
+        assert_eq!(header.sequence_number, 4);
+        match packet {
+            ReadPacket::VideoFrame(frame) => {
+                assert_eq!(frame.frame_id, 42);
+                assert_eq!(frame.timestamp, 1234);
+                assert_eq!(frame.data, vec![1, 2, 3, 4, 5, 6]);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+
+        assert!(reader.next().is_none());
+        assert_eq!(reader.assembler().dropped_frames(), 0);
+    }
+}
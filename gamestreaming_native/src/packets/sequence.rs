@@ -0,0 +1,152 @@
+/// Result of feeding a sequence number into `SequenceTracker`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// First packet, or the expected next sequence number
+    InOrder,
+    /// Packets from `first` to `last` (inclusive) were skipped
+    Gap { first: u16, last: u16 },
+    /// Packet arrived after a newer one, e.g. fills an earlier gap
+    Reordered,
+    /// Same sequence number as the newest received packet
+    Duplicate,
+}
+
+/// Tracks RTP sequence numbers to detect lost and reordered packets
+///
+/// Sequence numbers are extended to 64 bits to handle wrap around.
+/// A jump of less than half the sequence number space is treated as a
+/// gap, anything going backwards as reordering.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    /// Extended sequence number of the first packet
+    base: Option<u64>,
+    /// Extended sequence number of the newest packet
+    highest: u64,
+    received: u64,
+    reordered: u64,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the sequence number of a received packet
+    pub fn push(&mut self, sequence_number: u16) -> SequenceEvent {
+        if self.base.is_none() {
+            // Start out in the second cycle, so reordered packets
+            // right after the first one don't underflow
+            let extended = (1 << 16) | sequence_number as u64;
+            self.base = Some(extended);
+            self.highest = extended;
+            self.received = 1;
+            return SequenceEvent::InOrder;
+        }
+
+        let delta = sequence_number.wrapping_sub(self.highest as u16) as i16;
+        match delta {
+            0 => SequenceEvent::Duplicate,
+            1 => {
+                self.highest += 1;
+                self.received += 1;
+                SequenceEvent::InOrder
+            }
+            delta if delta > 1 => {
+                let first = (self.highest as u16).wrapping_add(1);
+                self.highest += delta as u64;
+                self.received += 1;
+                SequenceEvent::Gap {
+                    first,
+                    last: sequence_number.wrapping_sub(1),
+                }
+            }
+            _ => {
+                self.received += 1;
+                self.reordered += 1;
+                SequenceEvent::Reordered
+            }
+        }
+    }
+
+    /// Newest sequence number received
+    pub fn highest(&self) -> Option<u16> {
+        self.base.map(|_| self.highest as u16)
+    }
+
+    /// Count of packets expected, based on the first and newest sequence number
+    pub fn expected(&self) -> u64 {
+        match self.base {
+            Some(base) => self.highest - base + 1,
+            None => 0,
+        }
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Count of packets lost, packets arriving late are not counted
+    ///
+    /// NOTE: Like RFC 3550 cumulative loss, packets received before the
+    /// first one are counted as received, so this may undercount.
+    pub fn lost(&self) -> u64 {
+        self.expected().saturating_sub(self.received)
+    }
+
+    pub fn reordered(&self) -> u64 {
+        self.reordered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_gaps_and_reordering() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.highest(), None);
+
+        assert_eq!(tracker.push(100), SequenceEvent::InOrder);
+        assert_eq!(tracker.push(101), SequenceEvent::InOrder);
+        assert_eq!(
+            tracker.push(105),
+            SequenceEvent::Gap {
+                first: 102,
+                last: 104
+            }
+        );
+        assert_eq!(tracker.lost(), 3);
+
+        assert_eq!(tracker.push(103), SequenceEvent::Reordered);
+        assert_eq!(tracker.push(105), SequenceEvent::Duplicate);
+        assert_eq!(tracker.push(106), SequenceEvent::InOrder);
+
+        assert_eq!(tracker.highest(), Some(106));
+        assert_eq!(tracker.expected(), 7);
+        assert_eq!(tracker.received(), 5);
+        assert_eq!(tracker.lost(), 2);
+        assert_eq!(tracker.reordered(), 1);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut tracker = SequenceTracker::new();
+
+        assert_eq!(tracker.push(0xfffe), SequenceEvent::InOrder);
+        assert_eq!(tracker.push(0xffff), SequenceEvent::InOrder);
+        assert_eq!(tracker.push(0x0000), SequenceEvent::InOrder);
+        assert_eq!(
+            tracker.push(0x0003),
+            SequenceEvent::Gap {
+                first: 0x0001,
+                last: 0x0002
+            }
+        );
+        assert_eq!(tracker.push(0xfffd), SequenceEvent::Reordered);
+
+        assert_eq!(tracker.highest(), Some(0x0003));
+        assert_eq!(tracker.expected(), 6);
+        assert_eq!(tracker.lost(), 1);
+    }
+}
@@ -1,6 +1,9 @@
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u16")]
 pub enum ConnectionProbingType {
     Syn = 1,
@@ -8,6 +11,7 @@ pub enum ConnectionProbingType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ConnectionProbingSyn {
     // TODO: Implement deku(until = "")
     // We likely have to pass the total packet size here as ctx
@@ -20,12 +24,14 @@ pub struct ConnectionProbingSyn {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ConnectionProbingAck {
     pub accepted_packet_size: u16,
     pub appendix: u16,
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ConnectionProbingPacket {
     pub packet_type: ConnectionProbingType,
     #[deku(cond = "*packet_type == ConnectionProbingType::Syn")]
@@ -0,0 +1,63 @@
+use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// URCP (UDP rate control protocol) control message
+///
+/// URCP is the congestion control transport underneath the mux layer,
+/// the receiver periodically reports its state back to the sender.
+///
+/// NOTE: Provisional, the field layout is a guess that has not been
+/// checked against captured traffic. `decode_packet` does not use it and
+/// returns URCP control packets as `ParsedPacket::Unhandled`, parse them
+/// explicitly via `URCPControlPacket::from_bytes` if needed.
+#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct URCPControlPacket {
+    /// Highest RTP sequence number received
+    pub ack_sequence: u16,
+    /// Number of packets received since the previous control message
+    pub ack_count: u16,
+    /// Receive window in bytes
+    pub window: u32,
+    /// Smoothed round trip time in microseconds
+    pub rtt: u32,
+    /// Estimated receive rate in bytes per second
+    pub rate: u32,
+}
+
+/// URCP dummy packet, padding sent to probe the available bandwidth
+///
+/// The padding has no meaning and takes up the rest of the RTP payload,
+/// it is kept to allow re-serializing the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct URCPDummyPacket {
+    pub padding: Vec<u8>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_urcp_control() {
+        let mut buf = vec![];
+        buf.extend_from_slice(&0x1234u16.to_le_bytes()); // ack_sequence
+        buf.extend_from_slice(&8u16.to_le_bytes()); // ack_count
+        buf.extend_from_slice(&65536u32.to_le_bytes()); // window
+        buf.extend_from_slice(&25_000u32.to_le_bytes()); // rtt
+        buf.extend_from_slice(&1_250_000u32.to_le_bytes()); // rate
+
+        let (rest, packet) =
+            URCPControlPacket::from_bytes((&buf, 0)).expect("Failed to parse packet");
+
+        assert!(rest.0.is_empty());
+        assert_eq!(packet.ack_sequence, 0x1234);
+        assert_eq!(packet.ack_count, 8);
+        assert_eq!(packet.window, 65536);
+        assert_eq!(packet.rtt, 25_000);
+        assert_eq!(packet.rate, 1_250_000);
+        assert_eq!(packet.to_bytes().unwrap(), buf);
+    }
+}
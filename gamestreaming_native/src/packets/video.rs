@@ -1,6 +1,11 @@
+use std::io::{self, Read};
+
 use deku::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum VideoPacketType {
     ServerHandshake = 1,
@@ -10,6 +15,7 @@ pub enum VideoPacketType {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[deku(type = "u32")]
 pub enum VideoCodec {
     H264 = 0,
@@ -19,6 +25,7 @@ pub enum VideoCodec {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoControlFlags {
     /// Packet contains last displayed frame rendered
     /// Bit 31 / Mask LE 0x80000000 BE 0x80
@@ -58,23 +65,59 @@ pub struct VideoControlFlags {
     pub bitrate_update: bool,
     /// Packet contains video format change
     /// Bit 17 / Mask LE 0x00020000 BE 0x200
-    #[deku(pad_bits_after = "16", bits = "1")]
+    #[deku(pad_bits_after = "17", bits = "1")]
     pub video_format_change: bool,
 }
 
-#[derive(Debug, Clone, DekuRead, DekuWrite, Eq, PartialEq, Default)]
-pub struct VideoDataFlags {
-    /// Jitter info
-    /// Bit 28 / Mask LE 0x10000000 BE 0x10
-    #[deku(pad_bits_before = "3", bits = "1")]
-    pub jitter_info: bool,
+/// Flags of a video data packet
+///
+/// Stored as raw value, so bits of unknown meaning survive
+/// a parse / serialize round-trip.
+/// NOTE: Captured frames (testdata/video_data*.bin) have flags 0x04 set,
+/// which is neither jitter info nor hashed.
+#[derive(Debug, Clone, Copy, DekuRead, DekuWrite, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct VideoDataFlags(pub u32);
+
+impl VideoDataFlags {
     /// Hashed
     /// Bit 27 / Mask LE 0x08000000 BE 0x08
-    #[deku(pad_bits_after = "26", bits = "1")]
-    pub hashed: bool,
+    pub const HASHED: u32 = 0x08;
+    /// Jitter info
+    /// Bit 28 / Mask LE 0x10000000 BE 0x10
+    pub const JITTER_INFO: u32 = 0x10;
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn jitter_info(&self) -> bool {
+        self.0 & Self::JITTER_INFO != 0
+    }
+
+    pub fn set_jitter_info(&mut self, value: bool) {
+        self.set(Self::JITTER_INFO, value)
+    }
+
+    pub fn hashed(&self) -> bool {
+        self.0 & Self::HASHED != 0
+    }
+
+    pub fn set_hashed(&mut self, value: bool) {
+        self.set(Self::HASHED, value)
+    }
+
+    fn set(&mut self, mask: u32, value: bool) {
+        if value {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RGBVideoFormat {
     pub bpp: u32,
     pub unknown: u32,
@@ -84,6 +127,7 @@ pub struct RGBVideoFormat {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoFormat {
     pub fps: u32,
     pub width: u32,
@@ -94,6 +138,7 @@ pub struct VideoFormat {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoServerHandshake {
     pub unknown1: u32,
     pub unknown2: u32,
@@ -109,6 +154,7 @@ pub struct VideoServerHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoClientHandshake {
     pub unknown1: u32,
     pub unknown2: u32,
@@ -117,6 +163,7 @@ pub struct VideoClientHandshake {
 }
 
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoControl {
     pub flags: VideoControlFlags,
     // Tuple
@@ -135,7 +182,28 @@ pub struct VideoControl {
     pub smooth_rendering_settings: Option<(u64, u64, u64)>,
 }
 
+impl VideoControl {
+    /// Control packet reporting the frames `first` to `last` (inclusive) as lost
+    ///
+    /// Gaps can be detected via `SequenceTracker`.
+    pub fn report_lost_frames(first: u32, last: u32) -> Self {
+        Self {
+            flags: VideoControlFlags {
+                lost_frames: true,
+                ..Default::default()
+            },
+            last_displayed_frame: None,
+            queue_depth: None,
+            lost_frames: Some((first, last)),
+            bitrate_update: None,
+            video_format_update: None,
+            smooth_rendering_settings: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoData {
     pub unknown1: u32,
     pub unknown2: u32,
@@ -146,14 +214,89 @@ pub struct VideoData {
     pub total_size: u32,
     pub metadata_size: u32,
     pub offset: u32,
+    /// Length of `metadata`, only present in the first packet of a frame
+    #[deku(update = "self.metadata.len()")]
     pub unknown3: u32,
     #[deku(update = "self.data.len()")]
     pub data_size: u32,
+    #[deku(count = "unknown3")]
+    pub metadata: Vec<u8>,
     #[deku(count = "data_size")]
     pub data: Vec<u8>,
 }
 
+/// Fixed size fields of `VideoData`, preceding metadata and data
+#[derive(Debug, Clone, DekuRead, DekuWrite, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct VideoDataHeader {
+    pub unknown1: u32,
+    pub unknown2: u32,
+    pub flags: VideoDataFlags,
+    pub frame_id: u32,
+    pub timestamp: u64,
+    pub packet_count: u32,
+    pub total_size: u32,
+    pub metadata_size: u32,
+    pub offset: u32,
+    /// Length of the metadata, only non-zero in the first packet of a frame
+    pub unknown3: u32,
+    pub data_size: u32,
+}
+
+impl VideoDataHeader {
+    pub const SIZE: usize = 48;
+}
+
+/// Streaming counterpart of `VideoData`
+///
+/// Parses the header and (small) metadata up front, the frame data
+/// is exposed via `Read` and limited to `data_size`, so it can be
+/// handed to a decoder without buffering the whole packet.
+pub struct VideoDataReader<R: Read> {
+    pub header: VideoDataHeader,
+    pub metadata: Vec<u8>,
+    data: io::Take<R>,
+}
+
+impl<R: Read> VideoDataReader<R> {
+    /// Start reading a video data packet, `reader` is expected to be
+    /// positioned right after the `VideoPacketType::Data` packet type.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; VideoDataHeader::SIZE];
+        reader.read_exact(&mut header)?;
+        let (_, header) = VideoDataHeader::from_bytes((&header, 0))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        let mut metadata = vec![0u8; header.unknown3 as usize];
+        reader.read_exact(&mut metadata)?;
+
+        let data = reader.take(header.data_size as u64);
+        Ok(Self {
+            header,
+            metadata,
+            data,
+        })
+    }
+
+    /// Number of frame data bytes not read yet
+    pub fn remaining(&self) -> u64 {
+        self.data.limit()
+    }
+
+    /// Return the underlying reader, positioned after the consumed data
+    pub fn into_inner(self) -> R {
+        self.data.into_inner()
+    }
+}
+
+impl<R: Read> Read for VideoDataReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
 #[derive(Debug, DekuRead, DekuWrite, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VideoPacket {
     pub packet_type: VideoPacketType,
     #[deku(cond = "*packet_type == VideoPacketType::ServerHandshake")]
@@ -233,17 +376,21 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn deserialize_video_data() {
         let data = include_bytes!("../../testdata/video_data.bin");
+        // Captured payload is followed by 2 bytes of channel framing
+        let payload = &data[12..data.len() - 2];
         let (rest, packet) =
-            VideoPacket::from_bytes((&data[12..], 0)).expect("Failed to parse VideoData packet");
+            VideoPacket::from_bytes((payload, 0)).expect("Failed to parse VideoData packet");
 
+        assert_eq!(rest.0.len(), 0);
         assert_eq!(rest.1, 0);
 
-        let video_data = packet.data.expect("Data not parsed");
+        let video_data = packet.data.as_ref().expect("Data not parsed");
 
-        // assert_eq!(video_data.flags.);
+        assert_eq!(video_data.flags.bits(), 0x04);
+        assert!(!video_data.flags.jitter_info());
+        assert!(!video_data.flags.hashed());
         assert_eq!(video_data.frame_id, 1808917930);
         assert_eq!(video_data.timestamp, 3177068);
         assert_eq!(video_data.packet_count, 9);
@@ -251,8 +398,92 @@ mod test {
         assert_eq!(video_data.metadata_size, 9);
         assert_eq!(video_data.offset, 0);
         assert_eq!(video_data.unknown3, 9);
+        assert_eq!(video_data.metadata.len(), 9);
         assert_eq!(video_data.data_size, 1245);
         assert_eq!(video_data.data.len(), 1245);
+        // H.264 Annex B start code
+        assert_eq!(video_data.data[..4], [0x00, 0x00, 0x00, 0x01]);
+
+        let serialized = packet
+            .to_bytes()
+            .expect("Failed to serialize VideoData packet");
+        assert_eq!(serialized, payload);
+    }
+
+    #[test]
+    fn deserialize_video_data_continuation() {
+        let data = include_bytes!("../../testdata/video_data2.bin");
+        let (rest, packet) = VideoPacket::from_bytes((&data[12..data.len() - 2], 0))
+            .expect("Failed to parse VideoData packet");
+
+        assert_eq!(rest.0.len(), 0);
+
+        let video_data = packet.data.expect("Data not parsed");
+
+        assert_eq!(video_data.frame_id, 1808917930);
+        assert_eq!(video_data.offset, 1254);
+        assert_eq!(video_data.unknown3, 0);
+        assert!(video_data.metadata.is_empty());
+        assert_eq!(video_data.data.len(), 1254);
+    }
+
+    #[test]
+    fn read_video_data_streaming() {
+        let data = include_bytes!("../../testdata/video_data.bin");
+        // Skip the packet type, keep the trailing channel framing
+        let payload = &data[16..];
+
+        let mut reader = VideoDataReader::new(payload).expect("Failed to read VideoData header");
+        assert_eq!(reader.header.frame_id, 1808917930);
+        assert_eq!(reader.header.flags.bits(), 0x04);
+        assert_eq!(reader.header.data_size, 1245);
+        assert_eq!(reader.metadata.len(), 9);
+        assert_eq!(reader.remaining(), 1245);
+
+        let mut start_code = [0u8; 4];
+        reader.read_exact(&mut start_code).unwrap();
+        assert_eq!(start_code, [0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(reader.remaining(), 1241);
+
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest.len(), 1241);
+        assert_eq!(reader.into_inner().len(), 2);
+
+        let (_, packet) = VideoPacket::from_bytes((&data[12..data.len() - 2], 0)).unwrap();
+        assert_eq!(&packet.data.unwrap().data[4..], &rest[..]);
+    }
+
+    #[test]
+    fn read_video_data_truncated() {
+        let data = include_bytes!("../../testdata/video_data.bin");
+
+        assert!(VideoDataReader::new(&data[16..40]).is_err());
+    }
+
+    #[test]
+    fn video_data_flags_round_trip() {
+        fn create_flag(val: u32) -> VideoDataFlags {
+            let val_bytes: [u8; 4] = val.to_le_bytes();
+            let (_, flags) =
+                VideoDataFlags::from_bytes((&val_bytes, 0)).expect("Failed to create flags");
+
+            flags
+        }
+
+        assert!(create_flag(0x10).jitter_info());
+        assert!(!create_flag(0x10).hashed());
+        assert!(create_flag(0x08).hashed());
+        assert!(!create_flag(0x08).jitter_info());
+
+        let mut flags = create_flag(0x04);
+        flags.set_jitter_info(true);
+        flags.set_hashed(true);
+        assert_eq!(flags.bits(), 0x1C);
+        assert_eq!(flags.to_bytes().unwrap(), vec![0x1C, 0x00, 0x00, 0x00]);
+
+        flags.set_hashed(false);
+        assert_eq!(flags.bits(), 0x14);
     }
 
     #[test]
@@ -276,6 +507,26 @@ mod test {
         assert!(create_flag(0x1000).smooth_rendering_settings_sent);
     }
 
+    #[test]
+    fn parse_video_control_flags_length() {
+        // video_format_change followed by one byte of the next field
+        let data = [0x00, 0x02, 0x00, 0x00, 0xAB];
+        let ((rest, bit_offset), flags) =
+            VideoControlFlags::from_bytes((&data, 0)).expect("Failed to parse flags");
+
+        assert_eq!(
+            flags,
+            VideoControlFlags {
+                video_format_change: true,
+                ..Default::default()
+            }
+        );
+        // Flags span exactly 32 bits
+        assert_eq!(rest, &[0xAB]);
+        assert_eq!(bit_offset, 0);
+        assert_eq!(flags.to_bytes().unwrap(), &data[..4]);
+    }
+
     #[test]
     fn serialize_video_control_flags() {
         fn get_value(flags: VideoControlFlags) -> u32 {
@@ -340,4 +591,17 @@ mod test {
         assert_eq!(get_value(bitrate_update), 0x400);
         assert_eq!(get_value(smooth_rendering_settings_sent), 0x1000);
     }
+
+    #[test]
+    fn serialize_report_lost_frames() {
+        let control = VideoControl::report_lost_frames(5, 7);
+        let bytes = control.to_bytes().expect("Failed to serialize");
+        assert_eq!(
+            bytes,
+            vec![0x02, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00]
+        );
+
+        let (_, parsed) = VideoControl::from_bytes((&bytes, 0)).expect("Failed to parse");
+        assert_eq!(parsed, control);
+    }
 }
@@ -0,0 +1,206 @@
+//! Stream the first available xCloud title into a window
+//!
+//! Video is decoded in software (`decode` feature) and drawn via minifb,
+//! no GStreamer needed. Audio and input are not handled.
+//!
+//! Run with: cargo run --example play-window --features decode
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use minifb::{Key, ScaleMode, Window, WindowOptions};
+use tokio::sync::watch;
+use webrtc::api::interceptor_registry::register_default_interceptors;
+use webrtc::api::media_engine::MediaEngine;
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::interceptor::registry::Registry;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtp_transceiver::rtp_codec::RTPCodecType;
+use webrtc::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
+use webrtc::rtp_transceiver::RTCRtpTransceiverInit;
+
+use gamestreaming_webrtc::api::SessionResponse;
+use gamestreaming_webrtc::decode::{decoder_sink, DecodedFrame};
+use gamestreaming_webrtc::{ChannelType, GamestreamingClient, Platform};
+use xal::utils::TokenStore;
+
+const TOKENS_FILEPATH: &str = "tokens.json";
+
+/// Decoded frames waiting to be drawn, older ones are dropped if
+/// drawing can't keep up
+const FRAME_QUEUE_LEN: usize = 2;
+
+type Error = Box<dyn std::error::Error>;
+
+struct Stream {
+    xcloud: GamestreamingClient,
+    session: SessionResponse,
+    peer_connection: Arc<RTCPeerConnection>,
+    shutdown_tx: watch::Sender<bool>,
+}
+
+async fn create_peer_connection() -> Result<RTCPeerConnection, webrtc::Error> {
+    let config = RTCConfiguration {
+        ice_servers: vec![RTCIceServer {
+            urls: vec!["stun:stun.l.google.com:19302".to_owned()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Default codecs include H264 and Opus
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    let registry = register_default_interceptors(Registry::new(), &mut m)?;
+
+    let api = APIBuilder::new()
+        .with_media_engine(m)
+        .with_interceptor_registry(registry)
+        .build();
+
+    api.new_peer_connection(config).await
+}
+
+async fn start_stream(frame_tx: SyncSender<DecodedFrame>) -> Result<Stream, Error> {
+    let ts = TokenStore::load(TOKENS_FILEPATH)?;
+    let mut xcloud = GamestreamingClient::create(
+        Platform::Cloud,
+        &ts.gssv_token.token_data.token,
+        &ts.xcloud_transfer_token.lpt,
+    )
+    .await?;
+    if xcloud.allows_region_selection() {
+        xcloud.select_best_region().await?;
+    }
+
+    let title = xcloud
+        .lookup_games()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or("No titles received from API")?;
+    println!("Starting title: {}", title.title_id);
+    let session = xcloud.start_stream_xcloud(&title.title_id).await?;
+
+    let peer_connection = Arc::new(create_peer_connection().await?);
+    xcloud.observe_peer_connection(&peer_connection).await;
+
+    for channel_type in ChannelType::DATA_CHANNELS {
+        let params = channel_type
+            .params()
+            .expect("Data channel without parameters");
+        let channel = peer_connection
+            .create_data_channel(
+                channel_type.name(),
+                Some(RTCDataChannelInit {
+                    ordered: params.is_ordered,
+                    protocol: Some(params.protocol.to_owned()),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        xcloud.observe_data_channel(channel_type, &channel).await;
+    }
+
+    for (kind, direction) in [
+        (RTPCodecType::Audio, RTCRtpTransceiverDirection::Sendrecv),
+        (RTPCodecType::Video, RTCRtpTransceiverDirection::Recvonly),
+    ] {
+        peer_connection
+            .add_transceiver_from_kind(
+                kind,
+                &[RTCRtpTransceiverInit {
+                    direction,
+                    send_encodings: vec![],
+                }],
+            )
+            .await?;
+    }
+
+    xcloud
+        .on_video_frame(decoder_sink(Box::new(move |frame: DecodedFrame| {
+            // Window is closed if the receiver is gone, a full queue drops the frame
+            let _ = frame_tx.try_send(frame);
+            Box::pin(async {})
+        })))
+        .await;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    xcloud.observe_tracks(&peer_connection, shutdown_rx).await;
+
+    xcloud.negotiate(&session, &peer_connection).await?;
+    tokio::select! {
+        _ = xcloud.wait_until_ready() => println!("Connected"),
+        _ = tokio::time::sleep(Duration::from_secs(10)) => {
+            println!("Timeout waiting for data channels");
+        }
+    };
+
+    Ok(Stream {
+        xcloud,
+        session,
+        peer_connection,
+        shutdown_tx,
+    })
+}
+
+async fn stop_stream(stream: Stream) -> Result<(), Error> {
+    let _ = stream.shutdown_tx.send(true);
+    stream.xcloud.wait_for_tracks().await;
+    stream.peer_connection.close().await?;
+    stream
+        .xcloud
+        .logout(std::slice::from_ref(&stream.session))
+        .await?;
+
+    Ok(())
+}
+
+/// Draw frames until the window is closed or escape is pressed
+///
+/// Runs on the main thread, as some platforms require it for windowing.
+fn show_frames(frame_rx: Receiver<DecodedFrame>) -> Result<(), Error> {
+    let mut window = Window::new(
+        "xCloud",
+        1280,
+        720,
+        WindowOptions {
+            resize: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..Default::default()
+        },
+    )?;
+    window.limit_update_rate(Some(Duration::from_millis(16)));
+
+    let mut buffer: Vec<u32> = vec![];
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        match frame_rx.try_iter().last() {
+            Some(frame) => {
+                // minifb expects 0RGB pixels
+                buffer.clear();
+                buffer.extend(frame.rgb.chunks_exact(3).map(|px| {
+                    (u32::from(px[0]) << 16) | (u32::from(px[1]) << 8) | u32::from(px[2])
+                }));
+                window.update_with_buffer(&buffer, frame.width, frame.height)?;
+            }
+            None => window.update(),
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let (frame_tx, frame_rx) = mpsc::sync_channel(FRAME_QUEUE_LEN);
+
+    let stream = runtime.block_on(start_stream(frame_tx))?;
+    let result = show_frames(frame_rx);
+
+    println!("Stopping stream");
+    runtime.block_on(stop_stream(stream))?;
+
+    result
+}
@@ -1,7 +1,12 @@
-use reqwest::{header, header::HeaderMap, Client, ClientBuilder, StatusCode, Url};
+use reqwest::{
+    header, header::HeaderMap, Client, Method, RequestBuilder, Response, StatusCode, Url,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_aux::prelude::*;
 use serde_json;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,41 +15,178 @@ pub enum GssvApiError {
     HttpError(#[from] reqwest::Error),
     #[error(transparent)]
     Serialization(#[from] serde_json::error::Error),
+    /// Response body did not match the expected schema,
+    /// `body` holds the (truncated) payload for diagnostics
+    #[error("Failed to deserialize response: {source}, body: {body}")]
+    Deserialization {
+        source: serde_json::error::Error,
+        body: String,
+    },
+    /// Token was rejected (401 / 403), a token refresh is required
+    #[error("Unauthorized ({0}), token refresh required")]
+    Unauthorized(StatusCode),
+    #[error("Rate limited by server")]
+    RateLimited,
+    #[error("Unexpected response status {status}: {body}")]
+    UnexpectedStatus { status: StatusCode, body: String },
+    #[error("Region {0} has no network test hostname")]
+    NoNetworkTestHost(String),
+    #[error("Offering has no regions")]
+    NoRegions,
+    #[error("Unknown region {0}")]
+    UnknownRegion(String),
+    #[error("Offering does not allow region selection")]
+    RegionSelectionDisabled,
+    #[error("Invalid URL {url}: {reason}")]
+    InvalidUrl { url: String, reason: String },
+    /// Token can't be used as header value, e.g. contains a newline
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] header::InvalidHeaderValue),
     #[error("Unknown error")]
     Unknown,
 }
 
+impl GssvApiError {
+    fn from_status(status: StatusCode, body: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => GssvApiError::Unauthorized(status),
+            StatusCode::TOO_MANY_REQUESTS => GssvApiError::RateLimited,
+            _ => GssvApiError::UnexpectedStatus { status, body },
+        }
+    }
+
+    /// Build error from a response with unexpected status, consumes the body
+    async fn from_response(resp: Response) -> Self {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Self::from_status(status, body)
+    }
+}
+
+/// Pass through successful responses, map everything else into `GssvApiError`
+async fn check_status(resp: Response) -> Result<Response, GssvApiError> {
+    if resp.status().is_success() {
+        Ok(resp)
+    } else {
+        tracing::warn!(
+            target: "gssv::api",
+            status = %resp.status(),
+            url = %resp.url(),
+            "Request failed"
+        );
+        Err(GssvApiError::from_response(resp).await)
+    }
+}
+
+/// Parse `url`, e.g. a base URI from the login response
+fn parse_url(url: &str) -> Result<Url, GssvApiError> {
+    Url::parse(url).map_err(|err| GssvApiError::InvalidUrl {
+        url: url.to_owned(),
+        reason: err.to_string(),
+    })
+}
+
+/// Maximum number of response body bytes kept in `GssvApiError::Deserialization`
+const MAX_ERROR_BODY_LEN: usize = 4096;
+
+/// Parse a response body, keeping the raw text around if it does not match
+fn parse_json<T: DeserializeOwned>(body: &str) -> Result<T, GssvApiError> {
+    serde_json::from_str(body).map_err(|source| {
+        let mut end = body.len().min(MAX_ERROR_BODY_LEN);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut truncated = body[..end].to_string();
+        if end < body.len() {
+            truncated.push_str("...");
+        }
+        GssvApiError::Deserialization {
+            source,
+            body: truncated,
+        }
+    })
+}
+
+/// Read the response body and deserialize it as JSON
+async fn read_json<T: DeserializeOwned>(resp: Response) -> Result<T, GssvApiError> {
+    let body = resp.text().await.map_err(GssvApiError::HttpError)?;
+    parse_json(&body)
+}
+
 /// Gamestreaming API Client
+#[derive(Clone)]
 pub struct GssvApi {
     client: Client,
+    auth_value: header::HeaderValue,
     base_url: Url,
+    regions: Vec<OfferingRegion>,
+    allow_region_selection: bool,
+    /// Added to every request, overriding headers set by the crate
+    default_headers: HeaderMap,
     pub platform: &'static str,
 }
 
 impl GssvApi {
-    fn new(base_url: Url, gssv_token: &str, platform: &'static str) -> Self {
-        let mut headers = header::HeaderMap::new();
-
-        let mut auth_value = header::HeaderValue::from_str(&format!("Bearer {}", gssv_token))
-            .expect("Failed assembling auth header");
+    /// Number of probes sent by `network_test`
+    const NETWORK_TEST_PROBES: usize = 5;
+    const NETWORK_TEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub(crate) fn new(
+        client: Client,
+        base_url: Url,
+        gssv_token: &str,
+        platform: &'static str,
+    ) -> Result<Self, GssvApiError> {
+        let mut auth_value = header::HeaderValue::from_str(&format!("Bearer {}", gssv_token))?;
         auth_value.set_sensitive(true);
-        headers.insert(header::AUTHORIZATION, auth_value);
 
-        Self {
-            client: ClientBuilder::new()
-                .default_headers(headers)
-                .build()
-                .expect("Failed to build client"),
+        Ok(Self {
+            client,
+            auth_value,
             base_url,
+            regions: vec![],
+            allow_region_selection: false,
+            default_headers: HeaderMap::new(),
             platform,
-        }
+        })
+    }
+
+    /// Headers added to every request, e.g. `x-gssv-client` to match
+    /// a specific client build.
+    ///
+    /// They take precedence over headers set by the crate itself.
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.default_headers
+    }
+
+    pub fn default_headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.default_headers
     }
 
-    async fn login(offering_id: &str, token: &str) -> Result<LoginResponse, GssvApiError> {
-        let login_url = format!(
+    pub fn set_default_headers(&mut self, headers: HeaderMap) {
+        self.default_headers = headers;
+    }
+
+    /// Copy of the client sending `headers` with every request, on top
+    /// of the default headers and overriding them
+    ///
+    /// Meant for changing headers of single calls, e.g.
+    /// `api.with_headers(headers).get_consoles().await`.
+    pub fn with_headers(&self, headers: HeaderMap) -> Self {
+        let mut api = self.clone();
+        api.default_headers.extend(headers);
+        api
+    }
+
+    async fn login(
+        client: &Client,
+        offering_id: &str,
+        token: &str,
+    ) -> Result<LoginResponse, GssvApiError> {
+        let login_url = parse_url(&format!(
             "https://{}.gssv-play-prod.xboxlive.com/v2/login/user",
             offering_id
-        );
+        ))?;
         let mut headers = HeaderMap::new();
         headers.insert(
             "x-gssv-client",
@@ -53,8 +195,7 @@ impl GssvApi {
                 .map_err(|_| GssvApiError::Unknown)?,
         );
 
-        let client = reqwest::Client::new();
-        client
+        let resp = client
             .post(login_url)
             .headers(headers)
             .json(&LoginRequest {
@@ -63,59 +204,249 @@ impl GssvApi {
             })
             .send()
             .await
-            .map_err(GssvApiError::HttpError)?
-            .error_for_status()?
-            .json::<LoginResponse>()
-            .await
-            .map_err(GssvApiError::HttpError)
+            .map_err(GssvApiError::HttpError)?;
+
+        read_json(check_status(resp).await?).await
+    }
+
+    async fn login_platform(
+        client: Client,
+        offering_id: &str,
+        platform: &'static str,
+        token: &str,
+    ) -> Result<Self, GssvApiError> {
+        let resp = GssvApi::login(&client, offering_id, token).await?;
+        let region = resp
+            .offering_settings
+            .default_region()
+            .ok_or(GssvApiError::NoRegions)?;
+
+        let mut api = Self::new(
+            client,
+            parse_url(&region.base_uri)?,
+            &resp.gs_token,
+            platform,
+        )?;
+        api.regions = resp.offering_settings.regions;
+        api.allow_region_selection = resp.offering_settings.allow_region_selection;
+        tracing::info!(target: "gssv::api", offering_id, region = %region.name, "Logged in");
+
+        Ok(api)
     }
 
     pub async fn login_xhome(token: &str) -> Result<Self, GssvApiError> {
-        let resp = GssvApi::login("xhome", token).await?;
+        GssvApi::login_xhome_with_client(Client::new(), token).await
+    }
 
-        Ok(Self::new(
-            Url::parse(&resp.offering_settings.regions.first().unwrap().base_uri).unwrap(),
-            &resp.gs_token,
-            "home",
-        ))
+    /// Login to xHome, using `client` for login and all further requests,
+    /// e.g. to apply proxy or TLS settings.
+    pub async fn login_xhome_with_client(
+        client: Client,
+        token: &str,
+    ) -> Result<Self, GssvApiError> {
+        GssvApi::login_platform(client, "xhome", "home", token).await
     }
 
     pub async fn login_xcloud(token: &str) -> Result<Self, GssvApiError> {
-        let resp = GssvApi::login("xgpuweb", token).await?;
+        GssvApi::login_xcloud_with_client(Client::new(), token).await
+    }
 
-        Ok(Self::new(
-            Url::parse(&resp.offering_settings.regions.first().unwrap().base_uri).unwrap(),
-            &resp.gs_token,
-            "cloud",
-        ))
+    /// Login to xCloud, using `client` for login and all further requests,
+    /// e.g. to apply proxy or TLS settings.
+    pub async fn login_xcloud_with_client(
+        client: Client,
+        token: &str,
+    ) -> Result<Self, GssvApiError> {
+        GssvApi::login_platform(client, "xgpuweb", "cloud", token).await
     }
 
-    fn url(&self, path: &str) -> Url {
-        self.base_url.join(path).unwrap()
+    /// Regions available for the offering, as returned on login
+    pub fn regions(&self) -> &[OfferingRegion] {
+        &self.regions
     }
 
-    fn session_url(&self, session: &SessionResponse, path: &str) -> Url {
-        let path = session.session_path.to_owned() + path;
-        self.base_url.join(&path).expect("Failed to join string")
+    /// Regions ordered by preference: the default region first,
+    /// followed by the others by ascending `fallback_priority`
+    pub fn list_regions(&self) -> Vec<&OfferingRegion> {
+        order_regions(&self.regions)
     }
 
-    async fn get_json<T>(&self, url: Url, headers: Option<HeaderMap>) -> Result<T, GssvApiError>
-    where
-        T: DeserializeOwned,
-    {
-        let mut req = self.client.get(url);
+    /// Whether the offering allows sending requests to other
+    /// regions than the default one
+    pub fn allows_region_selection(&self) -> bool {
+        self.allow_region_selection
+    }
+
+    /// Send all further requests to the region named `name`
+    /// (case-insensitive), instead of the default region.
+    ///
+    /// Fails if the offering does not allow region selection.
+    pub fn with_region(mut self, name: &str) -> Result<Self, GssvApiError> {
+        let region = self
+            .regions
+            .iter()
+            .find(|region| region.name.eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| GssvApiError::UnknownRegion(name.to_owned()))?;
+        self.set_region(&region)?;
+
+        Ok(self)
+    }
+
+    /// Send all further requests to `region`
+    ///
+    /// Fails if the offering does not allow region selection.
+    pub fn set_region(&mut self, region: &OfferingRegion) -> Result<(), GssvApiError> {
+        if !self.allow_region_selection {
+            return Err(GssvApiError::RegionSelectionDisabled);
+        }
+
+        self.base_url = parse_url(&region.base_uri)?;
+        Ok(())
+    }
+
+    /// Measure round trip time and loss against the region's
+    /// network test host.
+    ///
+    /// Any HTTP response counts as a successful probe, whatever its
+    /// status: the host is only used as echo, it may well answer HEAD
+    /// with 4xx. Only probes failing on the transport (timeout,
+    /// connection errors) are counted as lost.
+    ///
+    /// NOTE: Probes are plain HTTPS requests, so the RTT includes
+    /// server processing time, the first probe also includes
+    /// connection setup.
+    pub async fn network_test(
+        &self,
+        region: &OfferingRegion,
+    ) -> Result<NetworkTestResult, GssvApiError> {
+        let hostname = region
+            .network_test_hostname
+            .as_ref()
+            .ok_or_else(|| GssvApiError::NoNetworkTestHost(region.name.clone()))?;
+        let url = Url::parse(&format!("https://{}/", hostname))
+            .map_err(|_| GssvApiError::NoNetworkTestHost(region.name.clone()))?;
+
+        Ok(self.probe(&region.name, url).await)
+    }
+
+    /// Send `NETWORK_TEST_PROBES` HEAD requests to `url`, keeping the
+    /// round trip time of every probe that got a response
+    async fn probe(&self, region: &str, url: Url) -> NetworkTestResult {
+        let mut rtts = vec![];
+        for _ in 0..Self::NETWORK_TEST_PROBES {
+            let start = Instant::now();
+            let resp = self
+                .client
+                .head(url.clone())
+                .timeout(Self::NETWORK_TEST_TIMEOUT)
+                .send()
+                .await;
+
+            match resp {
+                Ok(_) => rtts.push(start.elapsed()),
+                Err(err) => tracing::debug!(
+                    target: "gssv::api",
+                    region,
+                    %err,
+                    "Network test probe lost"
+                ),
+            }
+        }
+
+        NetworkTestResult::from_probes(region, rtts, Self::NETWORK_TEST_PROBES)
+    }
+
+    /// Run `network_test` against all regions and send all further
+    /// requests to the one with the lowest round trip time.
+    ///
+    /// Returns the result of the selected region, `None` if no region
+    /// could be reached - the current region is kept then.
+    /// Fails if the offering does not allow region selection.
+    pub async fn select_best_region(&mut self) -> Result<Option<NetworkTestResult>, GssvApiError> {
+        if !self.allow_region_selection {
+            return Err(GssvApiError::RegionSelectionDisabled);
+        }
+
+        let mut results = vec![];
+        for region in &self.regions {
+            match self.network_test(region).await {
+                Ok(result) => results.push(result),
+                Err(GssvApiError::NoNetworkTestHost(_)) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let best = match NetworkTestResult::best(results) {
+            Some(best) => best,
+            None => return Ok(None),
+        };
+
+        if let Some(region) = self.regions.iter().find(|r| r.name == best.region).cloned() {
+            self.set_region(&region)?;
+        }
+
+        Ok(Some(best))
+    }
+
+    /// Start an authenticated request
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.request_with_headers(method, url, None)
+    }
+
+    /// Start an authenticated request with additional `headers`,
+    /// the default headers are applied last
+    fn request_with_headers(
+        &self,
+        method: Method,
+        url: Url,
+        headers: Option<HeaderMap>,
+    ) -> RequestBuilder {
+        tracing::debug!(target: "gssv::api", %method, %url, "Request");
+        let mut req = self
+            .client
+            .request(method, url)
+            .header(header::AUTHORIZATION, self.auth_value.clone());
 
         if let Some(headers) = headers {
             req = req.headers(headers);
         }
 
-        req.send()
-            .await
-            .map_err(GssvApiError::HttpError)?
-            .error_for_status()?
-            .json::<T>()
+        req.headers(self.default_headers.clone())
+    }
+
+    fn url(&self, path: &str) -> Result<Url, GssvApiError> {
+        self.base_url
+            .join(path)
+            .map_err(|err| GssvApiError::InvalidUrl {
+                url: format!("{} + {}", self.base_url, path),
+                reason: err.to_string(),
+            })
+    }
+
+    /// URL of `path` below the session, `path` is appended to the
+    /// session path with exactly one slash in between
+    fn session_url(&self, session: &SessionResponse, path: &str) -> Result<Url, GssvApiError> {
+        let session_path = session.session_path.trim_end_matches('/');
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            self.url(session_path)
+        } else {
+            self.url(&format!("{}/{}", session_path, path))
+        }
+    }
+
+    async fn get_json<T>(&self, url: Url, headers: Option<HeaderMap>) -> Result<T, GssvApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let resp = self
+            .request_with_headers(Method::GET, url, headers)
+            .send()
             .await
-            .map_err(GssvApiError::HttpError)
+            .map_err(GssvApiError::HttpError)?;
+
+        read_json(check_status(resp).await?).await
     }
 
     async fn post_json<RQ, RS>(
@@ -128,28 +459,88 @@ impl GssvApi {
         RQ: Serialize,
         RS: DeserializeOwned,
     {
-        let mut req = self.client.post(url);
-
-        if let Some(headers) = headers {
-            req = req.headers(headers);
-        }
-
-        req.json(&request_body)
+        let resp = self
+            .request_with_headers(Method::POST, url, headers)
+            .json(&request_body)
             .send()
             .await
-            .map_err(GssvApiError::HttpError)?
-            .error_for_status()?
-            .json::<RS>()
-            .await
-            .map_err(GssvApiError::HttpError)
+            .map_err(GssvApiError::HttpError)?;
+
+        read_json(check_status(resp).await?).await
+    }
+
+    fn paged_url(&self, path: &str, continuation_token: Option<&str>) -> Result<Url, GssvApiError> {
+        let mut url = self.url(path)?;
+        if let Some(token) = continuation_token {
+            url.query_pairs_mut()
+                .append_pair("continuationToken", token);
+        }
+        Ok(url)
     }
 
     pub async fn get_consoles(&self) -> Result<ConsolesResponse, GssvApiError> {
-        self.get_json(self.url("/v6/servers/home"), None).await
+        self.get_consoles_page(None).await
+    }
+
+    pub async fn get_consoles_page(
+        &self,
+        continuation_token: Option<&str>,
+    ) -> Result<ConsolesResponse, GssvApiError> {
+        self.get_json(
+            self.paged_url("/v6/servers/home", continuation_token)?,
+            None,
+        )
+        .await
+    }
+
+    /// Fetch consoles, following continuation tokens until exhausted
+    pub async fn get_all_consoles(&self) -> Result<Vec<String>, GssvApiError> {
+        let mut results = vec![];
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut page = self
+                .get_consoles_page(continuation_token.as_deref())
+                .await?;
+            results.append(&mut page.results);
+
+            match page.continuation_token {
+                Some(token) if !token.is_empty() => continuation_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(results)
     }
 
     pub async fn get_titles(&self) -> Result<TitlesResponse, GssvApiError> {
-        self.get_json(self.url("/v1/titles"), None).await
+        self.get_titles_page(None).await
+    }
+
+    pub async fn get_titles_page(
+        &self,
+        continuation_token: Option<&str>,
+    ) -> Result<TitlesResponse, GssvApiError> {
+        self.get_json(self.paged_url("/v1/titles", continuation_token)?, None)
+            .await
+    }
+
+    /// Fetch titles, following continuation tokens until exhausted
+    pub async fn get_all_titles(&self) -> Result<Vec<TitleResult>, GssvApiError> {
+        let mut results = vec![];
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut page = self.get_titles_page(continuation_token.as_deref()).await?;
+            results.append(&mut page.results);
+
+            match page.continuation_token {
+                Some(token) if !token.is_empty() => continuation_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(results)
     }
 
     pub async fn start_session(
@@ -219,7 +610,7 @@ impl GssvApi {
         };
 
         self.post_json(
-            self.url(&format!("/v5/sessions/{}/play", self.platform)),
+            self.url(&format!("/v5/sessions/{}/play", self.platform))?,
             &request_body,
             Some(headers),
         )
@@ -232,8 +623,7 @@ impl GssvApi {
         xcloud_transfer_token: &str,
     ) -> Result<(), GssvApiError> {
         let resp = self
-            .client
-            .post(self.session_url(session, "/connect"))
+            .request(Method::POST, self.session_url(session, "/connect")?)
             .json(&XCloudConnect {
                 user_token: xcloud_transfer_token.into(),
             })
@@ -243,63 +633,97 @@ impl GssvApi {
 
         match resp.status() {
             StatusCode::ACCEPTED => Ok(()),
-            _ => Err(GssvApiError::Unknown),
+            _ => Err(GssvApiError::from_response(resp).await),
         }
     }
 
+    /// Terminate the session on the server side
+    pub async fn stop_session(&self, session: &SessionResponse) -> Result<(), GssvApiError> {
+        let resp = self
+            .request(Method::DELETE, self.session_url(session, "")?)
+            .send()
+            .await
+            .map_err(GssvApiError::HttpError)?;
+
+        match resp.status() {
+            StatusCode::OK | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(GssvApiError::from_response(resp).await),
+        }
+    }
+
+    /// Stop `sessions` and drop the GSSV token.
+    ///
+    /// The service offers no endpoint to revoke the token, it stays
+    /// valid until it expires (see `LoginResponse::duration_in_seconds`),
+    /// so this only releases the sessions held by this login.
+    /// Sessions that are already gone are skipped; all sessions are
+    /// attempted, the first error is returned.
+    pub async fn logout(self, sessions: &[SessionResponse]) -> Result<(), GssvApiError> {
+        let mut result = Ok(());
+        for session in sessions {
+            match self.stop_session(session).await {
+                Ok(()) => {}
+                Err(GssvApiError::UnexpectedStatus {
+                    status: StatusCode::NOT_FOUND,
+                    ..
+                }) => {}
+                Err(err) => {
+                    tracing::warn!(
+                        target: "gssv::api",
+                        session = session.session_path(),
+                        %err,
+                        "Failed to stop session on logout"
+                    );
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+
+        tracing::info!(target: "gssv::api", platform = self.platform, "Logged out");
+        result
+    }
+
     pub async fn get_session_state(
         &self,
         session: &SessionResponse,
     ) -> Result<SessionStateResponse, GssvApiError> {
-        self.get_json(self.session_url(session, "/state"), None)
+        self.get_json(self.session_url(session, "/state")?, None)
             .await
     }
 
+    /// Read back the configuration of a provisioned session
+    ///
+    /// NOTE: This is not the `GssvSessionConfig` sent to `start_session`,
+    /// see `GssvSessionConfigResponse`.
     pub async fn get_session_config(
         &self,
         session: &SessionResponse,
-    ) -> Result<GssvSessionConfig, GssvApiError> {
-        self.get_json(self.session_url(session, "/configuration"), None)
+    ) -> Result<GssvSessionConfigResponse, GssvApiError> {
+        self.get_json(self.session_url(session, "/configuration")?, None)
             .await
     }
 
     pub async fn set_sdp(&self, session: &SessionResponse, sdp: &str) -> Result<(), GssvApiError> {
+        self.set_sdp_with_config(session, sdp, &SdpConfiguration::default())
+            .await
+    }
+
+    /// Send the SDP offer, requesting the channel versions and chat
+    /// format of `configuration`
+    pub async fn set_sdp_with_config(
+        &self,
+        session: &SessionResponse,
+        sdp: &str,
+        configuration: &SdpConfiguration,
+    ) -> Result<(), GssvApiError> {
         let resp = self
-            .client
-            .post(self.session_url(session, "/sdp"))
+            .request(Method::POST, self.session_url(session, "/sdp")?)
             .json(&GssvSdpOffer {
                 message_type: "offer".into(),
                 sdp: sdp.to_string(),
-                configuration: SdpConfiguration {
-                    chat: ChannelVersion {
-                        min_version: 1,
-                        max_version: 1,
-                    },
-                    control: ChannelVersion {
-                        min_version: 1,
-                        max_version: 3,
-                    },
-                    input: ChannelVersion {
-                        min_version: 1,
-                        max_version: 7,
-                    },
-                    message: ChannelVersion {
-                        min_version: 1,
-                        max_version: 1,
-                    },
-                    audio: None,
-                    video: None,
-                    chat_configuration: ChatConfiguration {
-                        bytes_per_sample: 2,
-                        expected_clip_duration_ms: 100,
-                        format: ChatAudioFormat {
-                            codec: "opus".into(),
-                            container: "webm".into(),
-                        },
-                        num_channels: 1,
-                        sample_frequency_hz: 24000,
-                    },
-                },
+                configuration: configuration.clone(),
             })
             .send()
             .await
@@ -307,7 +731,7 @@ impl GssvApi {
 
         match resp.status() {
             StatusCode::ACCEPTED => Ok(()),
-            _ => Err(GssvApiError::Unknown),
+            _ => Err(GssvApiError::from_response(resp).await),
         }
     }
 
@@ -317,8 +741,7 @@ impl GssvApi {
         ice: Vec<IceCandidate>,
     ) -> Result<(), GssvApiError> {
         let resp = self
-            .client
-            .post(self.session_url(session, "/ice"))
+            .request(Method::POST, self.session_url(session, "/ice")?)
             .json(&IceMessage {
                 message_type: "iceCandidate".into(),
                 candidate: ice,
@@ -329,7 +752,7 @@ impl GssvApi {
 
         match resp.status() {
             StatusCode::ACCEPTED => Ok(()),
-            _ => Err(GssvApiError::Unknown),
+            _ => Err(GssvApiError::from_response(resp).await),
         }
     }
 
@@ -337,30 +760,58 @@ impl GssvApi {
         &self,
         session: &SessionResponse,
     ) -> Result<SdpExchangeResponse, GssvApiError> {
-        self.get_json(self.session_url(session, "/sdp"), None).await
+        self.get_json(self.session_url(session, "/sdp")?, None)
+            .await
+    }
+
+    /// Fetch the SDP answer, `None` while the server has not
+    /// produced one yet (204 / empty body).
+    pub async fn poll_sdp(
+        &self,
+        session: &SessionResponse,
+    ) -> Result<Option<SdpExchangeResponse>, GssvApiError> {
+        let resp = self
+            .request(Method::GET, self.session_url(session, "/sdp")?)
+            .send()
+            .await
+            .map_err(GssvApiError::HttpError)?;
+
+        if resp.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        let body = check_status(resp)
+            .await?
+            .text()
+            .await
+            .map_err(GssvApiError::HttpError)?;
+        if body.trim().is_empty() {
+            return Ok(None);
+        }
+
+        parse_json(&body).map(Some)
     }
 
     pub async fn get_ice(
         &self,
         session: &SessionResponse,
     ) -> Result<IceExchangeResponse, GssvApiError> {
-        self.get_json(self.session_url(session, "/ice"), None).await
+        self.get_json(self.session_url(session, "/ice")?, None)
+            .await
     }
 
     pub async fn send_keepalive(
         &self,
         session: &SessionResponse,
     ) -> Result<KeepaliveResponse, GssvApiError> {
-        self.client
-            .post(self.session_url(session, "/keepalive"))
+        let resp = self
+            .request(Method::POST, self.session_url(session, "/keepalive")?)
             .body("")
             .send()
             .await
-            .map_err(GssvApiError::HttpError)?
-            .error_for_status()?
-            .json::<KeepaliveResponse>()
-            .await
-            .map_err(GssvApiError::HttpError)
+            .map_err(GssvApiError::HttpError)?;
+
+        read_json(check_status(resp).await?).await
     }
 }
 
@@ -392,6 +843,7 @@ struct GssvSessionSettings {
     os_name: String,
 }
 
+/// Request body of `start_session`
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GssvSessionConfig {
@@ -402,42 +854,162 @@ pub struct GssvSessionConfig {
     fallback_region_names: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-struct ChannelVersion {
-    min_version: u8,
-    max_version: u8,
+pub struct GssvSrtpConfig {
+    /// Base64 encoded SRTP master key and salt
+    pub key: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Connection details of the streaming server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GssvServerDetails {
+    pub ip_address: Option<String>,
+    pub port: Option<u16>,
+    #[serde(rename = "ipV4Address")]
+    pub ipv4_address: Option<String>,
+    #[serde(rename = "ipV4Port")]
+    pub ipv4_port: Option<u16>,
+    #[serde(rename = "ipV6Address")]
+    pub ipv6_address: Option<String>,
+    #[serde(rename = "ipV6Port")]
+    pub ipv6_port: Option<u16>,
+    pub ice_exchange_path: Option<String>,
+    pub stun_server_address: Option<String>,
+    pub srtp: Option<GssvSrtpConfig>,
+}
+
+/// Response of `get_session_config`
+///
+/// The service does not echo the settings sent to `start_session`, it
+/// answers with the server connection details. Negotiated codecs are
+/// part of the SDP answer instead (see `SdpResponse`).
+/// Fields not modelled here are kept in `extra`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GssvSessionConfigResponse {
+    pub keep_alive_pulse_in_seconds: Option<u32>,
+    pub server_details: Option<GssvServerDetails>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Range of channel protocol versions, requested with the SDP offer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelVersion {
+    pub min_version: u8,
+    pub max_version: u8,
+}
+
+impl ChannelVersion {
+    pub fn new(min_version: u8, max_version: u8) -> Self {
+        Self {
+            min_version,
+            max_version,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatAudioFormat {
-    codec: String,
-    container: String,
+    pub codec: String,
+    pub container: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-struct ChatConfiguration {
-    bytes_per_sample: u8,
-    expected_clip_duration_ms: u32,
-    format: ChatAudioFormat,
-    num_channels: u8,
-    sample_frequency_hz: u32,
+pub struct ChatConfiguration {
+    pub bytes_per_sample: u8,
+    pub expected_clip_duration_ms: u32,
+    pub format: ChatAudioFormat,
+    pub num_channels: u8,
+    pub sample_frequency_hz: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Opus in WebM, 24kHz mono, 100ms clips
+impl Default for ChatConfiguration {
+    fn default() -> Self {
+        Self {
+            bytes_per_sample: 2,
+            expected_clip_duration_ms: 100,
+            format: ChatAudioFormat {
+                codec: "opus".into(),
+                container: "webm".into(),
+            },
+            num_channels: 1,
+            sample_frequency_hz: 24000,
+        }
+    }
+}
+
+impl ChatConfiguration {
+    pub fn with_bytes_per_sample(mut self, bytes_per_sample: u8) -> Self {
+        self.bytes_per_sample = bytes_per_sample;
+        self
+    }
+
+    /// Duration of a single chat audio clip, the browser client uses 20ms
+    pub fn with_clip_duration_ms(mut self, duration_ms: u32) -> Self {
+        self.expected_clip_duration_ms = duration_ms;
+        self
+    }
+
+    pub fn with_format(mut self, codec: &str, container: &str) -> Self {
+        self.format = ChatAudioFormat {
+            codec: codec.into(),
+            container: container.into(),
+        };
+        self
+    }
+
+    pub fn with_channels(mut self, num_channels: u8) -> Self {
+        self.num_channels = num_channels;
+        self
+    }
+
+    pub fn with_sample_frequency_hz(mut self, sample_frequency_hz: u32) -> Self {
+        self.sample_frequency_hz = sample_frequency_hz;
+        self
+    }
+}
+
+/// Channel versions and chat format, sent along with the SDP offer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-struct SdpConfiguration {
-    chat_configuration: ChatConfiguration,
-    chat: ChannelVersion,
-    control: ChannelVersion,
-    input: ChannelVersion,
-    message: ChannelVersion,
+pub struct SdpConfiguration {
+    pub chat_configuration: ChatConfiguration,
+    pub chat: ChannelVersion,
+    pub control: ChannelVersion,
+    pub input: ChannelVersion,
+    pub message: ChannelVersion,
     #[serde(skip_serializing_if = "Option::is_none")]
-    audio: Option<ChannelVersion>,
+    pub audio: Option<ChannelVersion>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    video: Option<ChannelVersion>,
+    pub video: Option<ChannelVersion>,
+}
+
+impl Default for SdpConfiguration {
+    fn default() -> Self {
+        Self {
+            chat_configuration: ChatConfiguration::default(),
+            chat: ChannelVersion::new(1, 1),
+            control: ChannelVersion::new(1, 3),
+            input: ChannelVersion::new(1, 7),
+            message: ChannelVersion::new(1, 1),
+            audio: None,
+            video: None,
+        }
+    }
+}
+
+impl SdpConfiguration {
+    pub fn with_chat_configuration(mut self, chat_configuration: ChatConfiguration) -> Self {
+        self.chat_configuration = chat_configuration;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -459,6 +1031,27 @@ pub struct IceCandidate {
     pub username_fragment: Option<String>,
 }
 
+impl IceCandidate {
+    /// Candidate line without the `a=` SDP attribute prefix and
+    /// surrounding whitespace, e.g. `candidate:1 1 UDP ...`
+    ///
+    /// The server sends candidates as full SDP lines (`a=candidate:...`),
+    /// while webrtc stacks expect the bare attribute value.
+    pub fn normalized(&self) -> Self {
+        let candidate = self.candidate.trim();
+        Self {
+            candidate: candidate.strip_prefix("a=").unwrap_or(candidate).to_owned(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether this is the end-of-candidates marker, which ends the
+    /// candidate list rather than being a candidate itself
+    pub fn is_end_of_candidates(&self) -> bool {
+        self.normalized().candidate == "end-of-candidates"
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct IceMessage {
@@ -534,7 +1127,7 @@ struct DeviceInfo {
     dev: DevInfo,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OfferingRegion {
     pub name: String,
@@ -545,6 +1138,44 @@ pub struct OfferingRegion {
     pub fallback_priority: i32,
 }
 
+/// Default region first, the rest by ascending fallback priority
+fn order_regions(regions: &[OfferingRegion]) -> Vec<&OfferingRegion> {
+    let mut ordered: Vec<&OfferingRegion> = regions.iter().collect();
+    ordered.sort_by_key(|region| (!region.is_default, region.fallback_priority));
+    ordered
+}
+
+/// Result of `GssvApi::network_test`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkTestResult {
+    /// Name of the tested region
+    pub region: String,
+    /// Median round trip time, `None` if all probes were lost
+    pub rtt: Option<Duration>,
+    /// Share of lost probes, 0.0 - 1.0
+    pub packet_loss: f32,
+}
+
+impl NetworkTestResult {
+    fn from_probes(region: &str, mut rtts: Vec<Duration>, probes: usize) -> Self {
+        rtts.sort();
+
+        Self {
+            region: region.to_owned(),
+            rtt: rtts.get(rtts.len() / 2).copied(),
+            packet_loss: (probes - rtts.len()) as f32 / probes as f32,
+        }
+    }
+
+    /// Result with the lowest round trip time, unreachable regions are skipped
+    fn best(results: Vec<Self>) -> Option<Self> {
+        results
+            .into_iter()
+            .filter(|result| result.rtt.is_some())
+            .min_by_key(|result| result.rtt)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct CloudEnvironment {
@@ -552,12 +1183,28 @@ pub struct CloudEnvironment {
     pub auth_base_uri: Option<String>,
 }
 
+#[cfg(feature = "xal")]
+impl From<&CloudEnvironment> for xal::app_params::XalEnvironment {
+    fn from(environment: &CloudEnvironment) -> Self {
+        Self::from_cloud_environment(&environment.name, environment.auth_base_uri.as_deref())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct ClientCloudSettings {
     pub environments: Vec<CloudEnvironment>,
 }
 
+impl ClientCloudSettings {
+    /// Get cloud environment by name (case-insensitive)
+    pub fn environment(&self, name: &str) -> Option<&CloudEnvironment> {
+        self.environments
+            .iter()
+            .find(|env| env.name.eq_ignore_ascii_case(name))
+    }
+}
+
 /* Responses */
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ErrorDetails {
@@ -574,6 +1221,14 @@ pub struct OfferingSettings {
     pub client_cloud_settings: ClientCloudSettings,
 }
 
+impl OfferingSettings {
+    /// Region flagged as default, otherwise the one with the
+    /// lowest `fallback_priority`
+    pub fn default_region(&self) -> Option<&OfferingRegion> {
+        order_regions(&self.regions).into_iter().next()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginResponse {
@@ -645,10 +1300,56 @@ pub struct TitlesResponse {
     pub continuation_token: Option<String>,
 }
 
+/// Response of `start_session`
+///
+/// Fields not modelled here are kept, see `extra`.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionResponse {
     session_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+impl SessionResponse {
+    pub fn session_path(&self) -> &str {
+        &self.session_path
+    }
+
+    /// Session id (GUID), as sent by the server or otherwise the last
+    /// segment of the session path
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id
+            .as_deref()
+            .filter(|id| !id.is_empty())
+            .or_else(|| {
+                self.session_path
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .filter(|id| !id.is_empty())
+            })
+    }
+
+    /// Region the session was assigned to, if sent by the server
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Remaining fields of the response (e.g. state or expiry)
+    pub fn extra(&self) -> &HashMap<String, serde_json::Value> {
+        &self.extra
+    }
+}
+
+impl fmt::Display for SessionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.session_path)
+    }
 }
 
 pub enum SessionState {
@@ -691,6 +1392,16 @@ pub struct SdpResponse {
     pub debug_info: Option<String>,
 }
 
+impl SdpResponse {
+    /// SDP answer, only available once `status` is 'success'
+    pub fn answer(&self) -> Option<&str> {
+        match self.status.as_deref() {
+            Some("success") => self.sdp.as_deref().filter(|sdp| !sdp.is_empty()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SdpExchangeResponse {
@@ -707,22 +1418,128 @@ pub struct IceExchangeResponse {
     pub error_details: Option<ErrorDetails>,
 }
 
+/// Reason sent with a keepalive response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum KeepaliveReason {
+    Alive,
+    SessionTerminated,
+    /// Reason not known (yet), holds the raw value
+    Unknown(String),
+}
+
+impl KeepaliveReason {
+    /// Session is gone, keepalives can be stopped
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, KeepaliveReason::SessionTerminated)
+    }
+}
+
+impl From<String> for KeepaliveReason {
+    fn from(reason: String) -> Self {
+        match reason.to_lowercase().as_ref() {
+            "alive" => KeepaliveReason::Alive,
+            "sessionterminated" => KeepaliveReason::SessionTerminated,
+            _ => KeepaliveReason::Unknown(reason),
+        }
+    }
+}
+
+impl From<KeepaliveReason> for String {
+    fn from(reason: KeepaliveReason) -> Self {
+        match reason {
+            KeepaliveReason::Alive => "Alive".into(),
+            KeepaliveReason::SessionTerminated => "SessionTerminated".into(),
+            KeepaliveReason::Unknown(reason) => reason,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct KeepaliveResponse {
     pub alive_seconds: Option<u32>,
-    pub reason: String,
+    pub reason: KeepaliveReason,
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Request as received by `serve`
+    pub(crate) struct RecordedRequest {
+        pub request_line: String,
+        pub headers: HashMap<String, String>,
+        pub body: String,
+    }
 
-    fn sdp_offer_message() -> &'static str {
+    /// Serve one HTTP request per entry of `responses` (status, JSON
+    /// body), in order
+    ///
+    /// Returns the base URL of the server and a handle yielding the
+    /// received requests.
+    pub(crate) fn serve(
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> (Url, JoinHandle<Vec<RecordedRequest>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().expect("Failed to accept connection");
+                let mut reader = BufReader::new(stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => {
+                            headers.insert(name.to_lowercase(), value.to_owned());
+                        }
+                        None => break,
+                    }
+                }
+
+                let content_length = headers
+                    .get("content-length")
+                    .map_or(0, |len| len.parse().unwrap());
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+
+                requests.push(RecordedRequest {
+                    request_line: request_line.trim_end().to_owned(),
+                    headers,
+                    body: String::from_utf8(request_body).unwrap(),
+                });
+            }
+            requests
+        });
+
+        (url, handle)
+    }
+
+    pub(crate) fn sdp_offer_message() -> &'static str {
         r#"{"messageType":"offer","sdp":"v=0\r\no=- 3296606666082362637 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1 2\r\na=extmap-allow-mixed\r\na=msid-semantic: WMS\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111 63 103 104 9 0 8 106 105 13 110 112 113 126\r\nc=IN IP4 0.0.0.0\r\na=rtcp:9 IN IP4 0.0.0.0\r\na=ice-ufrag:bSbi\r\na=ice-pwd:BXzujnFw/cHKF8tMgtoo/cne\r\na=ice-options:trickle\r\na=fingerprint:sha-256 CB:87:A2:17:63:29:8C:10:5F:CE:29:22:76:ED:C3:89:64:94:48:29:E0:7C:83:13:70:41:C0:5C:08:D2:69:33\r\na=setup:actpass\r\na=mid:0\r\na=extmap:1 urn:ietf:params:rtp-hdrext:ssrc-audio-level\r\na=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\na=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\r\na=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r\na=sendrecv\r\na=msid:- a75c2046-2efe-4b04-aeb9-ed7beecf7871\r\na=rtcp-mux\r\na=rtpmap:111 opus/48000/2\r\na=rtcp-fb:111 transport-cc\r\na=fmtp:111 minptime=10;useinbandfec=1\r\na=rtpmap:63 red/48000/2\r\na=fmtp:63 111/111\r\na=rtpmap:103 ISAC/16000\r\na=rtpmap:104 ISAC/32000\r\na=rtpmap:9 G722/8000\r\na=rtpmap:0 PCMU/8000\r\na=rtpmap:8 PCMA/8000\r\na=rtpmap:106 CN/32000\r\na=rtpmap:105 CN/16000\r\na=rtpmap:13 CN/8000\r\na=rtpmap:110 telephone-event/48000\r\na=rtpmap:112 telephone-event/32000\r\na=rtpmap:113 telephone-event/16000\r\na=rtpmap:126 telephone-event/8000\r\na=ssrc:2757659185 cname:8nJCvH9MPijHQSGZ\r\na=ssrc:2757659185 msid:- a75c2046-2efe-4b04-aeb9-ed7beecf7871\r\nm=video 9 UDP/TLS/RTP/SAVPF 96 97 98 99 100 101 102 122 127 121 125 107 108 109 124 120 123 119 35 36 37 38 39 40 41 42 114 115 116 43\r\nc=IN IP4 0.0.0.0\r\na=rtcp:9 IN IP4 0.0.0.0\r\na=ice-ufrag:bSbi\r\na=ice-pwd:BXzujnFw/cHKF8tMgtoo/cne\r\na=ice-options:trickle\r\na=fingerprint:sha-256 CB:87:A2:17:63:29:8C:10:5F:CE:29:22:76:ED:C3:89:64:94:48:29:E0:7C:83:13:70:41:C0:5C:08:D2:69:33\r\na=setup:actpass\r\na=mid:1\r\na=extmap:14 urn:ietf:params:rtp-hdrext:toffset\r\na=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\na=extmap:13 urn:3gpp:video-orientation\r\na=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\r\na=extmap:5 http://www.webrtc.org/experiments/rtp-hdrext/playout-delay\r\na=extmap:6 http://www.webrtc.org/experiments/rtp-hdrext/video-content-type\r\na=extmap:7 http://www.webrtc.org/experiments/rtp-hdrext/video-timing\r\na=extmap:8 http://www.webrtc.org/experiments/rtp-hdrext/color-space\r\na=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r\na=extmap:10 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r\na=extmap:11 urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id\r\na=recvonly\r\na=rtcp-mux\r\na=rtcp-rsize\r\na=rtpmap:96 VP8/90000\r\na=rtcp-fb:96 goog-remb\r\na=rtcp-fb:96 transport-cc\r\na=rtcp-fb:96 ccm fir\r\na=rtcp-fb:96 nack\r\na=rtcp-fb:96 nack pli\r\na=rtpmap:97 rtx/90000\r\na=fmtp:97 apt=96\r\na=rtpmap:98 VP9/90000\r\na=rtcp-fb:98 goog-remb\r\na=rtcp-fb:98 transport-cc\r\na=rtcp-fb:98 ccm fir\r\na=rtcp-fb:98 nack\r\na=rtcp-fb:98 nack pli\r\na=fmtp:98 profile-id=0\r\na=rtpmap:99 rtx/90000\r\na=fmtp:99 apt=98\r\na=rtpmap:100 VP9/90000\r\na=rtcp-fb:100 goog-remb\r\na=rtcp-fb:100 transport-cc\r\na=rtcp-fb:100 ccm fir\r\na=rtcp-fb:100 nack\r\na=rtcp-fb:100 nack pli\r\na=fmtp:100 profile-id=2\r\na=rtpmap:101 rtx/90000\r\na=fmtp:101 apt=100\r\na=rtpmap:102 VP9/90000\r\na=rtcp-fb:102 goog-remb\r\na=rtcp-fb:102 transport-cc\r\na=rtcp-fb:102 ccm fir\r\na=rtcp-fb:102 nack\r\na=rtcp-fb:102 nack pli\r\na=fmtp:102 profile-id=1\r\na=rtpmap:122 rtx/90000\r\na=fmtp:122 apt=102\r\na=rtpmap:127 H264/90000\r\na=rtcp-fb:127 goog-remb\r\na=rtcp-fb:127 transport-cc\r\na=rtcp-fb:127 ccm fir\r\na=rtcp-fb:127 nack\r\na=rtcp-fb:127 nack pli\r\na=fmtp:127 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42001f\r\na=rtpmap:121 rtx/90000\r\na=fmtp:121 apt=127\r\na=rtpmap:125 H264/90000\r\na=rtcp-fb:125 goog-remb\r\na=rtcp-fb:125 transport-cc\r\na=rtcp-fb:125 ccm fir\r\na=rtcp-fb:125 nack\r\na=rtcp-fb:125 nack pli\r\na=fmtp:125 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=42001f\r\na=rtpmap:107 rtx/90000\r\na=fmtp:107 apt=125\r\na=rtpmap:108 H264/90000\r\na=rtcp-fb:108 goog-remb\r\na=rtcp-fb:108 transport-cc\r\na=rtcp-fb:108 ccm fir\r\na=rtcp-fb:108 nack\r\na=rtcp-fb:108 nack pli\r\na=fmtp:108 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f\r\na=rtpmap:109 rtx/90000\r\na=fmtp:109 apt=108\r\na=rtpmap:124 H264/90000\r\na=rtcp-fb:124 goog-remb\r\na=rtcp-fb:124 transport-cc\r\na=rtcp-fb:124 ccm fir\r\na=rtcp-fb:124 nack\r\na=rtcp-fb:124 nack pli\r\na=fmtp:124 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=42e01f\r\na=rtpmap:120 rtx/90000\r\na=fmtp:120 apt=124\r\na=rtpmap:123 H264/90000\r\na=rtcp-fb:123 goog-remb\r\na=rtcp-fb:123 transport-cc\r\na=rtcp-fb:123 ccm fir\r\na=rtcp-fb:123 nack\r\na=rtcp-fb:123 nack pli\r\na=fmtp:123 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=4d001f\r\na=rtpmap:119 rtx/90000\r\na=fmtp:119 apt=123\r\na=rtpmap:35 H264/90000\r\na=rtcp-fb:35 goog-remb\r\na=rtcp-fb:35 transport-cc\r\na=rtcp-fb:35 ccm fir\r\na=rtcp-fb:35 nack\r\na=rtcp-fb:35 nack pli\r\na=fmtp:35 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=4d001f\r\na=rtpmap:36 rtx/90000\r\na=fmtp:36 apt=35\r\na=rtpmap:37 H264/90000\r\na=rtcp-fb:37 goog-remb\r\na=rtcp-fb:37 transport-cc\r\na=rtcp-fb:37 ccm fir\r\na=rtcp-fb:37 nack\r\na=rtcp-fb:37 nack pli\r\na=fmtp:37 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=f4001f\r\na=rtpmap:38 rtx/90000\r\na=fmtp:38 apt=37\r\na=rtpmap:39 H264/90000\r\na=rtcp-fb:39 goog-remb\r\na=rtcp-fb:39 transport-cc\r\na=rtcp-fb:39 ccm fir\r\na=rtcp-fb:39 nack\r\na=rtcp-fb:39 nack pli\r\na=fmtp:39 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=f4001f\r\na=rtpmap:40 rtx/90000\r\na=fmtp:40 apt=39\r\na=rtpmap:41 AV1/90000\r\na=rtcp-fb:41 goog-remb\r\na=rtcp-fb:41 transport-cc\r\na=rtcp-fb:41 ccm fir\r\na=rtcp-fb:41 nack\r\na=rtcp-fb:41 nack pli\r\na=rtpmap:42 rtx/90000\r\na=fmtp:42 apt=41\r\na=rtpmap:114 red/90000\r\na=rtpmap:115 rtx/90000\r\na=fmtp:115 apt=114\r\na=rtpmap:116 ulpfec/90000\r\na=rtpmap:43 flexfec-03/90000\r\na=rtcp-fb:43 goog-remb\r\na=rtcp-fb:43 transport-cc\r\na=fmtp:43 repair-window=10000000\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\nc=IN IP4 0.0.0.0\r\na=ice-ufrag:bSbi\r\na=ice-pwd:BXzujnFw/cHKF8tMgtoo/cne\r\na=ice-options:trickle\r\na=fingerprint:sha-256 CB:87:A2:17:63:29:8C:10:5F:CE:29:22:76:ED:C3:89:64:94:48:29:E0:7C:83:13:70:41:C0:5C:08:D2:69:33\r\na=setup:actpass\r\na=mid:2\r\na=sctp-port:5000\r\na=max-message-size:262144\r\n","configuration":{"chatConfiguration":{"bytesPerSample":2,"expectedClipDurationMs":20,"format":{"codec":"opus","container":"webm"},"numChannels":1,"sampleFrequencyHz":24000},"chat":{"minVersion":1,"maxVersion":1},"control":{"minVersion":1,"maxVersion":3},"input":{"minVersion":1,"maxVersion":7},"message":{"minVersion":1,"maxVersion":1}}}"#
     }
 
-    fn sdp_response_message() -> &'static str {
+    pub(crate) fn sdp_response_message() -> &'static str {
         r#"{"exchangeResponse":"{\"chat\":1,\"chatConfiguration\":{\"format\":{\"codec\":\"opus\",\"container\":\"webm\"}},\"control\":3,\"input\":7,\"message\":1,\"messageType\":\"answer\",\"sdp\":\"v=0\\r\\no=- 1206897819200911867 2 IN IP4 127.0.0.1\\r\\ns=-\\r\\nt=0 0\\r\\na=group:BUNDLE 0 1 2\\r\\na=extmap-allow-mixed\\r\\na=msid-semantic: WMS 0 1\\r\\nm=audio 9 UDP/TLS/RTP/SAVPF 111 110\\r\\nc=IN IP4 0.0.0.0\\r\\na=rtcp:9 IN IP4 0.0.0.0\\r\\na=ice-ufrag:s1MX\\r\\na=ice-pwd:oG+NQK6nqS9svO3OnnXF6b9F\\r\\na=ice-options:trickle renomination\\r\\na=fingerprint:sha-256 4F:6B:3D:56:F5:CC:A5:D9:B2:63:85:DA:C1:23:90:C5:DB:9D:CF:01:3F:C0:B0:4A:3F:2A:33:09:94:1E:21:8A\\r\\na=setup:active\\r\\na=mid:0\\r\\na=extmap:1 urn:ietf:params:rtp-hdrext:ssrc-audio-level\\r\\na=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\\r\\na=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\\r\\na=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\\r\\na=sendrecv\\r\\na=msid:0 f671d610-5792-4206-8b7a-5065f6c3b05f\\r\\na=rtcp-mux\\r\\na=rtpmap:111 opus/48000/2\\r\\na=fmtp:111 minptime=10;useinbandfec=1\\r\\na=rtpmap:110 telephone-event/48000\\r\\na=ssrc:1897225254 cname:OIHn/yQUJt/2NeUp\\r\\nm=video 9 UDP/TLS/RTP/SAVPF 127 121 125 107 108 109 124 120 123 119 114 115 116\\r\\nc=IN IP4 0.0.0.0\\r\\na=rtcp:9 IN IP4 0.0.0.0\\r\\na=ice-ufrag:s1MX\\r\\na=ice-pwd:oG+NQK6nqS9svO3OnnXF6b9F\\r\\na=ice-options:trickle renomination\\r\\na=fingerprint:sha-256 4F:6B:3D:56:F5:CC:A5:D9:B2:63:85:DA:C1:23:90:C5:DB:9D:CF:01:3F:C0:B0:4A:3F:2A:33:09:94:1E:21:8A\\r\\na=setup:active\\r\\na=mid:1\\r\\na=extmap:14 urn:ietf:params:rtp-hdrext:toffset\\r\\na=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\\r\\na=extmap:13 urn:3gpp:video-orientation\\r\\na=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\\r\\na=extmap:5 http://www.webrtc.org/experiments/rtp-hdrext/playout-delay\\r\\na=extmap:6 http://www.webrtc.org/experiments/rtp-hdrext/video-content-type\\r\\na=extmap:7 http://www.webrtc.org/experiments/rtp-hdrext/video-timing\\r\\na=extmap:8 http://www.webrtc.org/experiments/rtp-hdrext/color-space\\r\\na=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\\r\\na=extmap:10 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\\r\\na=extmap:11 urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id\\r\\na=sendonly\\r\\na=msid:1 0f37d49d-f1ce-43e9-acfe-eabd60755d3f\\r\\na=rtcp-mux\\r\\na=rtcp-rsize\\r\\na=rtpmap:127 H264/90000\\r\\na=rtcp-fb:127 goog-remb\\r\\na=rtcp-fb:127 transport-cc\\r\\na=rtcp-fb:127 ccm fir\\r\\na=rtcp-fb:127 nack\\r\\na=rtcp-fb:127 nack pli\\r\\na=fmtp:127 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42002a\\r\\na=rtpmap:121 rtx/90000\\r\\na=fmtp:121 apt=127\\r\\na=rtpmap:125 H264/90000\\r\\na=rtcp-fb:125 goog-remb\\r\\na=rtcp-fb:125 transport-cc\\r\\na=rtcp-fb:125 ccm fir\\r\\na=rtcp-fb:125 nack\\r\\na=rtcp-fb:125 nack pli\\r\\na=fmtp:125 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=42002a\\r\\na=rtpmap:107 rtx/90000\\r\\na=fmtp:107 apt=125\\r\\na=rtpmap:108 H264/90000\\r\\na=rtcp-fb:108 goog-remb\\r\\na=rtcp-fb:108 transport-cc\\r\\na=rtcp-fb:108 ccm fir\\r\\na=rtcp-fb:108 nack\\r\\na=rtcp-fb:108 nack pli\\r\\na=fmtp:108 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e02a\\r\\na=rtpmap:109 rtx/90000\\r\\na=fmtp:109 apt=108\\r\\na=rtpmap:124 H264/90000\\r\\na=rtcp-fb:124 goog-remb\\r\\na=rtcp-fb:124 transport-cc\\r\\na=rtcp-fb:124 ccm fir\\r\\na=rtcp-fb:124 nack\\r\\na=rtcp-fb:124 nack pli\\r\\na=fmtp:124 level-asymmetry-allowed=1;packetization-mode=0;profile-level-id=42e02a\\r\\na=rtpmap:120 rtx/90000\\r\\na=fmtp:120 apt=124\\r\\na=rtpmap:123 H264/90000\\r\\na=rtcp-fb:123 goog-remb\\r\\na=rtcp-fb:123 transport-cc\\r\\na=rtcp-fb:123 ccm fir\\r\\na=rtcp-fb:123 nack\\r\\na=rtcp-fb:123 nack pli\\r\\na=fmtp:123 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=4d002a\\r\\na=rtpmap:119 rtx/90000\\r\\na=fmtp:119 apt=123\\r\\na=rtpmap:114 red/90000\\r\\na=rtpmap:115 rtx/90000\\r\\na=fmtp:115 apt=114\\r\\na=rtpmap:116 ulpfec/90000\\r\\na=ssrc-group:FID 3945614638 633672403\\r\\na=ssrc:3945614638 cname:OIHn/yQUJt/2NeUp\\r\\na=ssrc:633672403 cname:OIHn/yQUJt/2NeUp\\r\\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\\r\\nc=IN IP4 0.0.0.0\\r\\nb=AS:30\\r\\na=ice-ufrag:s1MX\\r\\na=ice-pwd:oG+NQK6nqS9svO3OnnXF6b9F\\r\\na=ice-options:trickle renomination\\r\\na=fingerprint:sha-256 4F:6B:3D:56:F5:CC:A5:D9:B2:63:85:DA:C1:23:90:C5:DB:9D:CF:01:3F:C0:B0:4A:3F:2A:33:09:94:1E:21:8A\\r\\na=setup:active\\r\\na=mid:2\\r\\na=sctp-port:5000\\r\\na=max-message-size:262144\\r\\n\",\"sdpType\":\"answer\",\"status\":\"success\"}","errorDetails":null}"#
     }
 
@@ -781,6 +1598,67 @@ mod tests {
         assert!(json.is_ok());
     }
 
+    #[test]
+    fn sdp_configuration_default() {
+        let json = serde_json::to_value(SdpConfiguration::default())
+            .expect("Failed to serialize SDP configuration");
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "chatConfiguration": {
+                    "bytesPerSample": 2,
+                    "expectedClipDurationMs": 100,
+                    "format": {"codec": "opus", "container": "webm"},
+                    "numChannels": 1,
+                    "sampleFrequencyHz": 24000
+                },
+                "chat": {"minVersion": 1, "maxVersion": 1},
+                "control": {"minVersion": 1, "maxVersion": 3},
+                "input": {"minVersion": 1, "maxVersion": 7},
+                "message": {"minVersion": 1, "maxVersion": 1}
+            })
+        );
+
+        let config = SdpConfiguration {
+            video: Some(ChannelVersion::new(1, 2)),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(config).expect("Failed to serialize SDP configuration");
+        assert_eq!(
+            json["video"],
+            serde_json::json!({"minVersion": 1, "maxVersion": 2})
+        );
+        assert!(json.get("audio").is_none());
+    }
+
+    #[test]
+    fn chat_configuration_builder() {
+        // The browser client offers 20ms clips
+        let offer = serde_json::from_str::<GssvSdpOffer>(sdp_offer_message()).unwrap();
+        let chat_configuration = ChatConfiguration::default().with_clip_duration_ms(20);
+        assert_eq!(offer.configuration.chat_configuration, chat_configuration);
+
+        let config = SdpConfiguration::default().with_chat_configuration(
+            ChatConfiguration::default()
+                .with_format("pcm", "raw")
+                .with_channels(2)
+                .with_sample_frequency_hz(48000)
+                .with_bytes_per_sample(4),
+        );
+        let json = serde_json::to_value(config).expect("Failed to serialize SDP configuration");
+        assert_eq!(
+            json["chatConfiguration"],
+            serde_json::json!({
+                "bytesPerSample": 4,
+                "expectedClipDurationMs": 100,
+                "format": {"codec": "pcm", "container": "raw"},
+                "numChannels": 2,
+                "sampleFrequencyHz": 48000
+            })
+        );
+    }
+
     #[test]
     fn deserialize_sdp_answer() {
         let data = sdp_response_message();
@@ -804,6 +1682,29 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn normalize_ice_candidates() {
+        let result = serde_json::from_str::<IceExchangeResponse>(&ice_response_message())
+            .expect("Failed to deserialize ICE response");
+        let candidates: Vec<IceCandidate> = result
+            .exchange_response
+            .iter()
+            .map(IceCandidate::normalized)
+            .collect();
+
+        assert_eq!(
+            candidates[0].candidate,
+            "candidate:1 1 UDP 100 43.111.100.34 1136 typ host"
+        );
+        assert_eq!(candidates[0].sdp_mid.as_deref(), Some("0"));
+        assert!(!candidates[1].is_end_of_candidates());
+        assert_eq!(candidates[2].candidate, "end-of-candidates");
+        assert!(result.exchange_response[2].is_end_of_candidates());
+
+        // Already normalized candidates stay as-is
+        assert_eq!(candidates[0].normalized(), candidates[0]);
+    }
+
     #[test]
     fn serialize_ice_request() {
         let data = ice_request_message();
@@ -813,15 +1714,511 @@ mod tests {
         assert!(serialized.is_ok());
     }
 
+    fn session_config_response() -> &'static str {
+        r#"{
+            "keepAlivePulseInSeconds": 1200,
+            "serverDetails": {
+                "ipAddress": "20.62.131.77",
+                "port": 1056,
+                "ipV4Address": "20.62.131.77",
+                "ipV4Port": 1056,
+                "ipV6Address": "2603:1030:210:5::26",
+                "ipV6Port": 9002,
+                "iceExchangePath": "/v5/sessions/cloud/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3/ice",
+                "stunServerAddress": null,
+                "srtp": {
+                    "key": "RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+9SRf"
+                }
+            },
+            "signalingServerDetails": null
+        }"#
+    }
+
+    #[test]
+    fn deserialize_session_config_response() {
+        let config = serde_json::from_str::<GssvSessionConfigResponse>(session_config_response())
+            .expect("Failed to deserialize session config");
+
+        assert_eq!(config.keep_alive_pulse_in_seconds, Some(1200));
+        let server = config.server_details.expect("Server details missing");
+        assert_eq!(server.ipv4_address.as_deref(), Some("20.62.131.77"));
+        assert_eq!(server.ipv4_port, Some(1056));
+        assert_eq!(server.ipv6_port, Some(9002));
+        assert_eq!(server.stun_server_address, None);
+        assert_eq!(
+            server.srtp.map(|srtp| srtp.key).as_deref(),
+            Some("RdHzuLLVGuO1aHILIEVJ1UzR7RWVioepmpy+9SRf")
+        );
+        assert_eq!(
+            config.extra.get("signalingServerDetails"),
+            Some(&serde_json::Value::Null)
+        );
+
+        // The request body has a different shape
+        assert!(serde_json::from_str::<GssvSessionConfig>(session_config_response()).is_err());
+    }
+
     #[test]
     fn deserialize_sdp_response_success() {
         let result = serde_json::from_str::<SdpResponse>(&sdp_exchange_response_success());
         assert!(result.is_ok());
+        assert!(result.unwrap().answer().unwrap().starts_with("v=0"));
+    }
+
+    #[test]
+    fn paged_url() {
+        let api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+
+        assert_eq!(
+            api.paged_url("/v1/titles", None).unwrap().as_str(),
+            "https://example.com/v1/titles"
+        );
+        assert_eq!(
+            api.paged_url("/v1/titles", Some("abc=")).unwrap().as_str(),
+            "https://example.com/v1/titles?continuationToken=abc%3D"
+        );
+    }
+
+    #[test]
+    fn session_url() {
+        let api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+
+        for session_path in ["v5/sessions/cloud/ABC", "/v5/sessions/cloud/ABC/"] {
+            let session = serde_json::from_str::<SessionResponse>(&format!(
+                r#"{{"sessionPath": "{}"}}"#,
+                session_path
+            ))
+            .unwrap();
+
+            assert_eq!(
+                api.session_url(&session, "/sdp").unwrap().as_str(),
+                "https://example.com/v5/sessions/cloud/ABC/sdp"
+            );
+            assert_eq!(
+                api.session_url(&session, "").unwrap().as_str(),
+                "https://example.com/v5/sessions/cloud/ABC"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_url() {
+        let api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            api.url("//[invalid"),
+            Err(GssvApiError::InvalidUrl { .. })
+        ));
+        assert!(matches!(
+            parse_url("not a url"),
+            Err(GssvApiError::InvalidUrl { .. })
+        ));
+    }
+
+    #[test]
+    fn request_has_auth_header() {
+        let api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+
+        let request = api
+            .request(Method::GET, api.url("/v1/titles").unwrap())
+            .build()
+            .expect("Failed to build request");
+        let auth_value = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .expect("Authorization header missing");
+
+        assert_eq!(auth_value, "Bearer token");
+        assert!(auth_value.is_sensitive());
+    }
+
+    #[test]
+    fn invalid_token() {
+        let result = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token\n",
+            "cloud",
+        );
+        assert!(matches!(result, Err(GssvApiError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn session_response_accessors() {
+        let session = serde_json::from_str::<SessionResponse>(
+            r#"{"sessionPath": "v5/sessions/cloud/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3"}"#,
+        )
+        .expect("Failed to deserialize session response");
+
+        assert_eq!(
+            session.session_path(),
+            "v5/sessions/cloud/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3"
+        );
+        assert_eq!(
+            session.session_id(),
+            Some("BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3")
+        );
+        assert_eq!(
+            session.to_string(),
+            "v5/sessions/cloud/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3"
+        );
+
+        let session = serde_json::from_str::<SessionResponse>(r#"{"sessionPath": ""}"#)
+            .expect("Failed to deserialize session response");
+        assert_eq!(session.session_id(), None);
+    }
+
+    #[test]
+    fn session_response_fields() {
+        let session = serde_json::from_str::<SessionResponse>(
+            r#"{
+                "sessionPath": "v5/sessions/cloud/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3",
+                "sessionId": "5E1D0BC4-1A5F-4C43-9D3A-6E2E2F1B0C11",
+                "region": "WestEurope",
+                "state": "Provisioning"
+            }"#,
+        )
+        .expect("Failed to deserialize session response");
+
+        assert_eq!(
+            session.session_id(),
+            Some("5E1D0BC4-1A5F-4C43-9D3A-6E2E2F1B0C11")
+        );
+        assert_eq!(session.region(), Some("WestEurope"));
+        assert_eq!(
+            session.extra().get("state"),
+            Some(&serde_json::Value::from("Provisioning"))
+        );
+        assert!(!session.extra().contains_key("sessionPath"));
+
+        let session = serde_json::from_str::<SessionResponse>(
+            r#"{"sessionPath": "v5/sessions/cloud/ABC", "sessionId": ""}"#,
+        )
+        .expect("Failed to deserialize session response");
+        assert_eq!(session.session_id(), Some("ABC"));
+        assert_eq!(session.region(), None);
+        assert!(session.extra().is_empty());
     }
 
     #[test]
     fn deserialize_sdp_response_failure() {
         let result = serde_json::from_str::<SdpResponse>(&sdp_exchange_response_failure());
         assert!(result.is_ok());
+        assert_eq!(result.unwrap().answer(), None);
+    }
+
+    #[test]
+    fn error_from_status() {
+        assert!(matches!(
+            GssvApiError::from_status(StatusCode::UNAUTHORIZED, "".into()),
+            GssvApiError::Unauthorized(StatusCode::UNAUTHORIZED)
+        ));
+        assert!(matches!(
+            GssvApiError::from_status(StatusCode::FORBIDDEN, "".into()),
+            GssvApiError::Unauthorized(StatusCode::FORBIDDEN)
+        ));
+        assert!(matches!(
+            GssvApiError::from_status(StatusCode::TOO_MANY_REQUESTS, "".into()),
+            GssvApiError::RateLimited
+        ));
+
+        let err = GssvApiError::from_status(StatusCode::BAD_REQUEST, "Invalid offer".into());
+        assert!(matches!(
+            &err,
+            GssvApiError::UnexpectedStatus { status, body }
+                if *status == StatusCode::BAD_REQUEST && body == "Invalid offer"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unexpected response status 400 Bad Request: Invalid offer"
+        );
+    }
+
+    #[test]
+    fn network_test_result() {
+        let result = NetworkTestResult::from_probes(
+            "WestEurope",
+            vec![
+                Duration::from_millis(40),
+                Duration::from_millis(20),
+                Duration::from_millis(30),
+            ],
+            4,
+        );
+        assert_eq!(result.region, "WestEurope");
+        assert_eq!(result.rtt, Some(Duration::from_millis(30)));
+        assert_eq!(result.packet_loss, 0.25);
+
+        let unreachable = NetworkTestResult::from_probes("EastUS", vec![], 4);
+        assert_eq!(unreachable.rtt, None);
+        assert_eq!(unreachable.packet_loss, 1.0);
+
+        let near =
+            NetworkTestResult::from_probes("NorthEurope", vec![Duration::from_millis(10)], 4);
+        let best = NetworkTestResult::best(vec![result, unreachable, near.clone()]);
+        assert_eq!(best, Some(near));
+        assert_eq!(NetworkTestResult::best(vec![]), None);
+    }
+
+    #[tokio::test]
+    async fn network_test_probe() {
+        let api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+
+        // Error statuses still prove the host is reachable
+        let statuses = [
+            "200 OK",
+            "405 Method Not Allowed",
+            "404 Not Found",
+            "403 Forbidden",
+            "503 Service Unavailable",
+        ];
+        let (url, handle) = serve(statuses.iter().map(|status| (*status, "")).collect());
+        let result = api.probe("WestEurope", url).await;
+        assert_eq!(result.region, "WestEurope");
+        assert_eq!(result.packet_loss, 0.0);
+        assert!(result.rtt.is_some());
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), GssvApi::NETWORK_TEST_PROBES);
+        assert!(requests
+            .iter()
+            .all(|request| request.request_line == "HEAD / HTTP/1.1"));
+
+        // Refused connections are lost
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", closed.local_addr().unwrap())).unwrap();
+        drop(closed);
+        let result = api.probe("EastUS", url).await;
+        assert_eq!(result.packet_loss, 1.0);
+        assert_eq!(result.rtt, None);
+    }
+
+    fn region(name: &str, is_default: bool, fallback_priority: i32) -> OfferingRegion {
+        OfferingRegion {
+            name: name.into(),
+            base_uri: format!("https://{}.example.com", name.to_lowercase()),
+            network_test_hostname: None,
+            is_default,
+            system_update_groups: None,
+            fallback_priority,
+        }
+    }
+
+    #[test]
+    fn region_ordering() {
+        let settings = OfferingSettings {
+            allow_region_selection: true,
+            regions: vec![
+                region("EastUS", false, 3),
+                region("WestEurope", false, 1),
+                region("NorthEurope", true, 2),
+            ],
+            selectable_server_types: None,
+            client_cloud_settings: ClientCloudSettings {
+                environments: vec![],
+            },
+        };
+        assert_eq!(settings.default_region().unwrap().name, "NorthEurope");
+
+        let mut api = GssvApi::new(
+            Client::new(),
+            Url::parse(&settings.default_region().unwrap().base_uri).unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+        api.regions = settings.regions.clone();
+        let names: Vec<&str> = api
+            .list_regions()
+            .iter()
+            .map(|region| region.name.as_str())
+            .collect();
+        assert_eq!(names, ["NorthEurope", "WestEurope", "EastUS"]);
+
+        // Without a default, fallback priority decides
+        api.regions[2].is_default = false;
+        assert_eq!(api.list_regions()[0].name, "WestEurope");
+
+        let eastus = api.regions[0].clone();
+        assert!(matches!(
+            api.set_region(&eastus),
+            Err(GssvApiError::RegionSelectionDisabled)
+        ));
+        assert!(matches!(
+            api.with_region("westeurope"),
+            Err(GssvApiError::RegionSelectionDisabled)
+        ));
+    }
+
+    #[tokio::test]
+    async fn select_best_region_disabled() {
+        let mut api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://northeurope.example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+        api.regions = vec![region("NorthEurope", true, 1), region("EastUS", false, 2)];
+
+        assert!(matches!(
+            api.select_best_region().await,
+            Err(GssvApiError::RegionSelectionDisabled)
+        ));
+        assert_eq!(
+            api.url("/v5/sessions").unwrap().as_str(),
+            "https://northeurope.example.com/v5/sessions"
+        );
+    }
+
+    #[test]
+    fn default_headers() {
+        let mut api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://northeurope.example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+        api.default_headers_mut()
+            .insert("x-gssv-client", "XboxWindowsApp".parse().unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gssv-client", "XboxComBrowser".parse().unwrap());
+        headers.insert(header::USER_AGENT, "test".parse().unwrap());
+
+        let request = api
+            .request_with_headers(Method::GET, api.url("/v5/sessions").unwrap(), Some(headers))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-gssv-client"], "XboxWindowsApp");
+        assert_eq!(request.headers()[header::USER_AGENT], "test");
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Bearer token");
+    }
+
+    #[tokio::test]
+    async fn with_headers() {
+        let consoles = r#"{"totalItems":0,"continuationToken":null,"results":[]}"#;
+        let (url, handle) = serve(vec![("200 OK", consoles), ("200 OK", consoles)]);
+
+        let mut api = GssvApi::new(Client::new(), url, "token", "home").unwrap();
+        api.default_headers_mut()
+            .insert("x-gssv-client", "XboxWindowsApp".parse().unwrap());
+        api.default_headers_mut()
+            .insert("x-ms-flight", "default".parse().unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gssv-client", "XboxComBrowser".parse().unwrap());
+        headers.insert("x-experiment", "1".parse().unwrap());
+
+        api.with_headers(headers).get_consoles().await.unwrap();
+        // Overrides only apply to the copy
+        api.get_consoles().await.unwrap();
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].request_line, "GET /v6/servers/home HTTP/1.1");
+        assert_eq!(requests[0].headers["x-gssv-client"], "XboxComBrowser");
+        assert_eq!(requests[0].headers["x-experiment"], "1");
+        assert_eq!(requests[0].headers["x-ms-flight"], "default");
+        assert_eq!(requests[0].headers["authorization"], "Bearer token");
+
+        assert_eq!(requests[1].headers["x-gssv-client"], "XboxWindowsApp");
+        assert!(!requests[1].headers.contains_key("x-experiment"));
+    }
+
+    #[test]
+    fn with_region() {
+        let mut api = GssvApi::new(
+            Client::new(),
+            Url::parse("https://northeurope.example.com").unwrap(),
+            "token",
+            "cloud",
+        )
+        .unwrap();
+        api.regions = vec![region("NorthEurope", true, 1), region("EastUS", false, 2)];
+        api.allow_region_selection = true;
+
+        let api = api.with_region("eastus").unwrap();
+        assert_eq!(
+            api.url("/v5/sessions").unwrap().as_str(),
+            "https://eastus.example.com/v5/sessions"
+        );
+        assert!(matches!(
+            api.with_region("Mars"),
+            Err(GssvApiError::UnknownRegion(name)) if name == "Mars"
+        ));
+    }
+
+    #[test]
+    fn deserialize_keepalive_response() {
+        let resp =
+            serde_json::from_str::<KeepaliveResponse>(r#"{"aliveSeconds":240,"reason":"Alive"}"#)
+                .unwrap();
+        assert_eq!(resp.alive_seconds, Some(240));
+        assert_eq!(resp.reason, KeepaliveReason::Alive);
+        assert!(!resp.reason.is_terminal());
+
+        let resp = serde_json::from_str::<KeepaliveResponse>(
+            r#"{"aliveSeconds":null,"reason":"SessionTerminated"}"#,
+        )
+        .unwrap();
+        assert!(resp.reason.is_terminal());
+
+        let resp =
+            serde_json::from_str::<KeepaliveResponse>(r#"{"reason":"SomethingNew"}"#).unwrap();
+        assert_eq!(resp.reason, KeepaliveReason::Unknown("SomethingNew".into()));
+        assert_eq!(
+            serde_json::to_value(&resp).unwrap()["reason"],
+            serde_json::json!("SomethingNew")
+        );
+    }
+
+    #[test]
+    fn parse_json_keeps_body() {
+        match parse_json::<Vec<u32>>(r#"{"unexpected":true}"#) {
+            Err(GssvApiError::Deserialization { body, .. }) => {
+                assert_eq!(body, r#"{"unexpected":true}"#)
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let large = format!("\"{}\"", "\u{e9}".repeat(MAX_ERROR_BODY_LEN));
+        match parse_json::<Vec<u32>>(&large) {
+            Err(GssvApiError::Deserialization { body, .. }) => {
+                assert!(body.len() <= MAX_ERROR_BODY_LEN + 3);
+                assert!(body.ends_with("..."));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
     }
 }
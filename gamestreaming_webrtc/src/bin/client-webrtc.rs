@@ -1,12 +1,16 @@
 use anyhow::Result;
 use gamestreaming_webrtc::api::{IceCandidate, SessionResponse};
+use gamestreaming_webrtc::h265_writer::H265Writer;
+use gamestreaming_webrtc::media::{
+    tee_sink, writer_sink, MediaFrame, MediaWriter, OnFrameHdlrFn, RawRtpWriter,
+};
 use std::collections::HashMap;
 use std::fs::File;
 use std::sync::Arc;
-use tokio::sync::{Mutex, Notify};
+use tokio::sync::{watch, Mutex};
 use tokio::time::Duration;
 use webrtc::api::interceptor_registry::register_default_interceptors;
-use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS};
+use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_H264};
 use webrtc::api::APIBuilder;
 use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
@@ -17,21 +21,17 @@ use webrtc::interceptor::registry::Registry;
 use webrtc::media::io::h264_writer::H264Writer;
 use webrtc::media::io::ogg_writer::OggWriter;
 use webrtc::peer_connection::configuration::RTCConfiguration;
-use webrtc::peer_connection::math_rand_alpha;
 use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
-use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
 use webrtc::rtp_transceiver::rtp_codec::{
     RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType,
 };
-use webrtc::rtp_transceiver::rtp_receiver::RTCRtpReceiver;
 use webrtc::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
 use webrtc::rtp_transceiver::RTCRtpTransceiverInit;
-use webrtc::track::track_remote::TrackRemote;
 
-use gamestreaming_webrtc::{GamestreamingClient, Platform};
+use gamestreaming_webrtc::{ChannelType, GamestreamingClient, Platform};
 use xal::utils::TokenStore;
 
 #[macro_use]
@@ -39,12 +39,17 @@ extern crate lazy_static;
 
 const TOKENS_FILEPATH: &str = "tokens.json";
 
-#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
-struct DataChannelParams {
-    id: i32,
-    protocol: &'static str,
-    is_ordered: Option<bool>,
-}
+const MIME_TYPE_HEVC: &str = "video/H265";
+const MIME_TYPE_AV1: &str = "video/AV1";
+
+/// Video codecs to register additionally to the defaults
+/// (mime type, payload type)
+///
+/// H264 and Opus are part of the defaults. The payload types are only
+/// the ones offered, the answer may assign others (see
+/// `GamestreamingClient::negotiated_codecs`), which webrtc-rs picks up
+/// from the remote description.
+const VIDEO_CODECS: &[(&str, u8)] = &[(MIME_TYPE_HEVC, 104), (MIME_TYPE_AV1, 41)];
 
 lazy_static! {
     static ref PEER_CONNECTION_MUTEX: Arc<Mutex<Option<Arc<RTCPeerConnection>>>> =
@@ -54,40 +59,6 @@ lazy_static! {
     static ref GATHERED_CANDIDATES: Arc<Mutex<Vec<RTCIceCandidate>>> = Arc::new(Mutex::new(vec![]));
 }
 
-async fn save_to_disk(
-    writer: Arc<Mutex<dyn webrtc::media::io::Writer + Send + Sync>>,
-    track: Arc<TrackRemote>,
-    notify: Arc<Notify>,
-) -> Result<()> {
-    loop {
-        tokio::select! {
-            result = track.read_rtp() => {
-                if let Ok((rtp_packet, _)) = result {
-                    let mut w = writer.lock().await;
-                    w.write_rtp(&rtp_packet)?;
-                }else{
-                    println!("file closing begin after read_rtp error");
-                    let mut w = writer.lock().await;
-                    if let Err(err) = w.close() {
-                        println!("file close err: {}", err);
-                    }
-                    println!("file closing end after read_rtp error");
-                    return Ok(());
-                }
-            }
-            _ = notify.notified() => {
-                println!("file closing begin after notified");
-                let mut w = writer.lock().await;
-                if let Err(err) = w.close() {
-                    println!("file close err: {}", err);
-                }
-                println!("file closing end after notified");
-                return Ok(());
-            }
-        }
-    }
-}
-
 async fn create_peer_connection() -> Result<RTCPeerConnection, webrtc::Error> {
     // Prepare the configuration
     let config = RTCConfiguration {
@@ -101,35 +72,22 @@ async fn create_peer_connection() -> Result<RTCPeerConnection, webrtc::Error> {
     // Create a MediaEngine object to configure the supported codec
     let mut m = MediaEngine::default();
     m.register_default_codecs()?;
-    m.register_codec(
-        RTCRtpCodecParameters {
-            capability: RTCRtpCodecCapability {
-                mime_type: MIME_TYPE_H264.to_owned(),
-                clock_rate: 90000,
-                channels: 0,
-                sdp_fmtp_line: "".to_owned(),
-                rtcp_feedback: vec![],
+    for (mime_type, payload_type) in VIDEO_CODECS {
+        m.register_codec(
+            RTCRtpCodecParameters {
+                capability: RTCRtpCodecCapability {
+                    mime_type: mime_type.to_string(),
+                    clock_rate: 90000,
+                    channels: 0,
+                    sdp_fmtp_line: "".to_owned(),
+                    rtcp_feedback: vec![],
+                },
+                payload_type: *payload_type,
+                ..Default::default()
             },
-            payload_type: 102,
-            ..Default::default()
-        },
-        RTPCodecType::Video,
-    )?;
-
-    m.register_codec(
-        RTCRtpCodecParameters {
-            capability: RTCRtpCodecCapability {
-                mime_type: MIME_TYPE_OPUS.to_owned(),
-                clock_rate: 48000,
-                channels: 2,
-                sdp_fmtp_line: "".to_owned(),
-                rtcp_feedback: vec![],
-            },
-            payload_type: 111,
-            ..Default::default()
-        },
-        RTPCodecType::Audio,
-    )?;
+            RTPCodecType::Video,
+        )?;
+    }
 
     let mut registry = Registry::new();
 
@@ -158,18 +116,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let xcloud = GamestreamingClient::create(
+    let mut xcloud = GamestreamingClient::create(
         Platform::Cloud,
         &ts.gssv_token.token_data.token,
         &ts.xcloud_transfer_token.lpt,
     )
     .await?;
 
+    if !xcloud.allows_region_selection() {
+        println!("Region selection not allowed, using default");
+    } else {
+        match xcloud.select_best_region().await? {
+            Some(result) => println!("Selected region: {:?}", result),
+            None => println!("No region reachable for network test, using default"),
+        }
+    }
+
     let session = match xcloud.lookup_games().await?.first() {
         Some(title) => {
             println!("Starting title: {:?}", title);
             let session = xcloud.start_stream_xcloud(&title.title_id).await?;
-            println!("Session started successfully: {:?}", session);
+            println!("Session started successfully: {}", session);
 
             session
         }
@@ -215,48 +182,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }))
         .await;
 
-    let channel_params: HashMap<String, DataChannelParams> = [
-        (
-            "input".into(),
-            DataChannelParams {
-                id: 3,
-                protocol: "1.0".into(),
-                is_ordered: Some(true),
-            },
-        ),
-        (
-            "control".into(),
-            DataChannelParams {
-                id: 4,
-                protocol: "controlV1".into(),
-                is_ordered: None,
-            },
-        ),
-        (
-            "message".into(),
-            DataChannelParams {
-                id: 5,
-                protocol: "messageV1".into(),
-                is_ordered: None,
-            },
-        ),
-        (
-            "chat".into(),
-            DataChannelParams {
-                id: 6,
-                protocol: "chatV1".into(),
-                is_ordered: None,
-            },
-        ),
-    ]
-    .into();
-
-    let mut channel_defs: HashMap<String, Arc<RTCDataChannel>> = HashMap::new();
-    // Create channels and store in HashMap
-    for (name, params) in channel_params.into_iter() {
+    let mut channel_defs: Vec<(ChannelType, Arc<RTCDataChannel>)> = vec![];
+    // Create channels
+    for channel_type in ChannelType::DATA_CHANNELS {
+        let name = channel_type.name();
+        let params = channel_type
+            .params()
+            .expect("Data channel without parameters");
         let chan = peer_connection
             .create_data_channel(
-                &name,
+                name,
                 Some(RTCDataChannelInit {
                     ordered: params.is_ordered,
                     protocol: Some(params.protocol.to_owned()),
@@ -265,7 +200,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             )
             .await?;
 
-        channel_defs.insert(name, chan);
+        channel_defs.push((channel_type, chan));
     }
 
     // Allow us to receive 1 audio track, and 1 video track
@@ -289,56 +224,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
 
     let (done_tx, mut done_rx) = tokio::sync::mpsc::channel::<()>(1);
+    // Signals the connection got interrupted, triggers an ICE restart
+    let (restart_tx, mut restart_rx) = tokio::sync::mpsc::channel::<()>(1);
 
-    // Set the handler for Peer connection state
+    // Shutdown signal for all spawned tasks (PLI sender, track writers, data channels)
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    // Observe Peer connection state
     // This will notify you when the peer has connected/disconnected
-    peer_connection
-        .on_peer_connection_state_change(Box::new(move |s: RTCPeerConnectionState| {
+    xcloud.observe_peer_connection(&peer_connection).await;
+    let mut peer_state = xcloud.peer_state_stream();
+    tokio::spawn(async move {
+        while peer_state.changed().await.is_ok() {
+            let s = *peer_state.borrow();
             println!("Peer Connection State has changed: {}", s);
 
-            if s == RTCPeerConnectionState::Failed {
+            if s == RTCPeerConnectionState::Disconnected {
+                // Transient, e.g. on network change. Try to recover via ICE restart
+                let _ = restart_tx.try_send(());
+            } else if s == RTCPeerConnectionState::Failed || s == RTCPeerConnectionState::Closed {
                 // Wait until PeerConnection has had no network activity for 30 seconds or another failure. It may be reconnected using an ICE Restart.
                 // Use webrtc.PeerConnectionStateDisconnected if you are interested in detecting faster timeout.
                 // Note that the PeerConnection may come back from PeerConnectionStateDisconnected.
-                println!("Peer Connection has gone to failed exiting");
+                println!("Peer Connection has gone to {} exiting", s);
                 let _ = done_tx.try_send(());
+                break;
             }
-
-            Box::pin(async {})
-        }))
-        .await;
+        }
+    });
 
     // Register channel opening / on message handling
 
-    for (name, channel) in channel_defs.into_iter() {
-        let d1 = Arc::clone(&channel);
-        channel
-            .on_open(Box::new(move || {
-                println!("Data channel '{}'-'{}' open", d1.label(), d1.id());
-
-                let d2 = Arc::clone(&d1);
-                Box::pin(async move {
-                    let mut result = Result::<usize, webrtc::Error>::Ok(0);
-                    while result.is_ok() {
-                        let timeout = tokio::time::sleep(Duration::from_secs(5));
-                        tokio::pin!(timeout);
-
-                        tokio::select! {
-                            _ = timeout.as_mut() =>{
-                                /*
-                                From example code - Sending random strings over datachannel
-                                let message = math_rand_alpha(15);
-                                println!("Sending '{}'", message);
-                                result = d2.send_text(message).await.map_err(Into::into);
-                                */
-                            }
-                        };
-                    }
-                })
-            }))
-            .await;
+    for (channel_type, channel) in channel_defs.into_iter() {
+        // Tracks open state, sends the message channel handshake
+        xcloud.observe_data_channel(channel_type, &channel).await;
+        if channel_type == ChannelType::Message {
+            continue;
+        }
 
-        let message_label = name.clone();
+        let message_label = channel_type.name();
         channel
             .on_message(Box::new(move |msg: DataChannelMessage| {
                 let msg_str = match String::from_utf8(msg.data.to_vec()) {
@@ -356,69 +280,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .await;
     }
 
-    let (video_file, audio_file) = ("video.mkv", "audio.ogg");
-
-    let h264_writer: Arc<Mutex<dyn webrtc::media::io::Writer + Send + Sync>> =
-        Arc::new(Mutex::new(H264Writer::new(File::create(video_file)?)));
-    let ogg_writer: Arc<Mutex<dyn webrtc::media::io::Writer + Send + Sync>> = Arc::new(Mutex::new(
-        OggWriter::new(File::create(audio_file)?, 48000, 2)?,
-    ));
-
-    let notify_tx = Arc::new(Notify::new());
-    let notify_rx = notify_tx.clone();
-
-    // Set a handler for when a new remote track starts, this handler saves buffers to disk as
-    // an ivf file, since we could have multiple video tracks we provide a counter.
-    // In your application this is where you would handle/process video
-    let pc = Arc::downgrade(&peer_connection);
-    peer_connection.on_track(Box::new(move |track: Option<Arc<TrackRemote>>, _receiver: Option<Arc<RTCRtpReceiver>>| {
-        if let Some(track) = track {
-            // Send a PLI on an interval so that the publisher is pushing a keyframe every rtcpPLIInterval
-            let media_ssrc = track.ssrc();
-            let pc2 = pc.clone();
-            tokio::spawn(async move {
-                let mut result = Result::<usize>::Ok(0);
-                while result.is_ok() {
-                    let timeout = tokio::time::sleep(Duration::from_secs(3));
-                    tokio::pin!(timeout);
-
-                    tokio::select! {
-                        _ = timeout.as_mut() =>{
-                            if let Some(pc) = pc2.upgrade(){
-                                result = pc.write_rtcp(&[Box::new(PictureLossIndication{
-                                    sender_ssrc: 0,
-                                    media_ssrc,
-                                })]).await.map_err(Into::into);
-                            }else {
-                                break;
-                            }
-                        }
-                    };
-                }
-            });
-
-            let notify_rx2 = Arc::clone(&notify_rx);
-            let h264_writer2 = Arc::clone(&h264_writer);
-            let ogg_writer2 = Arc::clone(&ogg_writer);
-            Box::pin(async move {
-                let codec = track.codec().await;
-                let mime_type = codec.capability.mime_type.to_lowercase();
-                if mime_type == MIME_TYPE_OPUS.to_lowercase() {
-                    println!("Got Opus track, saving to disk as output.opus (48 kHz, 2 channels)");     
-                    tokio::spawn(async move {
-                        let _ = save_to_disk(ogg_writer2, track, notify_rx2).await;
-                    });
-                } else if mime_type == MIME_TYPE_H264.to_lowercase() {
-                    println!("Got h264 track, saving to disk as output.h264");
-                     tokio::spawn(async move {
-                         let _ = save_to_disk(h264_writer2, track, notify_rx2).await;
-                     });
+    let h264_writer: MediaWriter =
+        Arc::new(Mutex::new(H264Writer::new(File::create("video.h264")?)));
+    let h265_writer: MediaWriter =
+        Arc::new(Mutex::new(H265Writer::new(File::create("video.h265")?)));
+    let ogg_writer: MediaWriter = Arc::new(Mutex::new(OggWriter::new(
+        File::create("audio.ogg")?,
+        48000,
+        2,
+    )?));
+    // Raw audio + video RTP packets, for replaying depacketization offline
+    let raw_writer: MediaWriter =
+        Arc::new(Mutex::new(RawRtpWriter::new(File::create("stream.rtp")?)));
+
+    // Save frames to disk, routing video by its (lowercase) mime type
+    let video_writers: HashMap<String, MediaWriter> = [
+        (MIME_TYPE_H264.to_lowercase(), Arc::clone(&h264_writer)),
+        (MIME_TYPE_HEVC.to_lowercase(), Arc::clone(&h265_writer)),
+    ]
+    .into();
+    let video_sink: OnFrameHdlrFn = Box::new(move |frame: MediaFrame| {
+        let writer = video_writers.get(&frame.mime_type.to_lowercase()).cloned();
+        Box::pin(async move {
+            if let Some(writer) = writer {
+                if let Err(err) = writer.lock().await.write_rtp(&frame.packet) {
+                    println!("Failed to write {} frame: {}", frame.mime_type, err);
                 }
-            })
-        }else {
-            Box::pin(async {})
-        }
-	})).await;
+            }
+        })
+    });
+    xcloud
+        .on_video_frame(tee_sink(vec![
+            video_sink,
+            writer_sink(Arc::clone(&raw_writer)),
+        ]))
+        .await;
+    xcloud
+        .on_audio_frame(tee_sink(vec![
+            writer_sink(Arc::clone(&ogg_writer)),
+            writer_sink(Arc::clone(&raw_writer)),
+        ]))
+        .await;
+    xcloud.observe_tracks(&peer_connection, shutdown_rx).await;
 
     // Create an offer to send to the other process
     let offer = peer_connection.create_offer(None).await?;
@@ -440,6 +343,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("Failed to set remote SDP answer: {:?}", sdp_fail);
                 return Err(sdp_fail.into());
             }
+            for codec in xcloud.negotiated_codecs() {
+                println!(
+                    "Negotiated {} (payload type {})",
+                    codec.mime_type, codec.payload_type
+                );
+            }
         }
         None => {
             peer_connection.close().await?;
@@ -468,8 +377,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Adding remote ICE candidates");
     for candidate in ice_response.exchange_response {
+        let candidate = candidate.normalized();
         println!("Adding remote ICE candidate={:?}", candidate);
-        if candidate.candidate.contains("end-of-candidates") {
+        if candidate.is_end_of_candidates() {
             println!("End of candidates, jumping out");
             break;
         }
@@ -482,15 +392,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         peer_connection.add_ice_candidate(c).await?;
     }
 
-    println!("Press ctrl-c to stop");
     tokio::select! {
-        _ = done_rx.recv() => {
-            println!("received done signal!");
-        }
-        _ = tokio::signal::ctrl_c() => {
-            println!("");
+        _ = xcloud.wait_until_ready() => println!("Data channels ready"),
+        _ = tokio::time::sleep(Duration::from_secs(10)) => {
+            println!("Timeout waiting for data channels, input may be dropped");
         }
     };
 
+    println!("Press ctrl-c to stop");
+    loop {
+        tokio::select! {
+            _ = done_rx.recv() => {
+                println!("received done signal!");
+                break;
+            }
+            Some(_) = restart_rx.recv() => {
+                println!("Connection interrupted, restarting ICE");
+                if let Err(err) = xcloud.restart_ice(&session, &peer_connection).await {
+                    println!("ICE restart failed: {}", err);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("");
+                break;
+            }
+        };
+    }
+
+    println!("Stopping tasks");
+    let _ = shutdown_tx.send(true);
+    xcloud.wait_for_tracks().await;
+
+    for (filename, writer) in [
+        ("video.h264", h264_writer),
+        ("video.h265", h265_writer),
+        ("audio.ogg", ogg_writer),
+        ("stream.rtp", raw_writer),
+    ] {
+        if let Err(err) = writer.lock().await.close() {
+            println!("Failed to close {}: {}", filename, err);
+        }
+    }
+
+    println!("Closing peer connection");
+    peer_connection.close().await?;
+
+    println!("Terminating session and logging out");
+    xcloud.logout(std::slice::from_ref(&session)).await?;
+
     Ok(())
 }
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ChannelType {
     Chat,
     Control,
@@ -8,6 +8,99 @@ pub enum ChannelType {
     Video,
 }
 
+/// Parameters of a data channel, as expected by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataChannelParams {
+    pub id: u16,
+    pub protocol: &'static str,
+    pub is_ordered: Option<bool>,
+}
+
+impl ChannelType {
+    /// Channels that are carried over a data channel
+    pub const DATA_CHANNELS: [ChannelType; 4] = [
+        ChannelType::Input,
+        ChannelType::Control,
+        ChannelType::Message,
+        ChannelType::Chat,
+    ];
+
+    /// Label of the channel
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelType::Chat => "chat",
+            ChannelType::Control => "control",
+            ChannelType::Input => "input",
+            ChannelType::Message => "message",
+            ChannelType::Audio => "audio",
+            ChannelType::Video => "video",
+        }
+    }
+
+    /// Data channel parameters, `None` for media channels
+    pub fn params(&self) -> Option<DataChannelParams> {
+        let params = match self {
+            ChannelType::Input => DataChannelParams {
+                id: 3,
+                protocol: "1.0",
+                is_ordered: Some(true),
+            },
+            ChannelType::Control => DataChannelParams {
+                id: 4,
+                protocol: "controlV1",
+                is_ordered: None,
+            },
+            ChannelType::Message => DataChannelParams {
+                id: 5,
+                protocol: "messageV1",
+                is_ordered: None,
+            },
+            ChannelType::Chat => DataChannelParams {
+                id: 6,
+                protocol: "chatV1",
+                is_ordered: None,
+            },
+            ChannelType::Audio | ChannelType::Video => return None,
+        };
+
+        Some(params)
+    }
+}
+
+/// Open / handshake state of the data channels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelReadiness {
+    pub input_open: bool,
+    pub control_open: bool,
+    pub message_open: bool,
+    pub chat_open: bool,
+    /// Server acked the message channel handshake
+    pub handshake_acked: bool,
+}
+
+impl ChannelReadiness {
+    pub fn set_open(&mut self, channel_type: ChannelType, open: bool) {
+        match channel_type {
+            ChannelType::Input => self.input_open = open,
+            ChannelType::Control => self.control_open = open,
+            ChannelType::Message => {
+                self.message_open = open;
+                if !open {
+                    self.handshake_acked = false;
+                }
+            }
+            ChannelType::Chat => self.chat_open = open,
+            ChannelType::Audio | ChannelType::Video => {}
+        }
+    }
+
+    /// Input can be sent: handshake is acked and the control / input
+    /// channels are open
+    pub fn is_ready(&self) -> bool {
+        self.handshake_acked && self.message_open && self.control_open && self.input_open
+    }
+}
+
 #[derive(Debug)]
 pub struct GssvChannelEvent(String);
 
@@ -44,9 +137,56 @@ pub trait GssvChannel {
         todo!("Channel start not implemented")
     }
     fn on_message(&self, msg: &DataChannelMsg) -> Result<(), Box<dyn std::error::Error>> {
-        println!("on_message ({}): {:?}", Self::name(), msg);
+        tracing::debug!(target: "gssv::channel", channel = Self::name(), ?msg, "Message received");
         todo!()
     }
     fn send_message(&self, msg: &DataChannelMsg);
     fn send_event(&self, event: &GssvChannelEvent);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_channel_params() {
+        let params: Vec<(&str, u16, &str)> = ChannelType::DATA_CHANNELS
+            .iter()
+            .map(|c| {
+                let p = c.params().expect("Missing data channel params");
+                (c.name(), p.id, p.protocol)
+            })
+            .collect();
+
+        assert_eq!(
+            params,
+            vec![
+                ("input", 3, "1.0"),
+                ("control", 4, "controlV1"),
+                ("message", 5, "messageV1"),
+                ("chat", 6, "chatV1"),
+            ]
+        );
+        assert_eq!(ChannelType::Video.params(), None);
+    }
+
+    #[test]
+    fn channel_readiness() {
+        let mut readiness = ChannelReadiness::default();
+        for channel_type in ChannelType::DATA_CHANNELS {
+            readiness.set_open(channel_type, true);
+        }
+        assert!(!readiness.is_ready());
+
+        readiness.handshake_acked = true;
+        assert!(readiness.is_ready());
+
+        readiness.set_open(ChannelType::Chat, false);
+        assert!(readiness.is_ready());
+
+        // Handshake needs to be redone on a reopened message channel
+        readiness.set_open(ChannelType::Message, false);
+        readiness.set_open(ChannelType::Message, true);
+        assert!(!readiness.is_ready());
+    }
+}
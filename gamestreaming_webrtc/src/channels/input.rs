@@ -1,12 +1,30 @@
 use deku::{DekuContainerRead, DekuContainerWrite};
-use tokio::time::{Instant, Interval};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 
 use super::base::{DataChannelMsg, GssvChannel, GssvChannelEvent};
+use super::proxy::ChannelSender;
+use crate::error::GsError;
 use crate::packets::input::{
     ClientMetadataReport, GamepadData, GamepadReport, InputMetadataEntry, InputPacket,
     MetadataReport,
 };
 
+/// Interval in which queued input is sent
+const INPUT_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Handle of the task started by `InputChannel::start_tick`,
+/// the task is stopped once the handle is dropped.
+pub struct InputTickHandle {
+    task: JoinHandle<()>,
+}
+
+impl Drop for InputTickHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 pub struct InputChannel {
     time_origin: Instant,
     input_sequence_num: u32,
@@ -16,6 +34,12 @@ pub struct InputChannel {
     rumble_enabled: bool,
 }
 
+impl Default for InputChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GssvChannel for InputChannel {
     fn name() -> &'static str {
         "Input"
@@ -42,12 +66,17 @@ impl GssvChannel for InputChannel {
     }
 
     fn on_message(&self, msg: &DataChannelMsg) -> Result<(), Box<dyn std::error::Error>> {
-        println!("on_message ({}): {:?}", Self::name(), msg);
+        tracing::debug!(target: "gssv::channel", channel = Self::name(), ?msg, "Message received");
 
         match msg {
             DataChannelMsg::Bytes(bytes) => {
                 let (_, input_packet) = InputPacket::from_bytes((bytes, 0))?;
-                println!("[{}] Received packet: {:?}", Self::name(), input_packet);
+                tracing::debug!(
+                    target: "gssv::channel",
+                    channel = Self::name(),
+                    ?input_packet,
+                    "Input packet received"
+                );
                 todo!("Handle input packet")
             }
             val => Err(format!("[{}] Unhandled message type: {:?}", Self::name(), val).into()),
@@ -64,23 +93,104 @@ impl GssvChannel for InputChannel {
 }
 
 impl InputChannel {
+    /// Create input channel, sequence numbers and timestamps
+    /// start from zero.
+    ///
+    /// NOTE: Needs to be called from within a tokio runtime.
+    pub fn new() -> Self {
+        Self {
+            time_origin: Instant::now(),
+            input_sequence_num: 0,
+            metadata_queue: vec![],
+            input_frames: vec![],
+            input_interval: time::interval(INPUT_INTERVAL),
+            rumble_enabled: false,
+        }
+    }
+
+    /// Sequence number that is assigned to the next input packet
+    pub fn sequence_num(&self) -> u32 {
+        self.input_sequence_num
+    }
+
     fn next_sequence_num(&mut self) -> u32 {
         let current = self.input_sequence_num;
-        self.input_sequence_num += 1;
+        self.input_sequence_num = self.input_sequence_num.wrapping_add(1);
         current
     }
 
-    /// Get seconds since instantiation of this
+    /// Get milliseconds since instantiation of this
     /// channel.
     fn timestamp(&self) -> f64 {
-        self.time_origin.elapsed().as_secs_f64()
+        self.time_origin.elapsed().as_secs_f64() * 1000.0
+    }
+
+    /// Send gamepad data via `sender`, together with any queued input.
+    /// Sequence number and timestamp are assigned automatically.
+    pub fn send_gamepad(
+        &mut self,
+        sender: &ChannelSender,
+        data: GamepadData,
+    ) -> Result<(), GsError> {
+        self.on_button_press(data);
+        let packet = self.create_input_packet().to_bytes()?;
+        sender.send_bytes(packet)
+    }
+
+    /// Send the gamepad state returned by `state_fn` `rate_hz` times
+    /// per second via `sender`, together with any queued input.
+    ///
+    /// Ticks that are missed (e.g. by a slow `state_fn`) are skipped
+    /// instead of being sent in a burst. Sending stops when the returned
+    /// handle is dropped or the channel is closed.
+    ///
+    /// NOTE: Needs to be called from within a tokio runtime.
+    /// Panics if `rate_hz` is zero.
+    pub fn start_tick<F>(
+        mut self,
+        sender: ChannelSender,
+        rate_hz: u32,
+        mut state_fn: F,
+    ) -> InputTickHandle
+    where
+        F: FnMut() -> GamepadData + Send + 'static,
+    {
+        assert!(rate_hz > 0, "Tick rate must be non-zero");
+        let period = Duration::from_secs_f64(1.0 / f64::from(rate_hz));
+
+        let task = tokio::spawn(async move {
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            loop {
+                interval.tick().await;
+                self.on_button_press(state_fn());
+                let packet = match self.create_input_packet().to_bytes() {
+                    Ok(packet) => packet,
+                    Err(err) => {
+                        tracing::warn!(
+                            target: "gssv::channel",
+                            channel = Self::name(),
+                            %err,
+                            "Failed to serialize input packet"
+                        );
+                        continue;
+                    }
+                };
+                if sender.send_bytes(packet).is_err() {
+                    break;
+                }
+            }
+        });
+
+        InputTickHandle { task }
     }
 
     /// Handle incoming gamepad data.
     /// Stores the data into queue until drained
     /// by a call to `create_input_packet`
     fn on_button_press(&mut self, data: GamepadData) {
-        println!("Received gamepad data");
+        tracing::trace!(target: "gssv::channel", ?data, "Gamepad data queued");
         self.input_frames.push(data);
     }
 
@@ -124,3 +234,89 @@ impl InputChannel {
         self.metadata_queue.push(metadata);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::ChannelType;
+
+    #[tokio::test]
+    async fn input_packet_sequence() {
+        let mut channel = InputChannel::new();
+        assert_eq!(channel.sequence_num(), 0);
+
+        channel.on_button_press(GamepadData::default());
+        let first = channel.create_input_packet();
+        time::sleep(Duration::from_millis(5)).await;
+        channel.on_button_press(GamepadData::default());
+        let second = channel.create_input_packet();
+
+        assert_eq!(first.sequence_num(), Some(0));
+        assert_eq!(second.sequence_num(), Some(1));
+        assert_eq!(channel.sequence_num(), 2);
+
+        let first_ts = first.timestamp().expect("No timestamp");
+        let second_ts = second.timestamp().expect("No timestamp");
+        assert!(second_ts - first_ts >= 5.0);
+    }
+
+    #[tokio::test]
+    async fn send_gamepad() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sender = ChannelSender::new(ChannelType::Input, tx);
+        let mut channel = InputChannel::new();
+
+        for _ in 0..2 {
+            channel
+                .send_gamepad(&sender, GamepadData::default())
+                .expect("Failed to send gamepad data");
+        }
+
+        for expected in 0..2 {
+            let (channel_type, msg) = rx.recv().await.expect("No message sent");
+            assert_eq!(channel_type, ChannelType::Input);
+            let bytes = match msg {
+                DataChannelMsg::Bytes(bytes) => bytes,
+                other => panic!("Unexpected message: {:?}", other),
+            };
+            let (_, packet) = InputPacket::from_bytes((&bytes, 0)).unwrap();
+            assert_eq!(packet.sequence_num(), Some(expected));
+        }
+
+        drop(rx);
+        assert!(matches!(
+            channel.send_gamepad(&sender, GamepadData::default()),
+            Err(GsError::ChannelClosed(ChannelType::Input))
+        ));
+    }
+
+    #[tokio::test]
+    async fn input_tick() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sender = ChannelSender::new(ChannelType::Input, tx);
+
+        let handle = InputChannel::new().start_tick(sender, 100, || GamepadData {
+            left_trigger: 0x1234,
+            ..Default::default()
+        });
+        time::sleep(Duration::from_millis(55)).await;
+        drop(handle);
+
+        let mut sequence_nums = vec![];
+        while let Some((_, msg)) = rx.recv().await {
+            let bytes = match msg {
+                DataChannelMsg::Bytes(bytes) => bytes,
+                other => panic!("Unexpected message: {:?}", other),
+            };
+            let (_, packet) = InputPacket::from_bytes((&bytes, 0)).unwrap();
+            sequence_nums.push(packet.sequence_num().unwrap());
+        }
+
+        // Stopped sending (the sender was dropped) after a few ticks
+        assert!(sequence_nums.len() >= 3);
+        assert_eq!(
+            sequence_nums,
+            (0..sequence_nums.len() as u32).collect::<Vec<u32>>()
+        );
+    }
+}
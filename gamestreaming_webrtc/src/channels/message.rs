@@ -1,4 +1,4 @@
-use super::base::{DataChannelMsg, GssvChannel, GssvChannelEvent};
+use super::base::{ChannelType, DataChannelMsg, GssvChannel, GssvChannelEvent};
 use serde_json::{json, Value};
 
 pub struct MessageChannel;
@@ -9,13 +9,7 @@ impl GssvChannel for MessageChannel {
     }
 
     fn on_open(&self) {
-        let handshake = json!({
-            "type":"Handshake",
-            "version":"messageV1",
-            "id":"0ab125e2-6eee-4687-a2f4-5cfb347f0643",
-            "cv":"",
-        });
-        self.send_message(&handshake.into())
+        self.send_message(&Self::handshake().into())
     }
 
     fn on_close(&self) {
@@ -38,7 +32,7 @@ impl GssvChannel for MessageChannel {
     }
 
     fn on_message(&self, msg: &DataChannelMsg) -> Result<(), Box<dyn std::error::Error>> {
-        println!("on_message ({}): {:?}", Self::name(), msg);
+        tracing::debug!(target: "gssv::channel", channel = Self::name(), ?msg, "Message received");
 
         let json_msg: Value = msg.try_into()?;
         let msg_type = json_msg.get("type").unwrap().as_str().unwrap();
@@ -127,6 +121,23 @@ impl GssvChannel for MessageChannel {
 }
 
 impl MessageChannel {
+    /// Handshake to send once the channel is open, acked by the
+    /// server with `HandshakeAck`
+    pub(crate) fn handshake() -> Value {
+        json!({
+            "type":"Handshake",
+            "version": ChannelType::Message.params().map(|p| p.protocol),
+            "id":"0ab125e2-6eee-4687-a2f4-5cfb347f0643",
+            "cv":"",
+        })
+    }
+
+    pub(crate) fn is_handshake_ack(msg: &DataChannelMsg) -> bool {
+        Value::try_from(msg)
+            .map(|json_msg| json_msg.get("type") == Some(&json!("HandshakeAck")))
+            .unwrap_or(false)
+    }
+
     fn generate_message(
         path: &str,
         data: &Value,
@@ -153,3 +164,23 @@ impl MessageChannel {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_ack() {
+        let ack = DataChannelMsg::String(r#"{"type":"HandshakeAck","version":"messageV1"}"#.into());
+        assert!(MessageChannel::is_handshake_ack(&ack));
+
+        let handshake: DataChannelMsg = MessageChannel::handshake().into();
+        assert!(!MessageChannel::is_handshake_ack(&handshake));
+        assert!(!MessageChannel::is_handshake_ack(&DataChannelMsg::Bytes(
+            vec![0x7B, 0x7D]
+        )));
+        assert!(!MessageChannel::is_handshake_ack(&DataChannelMsg::String(
+            "not json".into()
+        )));
+    }
+}
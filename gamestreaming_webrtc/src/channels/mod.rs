@@ -5,3 +5,15 @@ mod chat;
 mod control;
 mod input;
 mod message;
+mod proxy;
+
+#[cfg(feature = "webrtc-rs")]
+pub(crate) use base::{ChannelReadiness, DataChannelMsg};
+pub use base::{ChannelType, DataChannelParams};
+#[cfg(feature = "webrtc-rs")]
+pub(crate) use message::MessageChannel;
+#[cfg(all(test, feature = "webrtc-rs"))]
+pub(crate) use proxy::tests::connected_channel;
+#[cfg(feature = "webrtc-rs")]
+pub use proxy::ChannelProxy;
+pub use proxy::ChannelSender;
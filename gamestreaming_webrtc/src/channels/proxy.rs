@@ -0,0 +1,313 @@
+#[cfg(feature = "webrtc-rs")]
+use std::collections::HashMap;
+#[cfg(feature = "webrtc-rs")]
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::mpsc;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::data_channel::RTCDataChannel;
+
+use super::base::{ChannelType, DataChannelMsg};
+use crate::error::GsError;
+#[cfg(feature = "webrtc-rs")]
+use crate::sdp::SctpParameters;
+
+type ChannelMsg = (ChannelType, DataChannelMsg);
+
+/// Cloneable handle queueing messages for a single data channel
+#[derive(Debug, Clone)]
+pub struct ChannelSender {
+    channel_type: ChannelType,
+    tx: mpsc::UnboundedSender<ChannelMsg>,
+}
+
+impl ChannelSender {
+    pub(crate) fn new(channel_type: ChannelType, tx: mpsc::UnboundedSender<ChannelMsg>) -> Self {
+        Self { channel_type, tx }
+    }
+
+    /// Channel the messages are sent on
+    pub fn channel_type(&self) -> ChannelType {
+        self.channel_type
+    }
+
+    pub fn send_text(&self, text: impl Into<String>) -> Result<(), GsError> {
+        self.send(DataChannelMsg::String(text.into()))
+    }
+
+    pub fn send_bytes(&self, data: Vec<u8>) -> Result<(), GsError> {
+        self.send(DataChannelMsg::Bytes(data))
+    }
+
+    pub fn send_json(&self, value: &serde_json::Value) -> Result<(), GsError> {
+        self.send(value.clone().into())
+    }
+
+    fn send(&self, msg: DataChannelMsg) -> Result<(), GsError> {
+        self.tx
+            .send((self.channel_type, msg))
+            .map_err(|_| GsError::ChannelClosed(self.channel_type))
+    }
+}
+
+/// Routes messages queued via `ChannelSender`s to the attached
+/// `RTCDataChannel` of their channel type.
+///
+/// Messages for a channel that is not attached are dropped.
+#[cfg(feature = "webrtc-rs")]
+pub struct ChannelProxy {
+    channels: Mutex<HashMap<ChannelType, Arc<RTCDataChannel>>>,
+    sctp_parameters: Mutex<Option<SctpParameters>>,
+    tx: mpsc::UnboundedSender<ChannelMsg>,
+}
+
+#[cfg(feature = "webrtc-rs")]
+impl ChannelProxy {
+    /// Create a proxy and spawn its routing task.
+    ///
+    /// The task ends once the proxy is dropped.
+    pub fn spawn() -> Arc<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let proxy = Arc::new(Self {
+            channels: Mutex::new(HashMap::new()),
+            sctp_parameters: Mutex::new(None),
+            tx,
+        });
+        tokio::spawn(Self::run(Arc::downgrade(&proxy), rx));
+        proxy
+    }
+
+    /// Get a sender for `channel_type`, `None` for media channels
+    pub fn sender(&self, channel_type: ChannelType) -> Option<ChannelSender> {
+        channel_type.params()?;
+        Some(ChannelSender::new(channel_type, self.tx.clone()))
+    }
+
+    /// Route messages for `channel_type` to `channel`, replacing
+    /// any previously attached one
+    pub fn attach(&self, channel_type: ChannelType, channel: Arc<RTCDataChannel>) {
+        self.channels.lock().unwrap().insert(channel_type, channel);
+    }
+
+    pub fn detach(&self, channel_type: ChannelType) {
+        self.channels.lock().unwrap().remove(&channel_type);
+    }
+
+    /// Detach and close all attached channels
+    ///
+    /// Channels failing to close are logged, the others are still closed.
+    pub async fn close_all(&self) {
+        let channels: Vec<_> = self.channels.lock().unwrap().drain().collect();
+        for (channel_type, channel) in channels {
+            tracing::debug!(
+                target: "gssv::channel",
+                channel = channel_type.name(),
+                "Closing channel"
+            );
+            if let Err(err) = channel.close().await {
+                tracing::warn!(
+                    target: "gssv::channel",
+                    channel = channel_type.name(),
+                    %err,
+                    "Failed to close channel"
+                );
+            }
+        }
+    }
+
+    /// Messages exceeding the negotiated `max-message-size` are dropped
+    pub fn set_sctp_parameters(&self, params: SctpParameters) {
+        *self.sctp_parameters.lock().unwrap() = Some(params);
+    }
+
+    async fn run(proxy: Weak<Self>, mut rx: mpsc::UnboundedReceiver<ChannelMsg>) {
+        while let Some((channel_type, msg)) = rx.recv().await {
+            let proxy = match proxy.upgrade() {
+                Some(proxy) => proxy,
+                None => break,
+            };
+            if let Err(err) = proxy.route(channel_type, msg).await {
+                tracing::warn!(
+                    target: "gssv::channel",
+                    channel = channel_type.name(),
+                    %err,
+                    "Failed to send message"
+                );
+            }
+        }
+    }
+
+    async fn route(&self, channel_type: ChannelType, msg: DataChannelMsg) -> Result<(), GsError> {
+        let channel = self.channels.lock().unwrap().get(&channel_type).cloned();
+        let channel = match channel {
+            Some(channel) => channel,
+            None => {
+                tracing::debug!(
+                    target: "gssv::channel",
+                    channel = channel_type.name(),
+                    "Channel not attached, dropping message"
+                );
+                return Ok(());
+            }
+        };
+
+        let len = match &msg {
+            DataChannelMsg::String(text) => text.len(),
+            DataChannelMsg::Bytes(data) => data.len(),
+        };
+        let sctp_parameters = *self.sctp_parameters.lock().unwrap();
+        if let Some(params) = sctp_parameters {
+            if !params.allows_message(len) {
+                return Err(GsError::MessageTooLarge {
+                    size: len,
+                    max: params.max_message_size,
+                });
+            }
+        }
+
+        match msg {
+            DataChannelMsg::String(text) => channel.send_text(text).await?,
+            DataChannelMsg::Bytes(data) => channel.send(&data.into()).await?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_sender() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = ChannelSender::new(ChannelType::Chat, tx);
+
+        sender.clone().send_text("hello").unwrap();
+        sender.send_json(&serde_json::json!({"a": 1})).unwrap();
+
+        match rx.try_recv().unwrap() {
+            (ChannelType::Chat, DataChannelMsg::String(text)) => assert_eq!(text, "hello"),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+        match rx.try_recv().unwrap() {
+            (ChannelType::Chat, DataChannelMsg::String(text)) => assert_eq!(text, r#"{"a":1}"#),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        drop(rx);
+        assert!(matches!(
+            sender.send_bytes(vec![1, 2]),
+            Err(GsError::ChannelClosed(ChannelType::Chat))
+        ));
+    }
+
+    /// Connect two in-process peers, returns the offerer, the answerer,
+    /// the open data channel `label` of the offerer and a receiver of
+    /// text messages arriving at the answerer
+    #[cfg(feature = "webrtc-rs")]
+    pub(crate) async fn connected_channel(
+        label: &str,
+    ) -> (
+        webrtc::peer_connection::RTCPeerConnection,
+        webrtc::peer_connection::RTCPeerConnection,
+        Arc<RTCDataChannel>,
+        mpsc::UnboundedReceiver<String>,
+    ) {
+        use webrtc::api::APIBuilder;
+        use webrtc::data_channel::data_channel_message::DataChannelMessage;
+        use webrtc::peer_connection::configuration::RTCConfiguration;
+
+        let api = APIBuilder::new().build();
+        let offerer = api
+            .new_peer_connection(RTCConfiguration::default())
+            .await
+            .unwrap();
+        let answerer = api
+            .new_peer_connection(RTCConfiguration::default())
+            .await
+            .unwrap();
+
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        answerer
+            .on_data_channel(Box::new(move |dc: Arc<RTCDataChannel>| {
+                let msg_tx = msg_tx.clone();
+                Box::pin(async move {
+                    dc.on_message(Box::new(move |msg: DataChannelMessage| {
+                        let _ = msg_tx.send(String::from_utf8(msg.data.to_vec()).unwrap());
+                        Box::pin(async {})
+                    }))
+                    .await;
+                })
+            }))
+            .await;
+
+        let channel = offerer.create_data_channel(label, None).await.unwrap();
+        let (open_tx, mut open_rx) = mpsc::unbounded_channel();
+        channel
+            .on_open(Box::new(move || {
+                let _ = open_tx.send(());
+                Box::pin(async {})
+            }))
+            .await;
+
+        let offer = offerer.create_offer(None).await.unwrap();
+        let mut gathering_complete = offerer.gathering_complete_promise().await;
+        offerer.set_local_description(offer).await.unwrap();
+        let _ = gathering_complete.recv().await;
+        answerer
+            .set_remote_description(offerer.local_description().await.unwrap())
+            .await
+            .unwrap();
+
+        let answer = answerer.create_answer(None).await.unwrap();
+        let mut gathering_complete = answerer.gathering_complete_promise().await;
+        answerer.set_local_description(answer).await.unwrap();
+        let _ = gathering_complete.recv().await;
+        offerer
+            .set_remote_description(answerer.local_description().await.unwrap())
+            .await
+            .unwrap();
+
+        open_rx.recv().await.unwrap();
+        (offerer, answerer, channel, msg_rx)
+    }
+
+    #[cfg(feature = "webrtc-rs")]
+    #[tokio::test]
+    async fn routing() {
+        let (_offerer, _answerer, channel, mut msg_rx) = connected_channel("chat").await;
+        let proxy = ChannelProxy::spawn();
+        proxy.attach(ChannelType::Chat, channel);
+
+        assert!(proxy.sender(ChannelType::Video).is_none());
+
+        // Control is not attached, its message is dropped
+        proxy
+            .sender(ChannelType::Control)
+            .unwrap()
+            .send_text("dropped")
+            .unwrap();
+        proxy
+            .sender(ChannelType::Chat)
+            .unwrap()
+            .send_text("hello")
+            .unwrap();
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(10), msg_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received, "hello");
+
+        // Detached channels don't receive further messages
+        proxy.detach(ChannelType::Chat);
+        proxy
+            .sender(ChannelType::Chat)
+            .unwrap()
+            .send_text("after detach")
+            .unwrap();
+        let received =
+            tokio::time::timeout(std::time::Duration::from_millis(500), msg_rx.recv()).await;
+        assert!(received.is_err());
+    }
+}
@@ -1,13 +1,42 @@
 use std::str::FromStr;
+#[cfg(feature = "webrtc-rs")]
+use std::sync::Arc;
 
 use chrono::{Duration, Utc};
+#[cfg(feature = "webrtc-rs")]
+use tokio::sync::{watch, Mutex};
+#[cfg(feature = "webrtc-rs")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::data_channel::{data_channel_message::DataChannelMessage, RTCDataChannel};
+#[cfg(feature = "webrtc-rs")]
+use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::peer_connection::{peer_connection_state::RTCPeerConnectionState, RTCPeerConnection};
+#[cfg(feature = "webrtc-rs")]
+use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::rtp_transceiver::rtp_receiver::RTCRtpReceiver;
+#[cfg(feature = "webrtc-rs")]
+use webrtc::track::track_remote::TrackRemote;
 
 use crate::api::GssvApi;
 use crate::api::{
-    ConsolesResponse, IceCandidate, IceExchangeResponse, SdpExchangeResponse, SessionResponse,
-    TitleResult,
+    ConsolesResponse, IceCandidate, IceExchangeResponse, NetworkTestResult, SdpConfiguration,
+    SdpExchangeResponse, SessionResponse, TitleResult,
+};
+#[cfg(feature = "webrtc-rs")]
+use crate::channels::{
+    ChannelProxy, ChannelReadiness, ChannelSender, ChannelType, DataChannelMsg, MessageChannel,
 };
 use crate::error::GsError;
+#[cfg(feature = "webrtc-rs")]
+use crate::media::{MediaFrame, OnFrameHdlrFn, OnTrackHdlrFn, RemoteTrack, TrackKind};
+use crate::sdp;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Platform {
@@ -38,28 +67,499 @@ impl ToString for Platform {
     }
 }
 
+/// How often `exchange_sdp` asks for the SDP answer before giving up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdpPolling {
+    pub attempts: u32,
+    pub interval: std::time::Duration,
+}
+
+impl Default for SdpPolling {
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            interval: std::time::Duration::from_millis(500),
+        }
+    }
+}
+
 pub struct GamestreamingClient {
     api: GssvApi,
     transfer_token: String,
     platform: Platform,
+    sdp_polling: SdpPolling,
+    /// Negotiated in the last SDP answer
+    sctp_parameters: std::sync::Mutex<Option<sdp::SctpParameters>>,
+    /// Bandwidth limits of the last SDP answer
+    bandwidth_limits: std::sync::Mutex<Vec<sdp::BandwidthLimit>>,
+    /// Codecs of the last SDP answer
+    negotiated_codecs: std::sync::Mutex<Vec<sdp::NegotiatedCodec>>,
+    #[cfg(feature = "webrtc-rs")]
+    peer_state: Arc<watch::Sender<RTCPeerConnectionState>>,
+    #[cfg(feature = "webrtc-rs")]
+    channel_state: Arc<watch::Sender<ChannelReadiness>>,
+    #[cfg(feature = "webrtc-rs")]
+    video_frame_handler: Arc<Mutex<Option<OnFrameHdlrFn>>>,
+    #[cfg(feature = "webrtc-rs")]
+    audio_frame_handler: Arc<Mutex<Option<OnFrameHdlrFn>>>,
+    #[cfg(feature = "webrtc-rs")]
+    track_handler: Arc<Mutex<Option<OnTrackHdlrFn>>>,
+    #[cfg(feature = "webrtc-rs")]
+    track_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    #[cfg(feature = "webrtc-rs")]
+    channel_proxy: Arc<ChannelProxy>,
 }
 
 impl GamestreamingClient {
     const CONNECTION_TIMEOUT_SECS: i64 = 30;
+    #[cfg(feature = "webrtc-rs")]
+    const PLI_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
 
     pub async fn create(
         platform: Platform,
         gssv_token: &str,
         xcloud_transfer_token: &str,
     ) -> Result<Self, GsError> {
-        Ok(Self {
-            api: match platform {
-                Platform::Cloud => GssvApi::login_xcloud(gssv_token).await?,
-                Platform::Home => GssvApi::login_xhome(gssv_token).await?,
-            },
+        Self::create_with_client(
+            platform,
+            reqwest::Client::new(),
+            gssv_token,
+            xcloud_transfer_token,
+        )
+        .await
+    }
+
+    /// Create a client, sending all API requests via `http_client`,
+    /// e.g. to apply proxy or TLS settings.
+    pub async fn create_with_client(
+        platform: Platform,
+        http_client: reqwest::Client,
+        gssv_token: &str,
+        xcloud_transfer_token: &str,
+    ) -> Result<Self, GsError> {
+        let api = match platform {
+            Platform::Cloud => GssvApi::login_xcloud_with_client(http_client, gssv_token).await?,
+            Platform::Home => GssvApi::login_xhome_with_client(http_client, gssv_token).await?,
+        };
+
+        Ok(Self::with_api(platform, api, xcloud_transfer_token))
+    }
+
+    fn with_api(platform: Platform, api: GssvApi, xcloud_transfer_token: &str) -> Self {
+        Self {
+            api,
             transfer_token: xcloud_transfer_token.into(),
             platform,
-        })
+            sdp_polling: SdpPolling::default(),
+            sctp_parameters: std::sync::Mutex::new(None),
+            bandwidth_limits: std::sync::Mutex::new(vec![]),
+            negotiated_codecs: std::sync::Mutex::new(vec![]),
+            #[cfg(feature = "webrtc-rs")]
+            peer_state: Arc::new(watch::channel(RTCPeerConnectionState::Unspecified).0),
+            #[cfg(feature = "webrtc-rs")]
+            channel_state: Arc::new(watch::channel(ChannelReadiness::default()).0),
+            #[cfg(feature = "webrtc-rs")]
+            video_frame_handler: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "webrtc-rs")]
+            audio_frame_handler: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "webrtc-rs")]
+            track_handler: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "webrtc-rs")]
+            track_tasks: Arc::new(Mutex::new(vec![])),
+            #[cfg(feature = "webrtc-rs")]
+            channel_proxy: ChannelProxy::spawn(),
+        }
+    }
+
+    /// Set how long `exchange_sdp` waits for a pending SDP answer
+    pub fn set_sdp_polling(&mut self, polling: SdpPolling) {
+        self.sdp_polling = polling;
+    }
+
+    /// Headers added to every API request, see `GssvApi::default_headers`
+    pub fn default_headers_mut(&mut self) -> &mut reqwest::header::HeaderMap {
+        self.api.default_headers_mut()
+    }
+
+    /// SCTP parameters of the data channels, negotiated by `exchange_sdp`
+    pub fn sctp_parameters(&self) -> Option<sdp::SctpParameters> {
+        *self.sctp_parameters.lock().unwrap()
+    }
+
+    /// Bandwidth limits (b= lines) the server advertised in the SDP
+    /// answer processed by `exchange_sdp`
+    pub fn bandwidth_limits(&self) -> Vec<sdp::BandwidthLimit> {
+        self.bandwidth_limits.lock().unwrap().clone()
+    }
+
+    /// Codecs of the SDP answer processed by `exchange_sdp`, with the
+    /// payload types the server assigned, most preferred first
+    pub fn negotiated_codecs(&self) -> Vec<sdp::NegotiatedCodec> {
+        self.negotiated_codecs.lock().unwrap().clone()
+    }
+
+    /// Negotiated payload type of the preferred codec with `mime_type`
+    /// (e.g. video/H264, case insensitive)
+    pub fn negotiated_payload_type(&self, mime_type: &str) -> Option<u8> {
+        self.negotiated_codecs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|codec| codec.mime_type.eq_ignore_ascii_case(mime_type))
+            .map(|codec| codec.payload_type)
+    }
+
+    /// Fail if a message of `len` bytes exceeds the negotiated
+    /// `max-message-size`, instead of it being silently dropped.
+    ///
+    /// Passes if no SDP answer was processed yet.
+    pub fn check_message_size(&self, len: usize) -> Result<(), GsError> {
+        match self.sctp_parameters() {
+            Some(params) if !params.allows_message(len) => Err(GsError::MessageTooLarge {
+                size: len,
+                max: params.max_message_size,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Send `text` on `channel`, see `check_message_size`
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn send_text(
+        &self,
+        channel: &RTCDataChannel,
+        text: String,
+    ) -> Result<usize, GsError> {
+        self.check_message_size(text.len())?;
+        Ok(channel.send_text(text).await?)
+    }
+
+    /// Send binary `data` on `channel`, see `check_message_size`
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn send_bytes(
+        &self,
+        channel: &RTCDataChannel,
+        data: Vec<u8>,
+    ) -> Result<usize, GsError> {
+        self.check_message_size(data.len())?;
+        Ok(channel.send(&data.into()).await?)
+    }
+
+    /// Get a cloneable sender queueing messages for the data channel
+    /// of `channel_type`, `None` for media channels.
+    ///
+    /// Messages are routed to the channel attached via
+    /// `observe_data_channel` and dropped while none is attached.
+    #[cfg(feature = "webrtc-rs")]
+    pub fn channel_sender(&self, channel_type: ChannelType) -> Option<ChannelSender> {
+        self.channel_proxy.sender(channel_type)
+    }
+
+    /// Forward all state transitions of `peer_connection` to
+    /// receivers obtained via `peer_state_stream`.
+    ///
+    /// NOTE: This registers the peer connection's state change handler,
+    /// replacing any previously registered one.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn observe_peer_connection(&self, peer_connection: &RTCPeerConnection) {
+        let peer_state = Arc::clone(&self.peer_state);
+        peer_connection
+            .on_peer_connection_state_change(Box::new(move |s: RTCPeerConnectionState| {
+                peer_state.send_replace(s);
+                Box::pin(async {})
+            }))
+            .await;
+    }
+
+    /// Get a receiver that observes the peer connection state.
+    /// Holds `RTCPeerConnectionState::Unspecified` until a peer connection
+    /// is attached via `observe_peer_connection`.
+    #[cfg(feature = "webrtc-rs")]
+    pub fn peer_state_stream(&self) -> watch::Receiver<RTCPeerConnectionState> {
+        self.peer_state.subscribe()
+    }
+
+    /// Track open / close of the data channel `channel` of `channel_type`
+    /// and route messages of `channel_sender` to it.
+    ///
+    /// For the message channel, the handshake is sent once it is open
+    /// and incoming messages are checked for the `HandshakeAck`, see
+    /// `wait_until_ready`.
+    ///
+    /// NOTE: This registers the data channel's open and close handlers,
+    /// and for the message channel the message handler, replacing any
+    /// previously registered ones.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn observe_data_channel(
+        &self,
+        channel_type: ChannelType,
+        channel: &Arc<RTCDataChannel>,
+    ) {
+        self.channel_proxy.attach(channel_type, Arc::clone(channel));
+
+        let channel_state = Arc::clone(&self.channel_state);
+        let dc = Arc::downgrade(channel);
+        channel
+            .on_open(Box::new(move || {
+                channel_state.send_modify(|state| state.set_open(channel_type, true));
+                Box::pin(async move {
+                    if channel_type != ChannelType::Message {
+                        return;
+                    }
+                    if let Some(dc) = dc.upgrade() {
+                        let handshake = MessageChannel::handshake().to_string();
+                        if let Err(err) = dc.send_text(handshake).await {
+                            tracing::warn!(
+                                target: "gssv::channel",
+                                %err,
+                                "Failed to send handshake"
+                            );
+                        }
+                    }
+                })
+            }))
+            .await;
+
+        let channel_state = Arc::clone(&self.channel_state);
+        let channel_proxy = Arc::clone(&self.channel_proxy);
+        channel
+            .on_close(Box::new(move || {
+                channel_state.send_modify(|state| state.set_open(channel_type, false));
+                channel_proxy.detach(channel_type);
+                Box::pin(async {})
+            }))
+            .await;
+
+        if channel_type != ChannelType::Message {
+            return;
+        }
+
+        let channel_state = Arc::clone(&self.channel_state);
+        channel
+            .on_message(Box::new(move |msg: DataChannelMessage| {
+                let msg = match String::from_utf8(msg.data.to_vec()) {
+                    Ok(str) => DataChannelMsg::String(str),
+                    Err(_) => DataChannelMsg::Bytes(msg.data.to_vec()),
+                };
+                tracing::debug!(
+                    target: "gssv::channel",
+                    channel = "message",
+                    ?msg,
+                    "Message received"
+                );
+                if MessageChannel::is_handshake_ack(&msg) {
+                    channel_state.send_modify(|state| state.handshake_acked = true);
+                }
+                Box::pin(async {})
+            }))
+            .await;
+    }
+
+    /// Wait until input can be sent: the message channel handshake is
+    /// acked and the control / input channels are open.
+    ///
+    /// Only channels attached via `observe_data_channel` are considered.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn wait_until_ready(&self) {
+        let mut channel_state = self.channel_state.subscribe();
+        while !channel_state.borrow_and_update().is_ready() {
+            // Sender is owned by self, can't be dropped while waiting
+            if channel_state.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Set the handler receiving the frames of remote video tracks.
+    ///
+    /// Frames are only delivered for tracks observed via `observe_tracks`.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn on_video_frame(&self, f: OnFrameHdlrFn) {
+        *self.video_frame_handler.lock().await = Some(f);
+    }
+
+    /// Set the handler receiving the frames of remote audio tracks.
+    ///
+    /// Frames are only delivered for tracks observed via `observe_tracks`.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn on_audio_frame(&self, f: OnFrameHdlrFn) {
+        *self.audio_frame_handler.lock().await = Some(f);
+    }
+
+    /// Set the handler notified about arriving remote audio / video tracks.
+    ///
+    /// Tracks are only reported when observed via `observe_tracks`.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn on_track(&self, f: OnTrackHdlrFn) {
+        *self.track_handler.lock().await = Some(f);
+    }
+
+    /// Report all remote tracks of `peer_connection` to the handler set
+    /// via `on_track`, and hand their frames to the handlers set via
+    /// `on_video_frame` / `on_audio_frame`.
+    ///
+    /// A track is only read if a frame handler for its kind is set,
+    /// otherwise reading it is left to the track handler. For video tracks
+    /// read this way, a picture loss indication is sent periodically, so
+    /// the server keeps pushing keyframes (see `send_pli`).
+    ///
+    /// Reading stops when a track ends or `shutdown` changes, use
+    /// `wait_for_tracks` to wait for that.
+    ///
+    /// NOTE: This registers the peer connection's track handler,
+    /// replacing any previously registered one.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn observe_tracks(
+        &self,
+        peer_connection: &Arc<RTCPeerConnection>,
+        shutdown: watch::Receiver<bool>,
+    ) {
+        let pc = Arc::downgrade(peer_connection);
+        let video_frame_handler = Arc::clone(&self.video_frame_handler);
+        let audio_frame_handler = Arc::clone(&self.audio_frame_handler);
+        let track_handler = Arc::clone(&self.track_handler);
+        let track_tasks = Arc::clone(&self.track_tasks);
+
+        peer_connection
+            .on_track(Box::new(
+                move |track: Option<Arc<TrackRemote>>, _receiver: Option<Arc<RTCRtpReceiver>>| {
+                    let track = match track {
+                        Some(track) => track,
+                        None => return Box::pin(async {}),
+                    };
+
+                    let kind = match TrackKind::from_codec_type(track.kind()) {
+                        Some(kind) => kind,
+                        None => return Box::pin(async {}),
+                    };
+
+                    let frame_handler = match kind {
+                        TrackKind::Video => Arc::clone(&video_frame_handler),
+                        TrackKind::Audio => Arc::clone(&audio_frame_handler),
+                    };
+
+                    let pc = pc.clone();
+                    let shutdown = shutdown.clone();
+                    let track_handler = Arc::clone(&track_handler);
+                    let track_tasks = Arc::clone(&track_tasks);
+                    Box::pin(async move {
+                        if let Some(f) = track_handler.lock().await.as_mut() {
+                            f(
+                                kind,
+                                RemoteTrack {
+                                    codec: track.codec().await.capability,
+                                    track: Arc::clone(&track),
+                                },
+                            )
+                            .await;
+                        }
+
+                        if frame_handler.lock().await.is_none() {
+                            return;
+                        }
+
+                        let mut tasks = track_tasks.lock().await;
+                        if kind == TrackKind::Video {
+                            tasks.push(tokio::spawn(Self::send_pli(
+                                pc,
+                                track.ssrc(),
+                                shutdown.clone(),
+                            )));
+                        }
+                        tasks.push(tokio::spawn(Self::read_track(
+                            track,
+                            frame_handler,
+                            shutdown,
+                        )));
+                    })
+                },
+            ))
+            .await;
+    }
+
+    /// Wait until all tasks spawned by `observe_tracks` have finished.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn wait_for_tracks(&self) {
+        let tasks: Vec<JoinHandle<()>> = self.track_tasks.lock().await.drain(..).collect();
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+
+    #[cfg(feature = "webrtc-rs")]
+    async fn read_track(
+        track: Arc<TrackRemote>,
+        handler: Arc<Mutex<Option<OnFrameHdlrFn>>>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mime_type = track.codec().await.capability.mime_type;
+        tracing::info!(target: "gssv::media", %mime_type, "Reading track");
+
+        loop {
+            tokio::select! {
+                result = track.read_rtp() => {
+                    let packet = match result {
+                        Ok((packet, _)) => packet,
+                        Err(_) => break,
+                    };
+
+                    if let Some(f) = handler.lock().await.as_mut() {
+                        f(MediaFrame {
+                            mime_type: mime_type.clone(),
+                            packet,
+                        })
+                        .await;
+                    }
+                }
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+
+    /// Periodically send a picture loss indication for the video track
+    /// with `media_ssrc`, until the peer connection is gone or `shutdown`
+    /// changes.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn send_pli(
+        peer_connection: std::sync::Weak<RTCPeerConnection>,
+        media_ssrc: u32,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(Self::PLI_INTERVAL) => {
+                    let pc = match peer_connection.upgrade() {
+                        Some(pc) => pc,
+                        None => break,
+                    };
+
+                    let pli = PictureLossIndication {
+                        sender_ssrc: 0,
+                        media_ssrc,
+                    };
+                    if pc.write_rtcp(&[Box::new(pli)]).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+
+    /// Whether the offering allows using another region than the default
+    pub fn allows_region_selection(&self) -> bool {
+        self.api.allows_region_selection()
+    }
+
+    /// Probe all offering regions and use the one with the lowest
+    /// round trip time for new sessions.
+    ///
+    /// Fails if the offering does not allow region selection.
+    pub async fn select_best_region(&mut self) -> Result<Option<NetworkTestResult>, GsError> {
+        self.api
+            .select_best_region()
+            .await
+            .map_err(GsError::ApiError)
     }
 
     pub async fn lookup_games(&self) -> Result<Vec<TitleResult>, GsError> {
@@ -69,12 +569,7 @@ impl GamestreamingClient {
             ));
         }
 
-        Ok(self
-            .api
-            .get_titles()
-            .await
-            .map_err(GsError::ApiError)?
-            .results)
+        self.api.get_all_titles().await.map_err(GsError::ApiError)
     }
 
     pub async fn lookup_consoles(&self) -> Result<ConsolesResponse, GsError> {
@@ -118,25 +613,38 @@ impl GamestreamingClient {
             let state_response = self.api.get_session_state(&session).await?;
             match state_response.state.as_ref() {
                 "WaitingForResources" | "Provisioning" => {
-                    println!("Waiting for session to get ready");
+                    tracing::debug!(
+                        target: "gssv::session",
+                        state = %state_response.state,
+                        "Waiting for session to get ready"
+                    );
                 }
                 "ReadyToConnect" => {
-                    println!("Stream is ready to connect");
+                    tracing::info!(target: "gssv::session", "Stream is ready to connect");
                     if let Err(connect_err) = self
                         .api
                         .session_connect(&session, &self.transfer_token)
                         .await
                     {
-                        println!("Failed to connect to session");
+                        tracing::error!(
+                            target: "gssv::session",
+                            err = %connect_err,
+                            "Failed to connect to session"
+                        );
                         return Err(connect_err.into());
                     }
                 }
                 "Provisioned" => {
-                    println!("Game session is ready!");
+                    tracing::info!(target: "gssv::session", "Game session is ready");
                     return Ok(session);
                 }
                 "Failed" => {
-                    println!("Failed to provision session");
+                    tracing::error!(
+                        target: "gssv::session",
+                        session_id = ?session.session_id(),
+                        region = ?session.region(),
+                        "Failed to provision session"
+                    );
                     return Err(GsError::Provisioning(format!(
                         "Received failed state - error: {:?}",
                         state_response.error_details
@@ -175,32 +683,99 @@ impl GamestreamingClient {
         self.start_stream(Some(server_id), None).await
     }
 
+    /// Terminate a running session.
+    ///
+    /// Closes the data channels attached to the channel proxy first,
+    /// then stops the session.
+    ///
+    /// Should be called before the client exits (e.g. on Ctrl-C),
+    /// otherwise the session stays allocated server-side until it
+    /// times out, possibly blocking the start of a new session.
+    pub async fn disconnect(&self, session: &SessionResponse) -> Result<(), GsError> {
+        #[cfg(feature = "webrtc-rs")]
+        self.channel_proxy.close_all().await;
+
+        self.api
+            .stop_session(session)
+            .await
+            .map_err(GsError::ApiError)
+    }
+
+    /// Terminate `sessions` and drop the API login and transfer token,
+    /// see `GssvApi::logout`.
+    ///
+    /// Track tasks are not stopped, signal their shutdown channel
+    /// and `wait_for_tracks` before.
+    pub async fn logout(self, sessions: &[SessionResponse]) -> Result<(), GsError> {
+        self.api.logout(sessions).await.map_err(GsError::ApiError)
+    }
+
     pub async fn exchange_sdp(
         &self,
         session: &SessionResponse,
         sdp: &str,
+    ) -> Result<SdpExchangeResponse, GsError> {
+        self.exchange_sdp_with_config(session, sdp, &SdpConfiguration::default())
+            .await
+    }
+
+    /// Exchange SDP, requesting the channel versions and chat format
+    /// of `configuration`
+    ///
+    /// The answer is polled according to `SdpPolling`, on success the
+    /// returned response always carries the SDP answer.
+    pub async fn exchange_sdp_with_config(
+        &self,
+        session: &SessionResponse,
+        sdp: &str,
+        configuration: &SdpConfiguration,
     ) -> Result<SdpExchangeResponse, GsError> {
         self.api
-            .set_sdp(session, sdp)
+            .set_sdp_with_config(session, sdp, configuration)
             .await
             .map_err(GsError::ApiError)?;
-        let sdp_response = self.api.get_sdp(session).await.map_err(GsError::ApiError)?;
-        let error_str = match &sdp_response.exchange_response.status {
-            Some(status) => match status.as_ref() {
-                "success" => {
-                    return Ok(sdp_response);
+
+        for attempt in 0..self.sdp_polling.attempts {
+            if attempt > 0 {
+                tokio::time::sleep(self.sdp_polling.interval).await;
+            }
+
+            let sdp_response = match self.api.poll_sdp(session).await? {
+                Some(sdp_response) => sdp_response,
+                None => continue,
+            };
+
+            if let Some(answer) = sdp_response.exchange_response.answer() {
+                sdp::validate_answer(sdp, answer).map_err(GsError::SdpMismatch)?;
+                let sctp_parameters = sdp::sctp_parameters(answer);
+                #[cfg(feature = "webrtc-rs")]
+                if let Some(params) = sctp_parameters {
+                    self.channel_proxy.set_sctp_parameters(params);
                 }
-                _ => format!("Answer status != success => {:?}", sdp_response),
-            },
-            _ => {
-                format!("SDP answer contains no status => {:?}", sdp_response)
+                *self.sctp_parameters.lock().unwrap() = sctp_parameters;
+                *self.bandwidth_limits.lock().unwrap() = sdp::bandwidth_limits(answer);
+                *self.negotiated_codecs.lock().unwrap() = sdp::negotiated_codecs(answer);
+                return Ok(sdp_response);
             }
-        };
 
-        Err(GsError::ConnectionExchange(format!(
-            "SDP failed, message={}",
-            error_str
-        )))
+            let exchange_response = &sdp_response.exchange_response;
+            match exchange_response.status.as_deref() {
+                // Answer not populated yet, failures carry `debug_info`
+                None | Some("success") | Some("pending")
+                    if exchange_response.debug_info.is_none() =>
+                {
+                    tracing::debug!(target: "gssv::sdp", "Waiting for SDP answer");
+                }
+                _ => {
+                    return Err(GsError::ConnectionExchange(format!(
+                        "SDP failed, message=Answer status != success => {:?}",
+                        sdp_response
+                    )));
+                }
+            }
+        }
+
+        Err(GsError::SdpTimeout(self.sdp_polling.attempts))
     }
 
     pub async fn exchange_ice(
@@ -208,13 +783,245 @@ impl GamestreamingClient {
         session: &SessionResponse,
         ice_candidate_init: Vec<IceCandidate>,
     ) -> Result<IceExchangeResponse, GsError> {
+        tracing::debug!(
+            target: "gssv::ice",
+            candidates = ice_candidate_init.len(),
+            "Sending local candidates"
+        );
         self.api
             .set_ice(session, ice_candidate_init)
             .await
             .map_err(GsError::ApiError)?;
         self.api.get_ice(session).await.map_err(GsError::ApiError)
     }
+
+    /// Connect `peer_connection` to `session`
+    ///
+    /// Creates an offer, waits for candidate gathering to complete,
+    /// exchanges SDP and ICE candidates with the API and applies the
+    /// answer. Data channels and transceivers have to be added before.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn negotiate(
+        &self,
+        session: &SessionResponse,
+        peer_connection: &RTCPeerConnection,
+    ) -> Result<(), GsError> {
+        tracing::info!(target: "gssv::ice", "Negotiating connection");
+        let offer = peer_connection.create_offer(None).await?;
+        self.negotiate_offer(session, peer_connection, offer).await
+    }
+
+    /// Recover an established session via ICE restart, e.g. after the
+    /// peer connection went `Disconnected` due to a network change.
+    ///
+    /// Like `negotiate`, with an offer that has `ice_restart` set.
+    /// Data channels and tracks stay in place.
+    #[cfg(feature = "webrtc-rs")]
+    pub async fn restart_ice(
+        &self,
+        session: &SessionResponse,
+        peer_connection: &RTCPeerConnection,
+    ) -> Result<(), GsError> {
+        tracing::info!(target: "gssv::ice", "Restarting ICE");
+        let offer = peer_connection
+            .create_offer(Some(RTCOfferOptions {
+                ice_restart: true,
+                ..Default::default()
+            }))
+            .await?;
+        self.negotiate_offer(session, peer_connection, offer).await
+    }
+
+    #[cfg(feature = "webrtc-rs")]
+    async fn negotiate_offer(
+        &self,
+        session: &SessionResponse,
+        peer_connection: &RTCPeerConnection,
+        offer: RTCSessionDescription,
+    ) -> Result<(), GsError> {
+        let mut gathering_complete = peer_connection.gathering_complete_promise().await;
+        peer_connection.set_local_description(offer).await?;
+        let _ = gathering_complete.recv().await;
+
+        let local_sdp = peer_connection
+            .local_description()
+            .await
+            .ok_or_else(|| GsError::ConnectionExchange("Local description missing".into()))?
+            .sdp;
+
+        let sdp_response = self.exchange_sdp(session, &local_sdp).await?;
+        let answer = sdp_response
+            .exchange_response
+            .sdp
+            .ok_or_else(|| GsError::ConnectionExchange("SDP answer missing".into()))?;
+        peer_connection
+            .set_remote_description(RTCSessionDescription::answer(answer)?)
+            .await?;
+
+        let ice_response = self
+            .exchange_ice(session, sdp::ice_candidates(&local_sdp))
+            .await?;
+        for candidate in ice_response.exchange_response {
+            let candidate = candidate.normalized();
+            if candidate.is_end_of_candidates() {
+                break;
+            }
+            tracing::debug!(
+                target: "gssv::ice",
+                candidate = %candidate.candidate,
+                "Adding remote candidate"
+            );
+            peer_connection
+                .add_ice_candidate(RTCIceCandidateInit {
+                    candidate: candidate.candidate,
+                    sdp_mid: candidate.sdp_mid,
+                    sdp_mline_index: candidate.sdp_mline_index,
+                    username_fragment: candidate.username_fragment,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::api::tests::{sdp_offer_message, sdp_response_message, serve};
+
+    fn session() -> SessionResponse {
+        serde_json::from_str(
+            r#"{"sessionPath": "v5/sessions/home/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3"}"#,
+        )
+        .unwrap()
+    }
+
+    fn offer() -> String {
+        let offer: serde_json::Value = serde_json::from_str(sdp_offer_message()).unwrap();
+        offer["sdp"].as_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn exchange_sdp_negotiated_codecs() {
+        let (url, handle) = serve(vec![
+            ("202 Accepted", ""),
+            ("200 OK", sdp_response_message()),
+        ]);
+        let api = GssvApi::new(reqwest::Client::new(), url, "token", "home").unwrap();
+        let client = GamestreamingClient::with_api(Platform::Home, api, "transfer");
+
+        client
+            .exchange_sdp(&session(), &offer())
+            .await
+            .expect("Failed to exchange SDP");
+
+        assert_eq!(client.negotiated_payload_type("video/H264"), Some(127));
+        assert_eq!(client.negotiated_payload_type("audio/opus"), Some(111));
+        assert_eq!(client.negotiated_payload_type("video/AV1"), None);
+
+        let requests = handle.join().unwrap();
+        assert_eq!(
+            requests[0].request_line,
+            "POST /v5/sessions/home/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3/sdp HTTP/1.1"
+        );
+        assert_eq!(
+            requests[1].request_line,
+            "GET /v5/sessions/home/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3/sdp HTTP/1.1"
+        );
+    }
+
+    const SDP_PENDING: &str = r#"{"exchangeResponse":"{\"chat\":1,\"chatConfiguration\":{\"format\":{\"codec\":\"opus\",\"container\":\"webm\"}},\"control\":3,\"input\":7,\"message\":1,\"status\":\"pending\"}","errorDetails":null}"#;
+
+    fn polling_client(url: reqwest::Url, attempts: u32) -> GamestreamingClient {
+        let api = GssvApi::new(reqwest::Client::new(), url, "token", "home").unwrap();
+        let mut client = GamestreamingClient::with_api(Platform::Home, api, "transfer");
+        client.set_sdp_polling(SdpPolling {
+            attempts,
+            interval: std::time::Duration::from_millis(1),
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn exchange_sdp_polls_pending_answer() {
+        let (url, handle) = serve(vec![
+            ("202 Accepted", ""),
+            ("204 No Content", ""),
+            ("200 OK", SDP_PENDING),
+            ("200 OK", sdp_response_message()),
+        ]);
+        let client = polling_client(url, 3);
+
+        let response = client
+            .exchange_sdp(&session(), &offer())
+            .await
+            .expect("Failed to exchange SDP");
+        assert!(response.exchange_response.answer().is_some());
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[1..]
+            .iter()
+            .all(|request| request.request_line.starts_with("GET ")));
+    }
+
+    #[tokio::test]
+    async fn exchange_sdp_timeout() {
+        let (url, handle) = serve(vec![
+            ("202 Accepted", ""),
+            ("204 No Content", ""),
+            ("200 OK", SDP_PENDING),
+        ]);
+        let client = polling_client(url, 2);
+
+        let result = client.exchange_sdp(&session(), &offer()).await;
+        assert!(matches!(result, Err(GsError::SdpTimeout(2))));
+        assert!(client.negotiated_codecs().is_empty());
+
+        // No further polls after the last attempt
+        assert_eq!(handle.join().unwrap().len(), 3);
+    }
+
+    #[cfg(feature = "webrtc-rs")]
+    #[tokio::test]
+    async fn disconnect_closes_channels() {
+        use crate::channels::connected_channel;
+        use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+
+        let (_offerer, _answerer, channel, mut msg_rx) = connected_channel("chat").await;
+        let (url, handle) = serve(vec![("204 No Content", "")]);
+        let api = GssvApi::new(reqwest::Client::new(), url, "token", "home").unwrap();
+        let client = GamestreamingClient::with_api(Platform::Home, api, "transfer");
+        client
+            .channel_proxy
+            .attach(ChannelType::Chat, channel.clone());
+
+        client
+            .disconnect(&session())
+            .await
+            .expect("Failed to disconnect");
+
+        assert!(matches!(
+            channel.ready_state(),
+            RTCDataChannelState::Closing | RTCDataChannelState::Closed
+        ));
+
+        // Channel is detached as well, messages are dropped
+        client
+            .channel_proxy
+            .sender(ChannelType::Chat)
+            .unwrap()
+            .send_text("after disconnect")
+            .unwrap();
+        let received =
+            tokio::time::timeout(std::time::Duration::from_millis(500), msg_rx.recv()).await;
+        assert!(!matches!(received, Ok(Some(_))));
+
+        let requests = handle.join().unwrap();
+        assert_eq!(
+            requests[0].request_line,
+            "DELETE /v5/sessions/home/BD0E6B4A-7A0B-4AE8-95C2-93B0A94AA1C3 HTTP/1.1"
+        );
+    }
+}
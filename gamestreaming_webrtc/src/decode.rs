@@ -0,0 +1,159 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use openh264::decoder::Decoder;
+use thiserror::Error;
+use webrtc::api::media_engine::MIME_TYPE_H264;
+use webrtc::rtp::codecs::h264::H264Packet;
+use webrtc::rtp::packet::Packet;
+use webrtc::rtp::packetizer::Depacketizer;
+
+use crate::media::{MediaFrame, OnFrameHdlrFn};
+
+#[derive(Error, Debug)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Depacketize(#[from] webrtc::rtp::Error),
+    #[error(transparent)]
+    Decoder(#[from] openh264::Error),
+}
+
+/// Decoded video frame
+#[derive(Debug, Clone)]
+pub struct DecodedFrame {
+    pub width: usize,
+    pub height: usize,
+    /// RTP timestamp of the access unit
+    pub timestamp: u32,
+    /// Pixel data, RGB8 row by row
+    pub rgb: Vec<u8>,
+}
+
+/// Handler invoked for every decoded video frame
+pub type OnDecodedFrameHdlrFn = Box<
+    dyn (FnMut(DecodedFrame) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync,
+>;
+
+/// Software H264 decoder, fed with RTP packets
+///
+/// Packets are depacketized (RFC 6184) into an Annex B access unit,
+/// which is decoded once the packet with the marker bit arrives.
+///
+/// NOTE: Packets have to be pushed in order, lost packets are not
+/// concealed, the decoder recovers on the next keyframe.
+pub struct H264Decoder {
+    depacketizer: H264Packet,
+    decoder: Decoder,
+    access_unit: Vec<u8>,
+}
+
+impl H264Decoder {
+    pub fn new() -> Result<Self, DecodeError> {
+        Ok(Self {
+            depacketizer: H264Packet::default(),
+            decoder: Decoder::new()?,
+            access_unit: vec![],
+        })
+    }
+
+    /// Push a RTP packet, returns a frame if an access unit was completed
+    /// and the decoder emitted a picture for it.
+    pub fn push(&mut self, packet: &Packet) -> Result<Option<DecodedFrame>, DecodeError> {
+        let nalus = self.depacketizer.depacketize(&packet.payload)?;
+        self.access_unit.extend_from_slice(&nalus);
+
+        if !packet.header.marker {
+            return Ok(None);
+        }
+
+        let access_unit = std::mem::take(&mut self.access_unit);
+        let yuv = match self.decoder.decode(&access_unit)? {
+            Some(yuv) => yuv,
+            None => return Ok(None),
+        };
+
+        let (width, height) = yuv.dimension_rgb();
+        let mut rgb = vec![0; width * height * 3];
+        yuv.write_rgb8(&mut rgb);
+
+        Ok(Some(DecodedFrame {
+            width,
+            height,
+            timestamp: packet.header.timestamp,
+            rgb,
+        }))
+    }
+}
+
+/// Frame handler decoding H264 frames and handing the pictures to `handler`,
+/// frames of other codecs are ignored.
+///
+/// Decoding happens on a dedicated thread, which stops once the returned
+/// handler is dropped. Has to be called from within a tokio runtime.
+pub fn decoder_sink(mut handler: OnDecodedFrameHdlrFn) -> OnFrameHdlrFn {
+    let (packet_tx, packet_rx) = mpsc::channel::<Packet>();
+    let (frame_tx, mut frame_rx) = tokio::sync::mpsc::unbounded_channel::<DecodedFrame>();
+
+    thread::spawn(move || {
+        let mut decoder = match H264Decoder::new() {
+            Ok(decoder) => decoder,
+            Err(err) => {
+                tracing::error!(target: "gssv::media", %err, "Failed to create H264 decoder");
+                return;
+            }
+        };
+
+        for packet in packet_rx {
+            match decoder.push(&packet) {
+                Ok(Some(frame)) => {
+                    if frame_tx.send(frame).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!(target: "gssv::media", %err, "Failed to decode H264 frame")
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(frame) = frame_rx.recv().await {
+            handler(frame).await;
+        }
+    });
+
+    let packet_tx = Mutex::new(packet_tx);
+    Box::new(move |frame: MediaFrame| {
+        if frame.mime_type.eq_ignore_ascii_case(MIME_TYPE_H264) {
+            // Decoder thread is gone if sending fails, it already reported why
+            let _ = packet_tx.lock().unwrap().send(frame.packet);
+        }
+        Box::pin(async {})
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_collects_access_unit() {
+        let mut decoder = H264Decoder::new().expect("Failed to create decoder");
+
+        let packet = Packet {
+            payload: vec![0x67, 0x42, 0x00, 0x1f].into(),
+            ..Default::default()
+        };
+
+        let frame = decoder.push(&packet).expect("Failed to push packet");
+        assert!(frame.is_none());
+        assert_eq!(
+            decoder.access_unit,
+            vec![0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1f]
+        );
+    }
+}
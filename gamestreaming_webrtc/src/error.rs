@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::api::GssvApiError;
+use crate::channels::ChannelType;
 
 #[derive(Error, Debug)]
 pub enum PacketError {
@@ -18,6 +19,19 @@ pub enum GsError {
     Provisioning(String),
     #[error("Connection exchange failed")]
     ConnectionExchange(String),
+    #[error("No SDP answer after {0} attempts")]
+    SdpTimeout(u32),
+    #[error("SDP answer does not match offer: {0}")]
+    SdpMismatch(String),
+    #[error("Message of {size} bytes exceeds negotiated max-message-size of {max} bytes")]
+    MessageTooLarge { size: usize, max: u64 },
+    #[error("Channel {0:?} is closed")]
+    ChannelClosed(ChannelType),
+    #[error("Failed to serialize packet: {0}")]
+    Serialization(#[from] deku::DekuError),
+    #[cfg(feature = "webrtc-rs")]
+    #[error(transparent)]
+    WebRtc(#[from] webrtc::Error),
     #[error("Unknown error")]
     Unknown,
 }
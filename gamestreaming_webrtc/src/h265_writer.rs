@@ -0,0 +1,173 @@
+use std::io::Write;
+use webrtc::media::io::Writer;
+use webrtc::rtp::packet::Packet;
+
+/// NAL unit start code (Annex B)
+const ANNEXB_START_CODE: &[u8] = &[0x00, 0x00, 0x00, 0x01];
+const NAL_HEADER_SIZE: usize = 2;
+const FU_HEADER_SIZE: usize = 1;
+const AP_NAL_SIZE_LEN: usize = 2;
+
+/// RFC 7798 - Aggregation packet
+const NAL_TYPE_AP: u8 = 48;
+/// RFC 7798 - Fragmentation unit
+const NAL_TYPE_FU: u8 = 49;
+/// RFC 7798 - PACI packet
+const NAL_TYPE_PACI: u8 = 50;
+
+/// Writes H.265/HEVC RTP payloads (RFC 7798) as Annex B bytestream
+///
+/// Assumes `sprop-max-don-diff` = 0, e.g. no DONL/DOND fields are present.
+pub struct H265Writer<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> H265Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    fn write_nal(&mut self, nal: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(ANNEXB_START_CODE)?;
+        self.writer.write_all(nal)
+    }
+
+    fn write_aggregation_packet(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let mut offset = NAL_HEADER_SIZE;
+        while offset + AP_NAL_SIZE_LEN <= payload.len() {
+            let nal_size = u16::from_be_bytes([payload[offset], payload[offset + 1]]) as usize;
+            offset += AP_NAL_SIZE_LEN;
+
+            match payload.get(offset..offset + nal_size) {
+                Some(nal) => self.write_nal(nal)?,
+                // Truncated aggregation packet
+                None => break,
+            }
+            offset += nal_size;
+        }
+
+        Ok(())
+    }
+
+    fn write_fragmentation_unit(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        if payload.len() <= NAL_HEADER_SIZE + FU_HEADER_SIZE {
+            return Ok(());
+        }
+
+        let fu_header = payload[NAL_HEADER_SIZE];
+        let is_start = fu_header & 0x80 != 0;
+        let fragment = &payload[NAL_HEADER_SIZE + FU_HEADER_SIZE..];
+
+        if is_start {
+            // Reconstruct the original NAL unit header
+            let nal_type = fu_header & 0x3F;
+            let nal_header = [(payload[0] & 0x81) | (nal_type << 1), payload[1]];
+
+            self.writer.write_all(ANNEXB_START_CODE)?;
+            self.writer.write_all(&nal_header)?;
+        }
+
+        self.writer.write_all(fragment)
+    }
+}
+
+impl<W: Write> Writer for H265Writer<W> {
+    fn write_rtp(&mut self, packet: &Packet) -> Result<(), webrtc::media::Error> {
+        let payload = &packet.payload[..];
+        if payload.len() <= NAL_HEADER_SIZE {
+            return Ok(());
+        }
+
+        let nal_type = (payload[0] >> 1) & 0x3F;
+        match nal_type {
+            NAL_TYPE_AP => self.write_aggregation_packet(payload)?,
+            NAL_TYPE_FU => self.write_fragmentation_unit(payload)?,
+            NAL_TYPE_PACI => {}
+            _ => self.write_nal(payload)?,
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), webrtc::media::Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(payload: &[u8]) -> Packet {
+        Packet {
+            header: Default::default(),
+            payload: payload.to_vec().into(),
+        }
+    }
+
+    fn write_packets(payloads: &[&[u8]]) -> Vec<u8> {
+        let mut writer = H265Writer::new(vec![]);
+        for payload in payloads {
+            writer
+                .write_rtp(&packet(payload))
+                .expect("Failed to write packet");
+        }
+        writer.close().expect("Failed to close writer");
+
+        writer.writer
+    }
+
+    #[test]
+    fn write_single_nal() {
+        // VPS
+        let data = write_packets(&[&[0x40, 0x01, 0xAA, 0xBB]]);
+
+        assert_eq!(data, vec![0x00, 0x00, 0x00, 0x01, 0x40, 0x01, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn write_aggregation_packet() {
+        let data = write_packets(&[&[
+            0x60, 0x01, // AP
+            0x00, 0x03, 0x40, 0x01, 0xAA, // VPS
+            0x00, 0x03, 0x42, 0x01, 0xBB, // SPS
+        ]]);
+
+        assert_eq!(
+            data,
+            vec![
+                0x00, 0x00, 0x00, 0x01, 0x40, 0x01, 0xAA, 0x00, 0x00, 0x00, 0x01, 0x42, 0x01, 0xBB
+            ]
+        );
+    }
+
+    #[test]
+    fn write_fragmentation_unit() {
+        let data = write_packets(&[
+            // FU start, IDR_W_RADL (19)
+            &[0x62, 0x01, 0x93, 0x01, 0x02],
+            // FU middle
+            &[0x62, 0x01, 0x13, 0x03, 0x04],
+            // FU end
+            &[0x62, 0x01, 0x53, 0x05],
+        ]);
+
+        assert_eq!(
+            data,
+            vec![0x00, 0x00, 0x00, 0x01, 0x26, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05]
+        );
+    }
+
+    #[test]
+    fn skip_invalid_packets() {
+        let data = write_packets(&[
+            &[],
+            &[0x40],
+            &[0x62, 0x01, 0x93],
+            &[0x60, 0x01, 0x00, 0x05, 0x40],
+        ]);
+
+        assert!(data.is_empty());
+    }
+}
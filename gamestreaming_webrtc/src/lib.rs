@@ -1,8 +1,19 @@
 pub mod api;
 mod channels;
 mod client;
+#[cfg(feature = "decode")]
+pub mod decode;
 pub mod error;
+#[cfg(feature = "webrtc-rs")]
+pub mod h265_writer;
+#[cfg(feature = "webrtc-rs")]
+pub mod media;
 mod packets;
+mod sdp;
 mod serde_helpers;
 
-pub use client::{GamestreamingClient, Platform};
+#[cfg(feature = "webrtc-rs")]
+pub use channels::ChannelProxy;
+pub use channels::{ChannelSender, ChannelType, DataChannelParams};
+pub use client::{GamestreamingClient, Platform, SdpPolling};
+pub use sdp::{BandwidthLimit, NegotiatedCodec, SctpParameters};
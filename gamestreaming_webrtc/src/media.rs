@@ -0,0 +1,307 @@
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+use webrtc::media::io::Writer;
+use webrtc::rtp::packet::Packet;
+use webrtc::rtp_transceiver::rtp_codec::{RTCRtpCodecCapability, RTPCodecType};
+use webrtc::track::track_remote::TrackRemote;
+use webrtc::util::{Marshal, Unmarshal};
+
+/// Media writer, shareable across track tasks
+pub type MediaWriter = Arc<Mutex<dyn Writer + Send + Sync>>;
+
+/// Handler invoked for every RTP packet received on a remote track
+pub type OnFrameHdlrFn = Box<
+    dyn (FnMut(MediaFrame) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync,
+>;
+
+/// Handler invoked when a remote audio / video track arrives
+pub type OnTrackHdlrFn = Box<
+    dyn (FnMut(TrackKind, RemoteTrack) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>)
+        + Send
+        + Sync,
+>;
+
+/// Kind of a remote media track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackKind {
+    Audio,
+    Video,
+}
+
+impl TrackKind {
+    /// Returns `None` for unspecified codec types
+    pub fn from_codec_type(codec_type: RTPCodecType) -> Option<Self> {
+        match codec_type {
+            RTPCodecType::Audio => Some(TrackKind::Audio),
+            RTPCodecType::Video => Some(TrackKind::Video),
+            _ => None,
+        }
+    }
+}
+
+/// Remote track, as reported to the track handler
+#[derive(Clone)]
+pub struct RemoteTrack {
+    /// Codec negotiated for the track
+    pub codec: RTCRtpCodecCapability,
+    pub track: Arc<TrackRemote>,
+}
+
+impl RemoteTrack {
+    /// Mime type of the track's codec, e.g. video/H264
+    pub fn mime_type(&self) -> &str {
+        &self.codec.mime_type
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.track.ssrc()
+    }
+}
+
+/// Media frame (RTP packet) received on a remote track
+///
+/// NOTE: Frames are handed out per RTP packet, no depacketization
+/// is done. Use a `webrtc::media::io::Writer` (or `writer_sink`)
+/// to assemble a bytestream.
+#[derive(Debug, Clone)]
+pub struct MediaFrame {
+    /// Mime type of the track's codec, e.g. video/H264
+    pub mime_type: String,
+    pub packet: Packet,
+}
+
+/// Frame handler writing all received frames to `writer`,
+/// e.g. to save a track to disk.
+///
+/// The writer is not closed by the handler, call `close` on it
+/// when the stream has ended.
+pub fn writer_sink(writer: MediaWriter) -> OnFrameHdlrFn {
+    Box::new(move |frame: MediaFrame| {
+        let writer = Arc::clone(&writer);
+        Box::pin(async move {
+            if let Err(err) = writer.lock().await.write_rtp(&frame.packet) {
+                tracing::warn!(
+                    target: "gssv::media",
+                    mime_type = %frame.mime_type,
+                    %err,
+                    "Failed to write frame"
+                );
+            }
+        })
+    })
+}
+
+/// Frame handler forwarding every frame to all of `handlers`,
+/// e.g. to record the raw stream (see `RawRtpWriter`) alongside
+/// decoding / saving the media.
+pub fn tee_sink(mut handlers: Vec<OnFrameHdlrFn>) -> OnFrameHdlrFn {
+    Box::new(move |frame: MediaFrame| {
+        let pending: Vec<_> = handlers.iter_mut().map(|f| f(frame.clone())).collect();
+        Box::pin(async move {
+            for f in pending {
+                f.await;
+            }
+        })
+    })
+}
+
+/// Writer archiving RTP packets as received, to reproduce
+/// depacketization / decode issues offline (see `RawRtpReader`).
+///
+/// Each packet is stored as a record of:
+/// - u64 LE: microseconds since the writer was created
+/// - u32 LE: length of the packet
+/// - the marshalled RTP packet
+pub struct RawRtpWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> RawRtpWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    fn write_record(&mut self, elapsed: Duration, packet: &Packet) -> io::Result<()> {
+        let data = packet
+            .marshal()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        self.writer
+            .write_all(&(elapsed.as_micros() as u64).to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&data)
+    }
+}
+
+impl<W: Write> Writer for RawRtpWriter<W> {
+    fn write_rtp(&mut self, packet: &Packet) -> Result<(), webrtc::media::Error> {
+        self.write_record(self.start.elapsed(), packet)
+            .map_err(|err| webrtc::media::Error::Other(err.to_string()))
+    }
+
+    fn close(&mut self) -> Result<(), webrtc::media::Error> {
+        self.writer
+            .flush()
+            .map_err(|err| webrtc::media::Error::Other(err.to_string()))
+    }
+}
+
+/// Reads back the records written by `RawRtpWriter`, yielding
+/// the arrival time (relative to the first record's writer) and packet
+pub struct RawRtpReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> RawRtpReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<(Duration, Packet)>> {
+        let mut timestamp = [0u8; 8];
+        match self.reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let packet = Packet::unmarshal(&mut &data[..])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        Ok(Some((
+            Duration::from_micros(u64::from_le_bytes(timestamp)),
+            packet,
+        )))
+    }
+}
+
+impl<R: Read> Iterator for RawRtpReader<R> {
+    type Item = io::Result<(Duration, Packet)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWriter {
+        packets: usize,
+    }
+
+    impl Writer for CountingWriter {
+        fn write_rtp(&mut self, _packet: &Packet) -> Result<(), webrtc::media::Error> {
+            self.packets += 1;
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), webrtc::media::Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn writer_sink_writes_frames() {
+        let writer = Arc::new(Mutex::new(CountingWriter::default()));
+        let mut sink = writer_sink(writer.clone());
+
+        for _ in 0..3 {
+            sink(MediaFrame {
+                mime_type: "video/H264".into(),
+                packet: Packet::default(),
+            })
+            .await;
+        }
+
+        assert_eq!(writer.lock().await.packets, 3);
+    }
+
+    #[tokio::test]
+    async fn tee_sink_forwards_frames() {
+        let first = Arc::new(Mutex::new(CountingWriter::default()));
+        let second = Arc::new(Mutex::new(CountingWriter::default()));
+        let mut sink = tee_sink(vec![
+            writer_sink(first.clone()),
+            writer_sink(second.clone()),
+        ]);
+
+        for _ in 0..2 {
+            sink(MediaFrame {
+                mime_type: "audio/opus".into(),
+                packet: Packet::default(),
+            })
+            .await;
+        }
+
+        assert_eq!(first.lock().await.packets, 2);
+        assert_eq!(second.lock().await.packets, 2);
+    }
+
+    #[test]
+    fn raw_rtp_round_trip() {
+        let mut packet = Packet::default();
+        packet.header.version = 2;
+        packet.header.payload_type = 102;
+        packet.header.sequence_number = 1234;
+        packet.header.ssrc = 0xDEADBEEF;
+        packet.payload = vec![0x01, 0x02, 0x03].into();
+
+        let mut writer = RawRtpWriter::new(vec![]);
+        writer
+            .write_record(Duration::from_micros(20), &packet)
+            .unwrap();
+        writer.write_rtp(&packet).unwrap();
+        writer.close().unwrap();
+
+        // Timestamp + length + 12 bytes header + payload
+        assert_eq!(writer.writer.len(), 2 * (8 + 4 + 12 + 3));
+
+        let records: Vec<(Duration, Packet)> = RawRtpReader::new(&writer.writer[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, Duration::from_micros(20));
+        assert_eq!(records[0].1, packet);
+        assert_eq!(records[1].1, packet);
+    }
+
+    #[test]
+    fn raw_rtp_truncated() {
+        let mut data = 0u64.to_le_bytes().to_vec();
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&[0x80, 0x66]);
+
+        let mut reader = RawRtpReader::new(&data[..]);
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn track_kind_from_codec_type() {
+        assert_eq!(
+            TrackKind::from_codec_type(RTPCodecType::Audio),
+            Some(TrackKind::Audio)
+        );
+        assert_eq!(
+            TrackKind::from_codec_type(RTPCodecType::Video),
+            Some(TrackKind::Video)
+        );
+        assert_eq!(TrackKind::from_codec_type(RTPCodecType::Unspecified), None);
+    }
+}
@@ -1,5 +1,50 @@
+use deku::bitvec::{BitSlice, Msb0};
+use deku::ctx::Limit;
 use deku::prelude::*;
 
+/// Read `count` entries of `entry_size` bytes each.
+///
+/// Rejects counts exceeding the remaining input before parsing any entry,
+/// so malformed length fields fail with a clear error.
+fn read_counted<'a, T>(
+    rest: &'a BitSlice<u8, Msb0>,
+    count: u8,
+    entry_size: usize,
+) -> Result<(&'a BitSlice<u8, Msb0>, Vec<T>), DekuError>
+where
+    T: DekuRead<'a>,
+{
+    let needed = count as usize * entry_size;
+    let available = rest.len() / 8;
+    if needed > available {
+        return Err(DekuError::Parse(format!(
+            "Declared count {} needs {} bytes, only {} available",
+            count, needed, available
+        )));
+    }
+
+    Vec::<T>::read(rest, Limit::new_count(count as usize))
+}
+
+/// Read all remaining bytes
+fn read_remaining(rest: &BitSlice<u8, Msb0>) -> Result<(&BitSlice<u8, Msb0>, Vec<u8>), DekuError> {
+    Vec::<u8>::read(rest, Limit::new_count(rest.len() / 8))
+}
+
+/// Read a report of type `T` if `present`, advancing `rest` past it
+fn read_report<'a, T>(present: bool, rest: &mut (&'a [u8], usize)) -> Result<Option<T>, DekuError>
+where
+    T: DekuContainerRead<'a>,
+{
+    if !present {
+        return Ok(None);
+    }
+
+    let (next, report) = T::from_bytes(*rest)?;
+    *rest = next;
+    Ok(Some(report))
+}
+
 #[allow(non_snake_case)]
 #[derive(Copy, Clone, Default, DekuRead, DekuWrite, Debug, Eq, PartialEq)]
 #[deku(endian = "little")]
@@ -30,8 +75,52 @@ pub struct InputReportType {
     Metadata: bool,
 }
 
+/// Kind of report carried by an `InputPacket`, see `InputReportType::from_reports`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReportKind {
+    Metadata,
+    GamepadReport,
+    ClientMetadata,
+    ServerMetadata,
+    Mouse,
+    Keyboard,
+    Vibration,
+}
+
+impl InputReportType {
+    /// Report type with the bits of `kinds` set
+    pub fn from_reports(kinds: &[ReportKind]) -> Self {
+        let mut report_type = Self::default();
+        for kind in kinds {
+            match kind {
+                ReportKind::Metadata => report_type.Metadata = true,
+                ReportKind::GamepadReport => report_type.GamepadReport = true,
+                ReportKind::ClientMetadata => report_type.ClientMetadata = true,
+                ReportKind::ServerMetadata => report_type.ServerMetadata = true,
+                ReportKind::Mouse => report_type.Mouse = true,
+                ReportKind::Keyboard => report_type.Keyboard = true,
+                ReportKind::Vibration => report_type.Vibration = true,
+            }
+        }
+
+        report_type
+    }
+
+    pub fn contains(&self, kind: ReportKind) -> bool {
+        match kind {
+            ReportKind::Metadata => self.Metadata,
+            ReportKind::GamepadReport => self.GamepadReport,
+            ReportKind::ClientMetadata => self.ClientMetadata,
+            ReportKind::ServerMetadata => self.ServerMetadata,
+            ReportKind::Mouse => self.Mouse,
+            ReportKind::Keyboard => self.Keyboard,
+            ReportKind::Vibration => self.Vibration,
+        }
+    }
+}
+
 #[allow(non_snake_case)]
-#[derive(Copy, Clone, DekuRead, DekuWrite, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Default, DekuRead, DekuWrite, Debug, Eq, PartialEq)]
 #[deku(endian = "little")]
 pub struct GamepadButton {
     /// Bitmask: 0x8000
@@ -110,15 +199,43 @@ pub struct InputMetadataEntry {
     pub frame_date_now: u32,
 }
 
+impl InputMetadataEntry {
+    /// Serialized size in bytes
+    pub const SIZE: usize = 28;
+
+    /// Time from the first packet of the frame arriving until it was
+    /// submitted to the decoder
+    ///
+    /// Timestamps are millisecond counters that may wrap around, the
+    /// differences are computed modulo 2^32.
+    pub fn queue_latency_ms(&self) -> u32 {
+        self.frame_submitted_time_ms
+            .wrapping_sub(self.first_frame_packet_arrival_time_ms)
+    }
+
+    /// Time the decoder took for the frame
+    pub fn decode_latency_ms(&self) -> u32 {
+        self.frame_decoded_time_ms
+            .wrapping_sub(self.frame_submitted_time_ms)
+    }
+
+    /// Time from the first packet of the frame arriving until it was
+    /// rendered, covering queueing, decoding and presentation
+    pub fn render_latency_ms(&self) -> u32 {
+        self.frame_rendered_time_ms
+            .wrapping_sub(self.first_frame_packet_arrival_time_ms)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, DekuRead, DekuWrite)]
 pub struct MetadataReport {
     #[deku(update = "self.metadata.len()")]
     pub queue_len: u8,
-    #[deku(count = "queue_len")]
+    #[deku(reader = "read_counted(deku::rest, *queue_len, InputMetadataEntry::SIZE)")]
     pub metadata: Vec<InputMetadataEntry>,
 }
 
-#[derive(Debug, Eq, PartialEq, DekuRead, DekuWrite)]
+#[derive(Debug, Default, Eq, PartialEq, DekuRead, DekuWrite)]
 pub struct GamepadData {
     pub gamepad_index: u8,
     pub button_mask: GamepadButton,
@@ -132,11 +249,16 @@ pub struct GamepadData {
     pub virtual_physicality: u32,
 }
 
+impl GamepadData {
+    /// Serialized size in bytes
+    pub const SIZE: usize = 23;
+}
+
 #[derive(Debug, Eq, PartialEq, DekuRead, DekuWrite)]
 pub struct GamepadReport {
     #[deku(update = "self.gamepad_data.len()")]
     pub queue_len: u8,
-    #[deku(count = "queue_len")]
+    #[deku(reader = "read_counted(deku::rest, *queue_len, GamepadData::SIZE)")]
     pub gamepad_data: Vec<GamepadData>,
 }
 
@@ -145,25 +267,121 @@ pub struct ClientMetadataReport {
     pub metadata: u8,
 }
 
+/// Sent by the server, announces the stream dimensions
+///
+/// NOTE: Provisional, the field layout is a guess that has not been
+/// checked against captured traffic, see `InputPacket::provisional_reports`.
+#[derive(Debug, Default, Eq, PartialEq, DekuRead, DekuWrite)]
+pub struct ServerMetadataReport {
+    pub width: u32,
+    pub height: u32,
+    pub max_touchpoints: u8,
+}
+
+#[allow(non_snake_case)]
+#[derive(Copy, Clone, Default, DekuRead, DekuWrite, Debug, Eq, PartialEq)]
+#[deku(endian = "little")]
+pub struct MouseButton {
+    /// Bitmask: 0xE0
+    #[deku(bits = "3")]
+    Unused: u8,
+    /// Bitmask: 0x10
+    #[deku(bits = "1")]
+    X2: bool,
+    /// Bitmask: 0x08
+    #[deku(bits = "1")]
+    X1: bool,
+    /// Bitmask: 0x04
+    #[deku(bits = "1")]
+    Middle: bool,
+    /// Bitmask: 0x02
+    #[deku(bits = "1")]
+    Right: bool,
+    /// Bitmask: 0x01
+    #[deku(bits = "1")]
+    Left: bool,
+}
+
+/// NOTE: Provisional, the field layout is a guess that has not been
+/// checked against captured traffic, see `InputPacket::provisional_reports`.
+#[derive(Debug, Default, Eq, PartialEq, DekuRead, DekuWrite)]
+pub struct MouseReport {
+    /// Absolute position or delta, see `relative`
+    pub x: i32,
+    pub y: i32,
+    pub wheel_x: i16,
+    pub wheel_y: i16,
+    pub buttons: MouseButton,
+    /// 1 if `x` / `y` are relative movement
+    pub relative: u8,
+}
+
+/// NOTE: Provisional, the field layout is a guess that has not been
+/// checked against captured traffic, see `InputPacket::provisional_reports`.
+#[derive(Debug, Default, Eq, PartialEq, DekuRead, DekuWrite)]
+pub struct KeyboardReport {
+    /// 1 = key down, 0 = key up
+    pub pressed: u8,
+    /// Virtual key code
+    pub key_code: u16,
+}
+
+/// Server metadata, mouse and keyboard reports of an `InputPacket`,
+/// parsed with their provisional layouts
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ProvisionalReports {
+    pub server_metadata: Option<ServerMetadataReport>,
+    pub mouse: Option<MouseReport>,
+    pub keyboard: Option<KeyboardReport>,
+}
+
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 pub struct SequenceInfo {
     sequence_num: u32,
     timestamp: f64,
 }
 
+/// Input packet
+///
+/// The asserts ensure the report type bits match the reports present,
+/// serializing a packet with mismatching bits fails.
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 pub struct InputPacket {
     report_type: InputReportType,
-    #[deku(cond = "!report_type.Vibration")]
+    #[deku(
+        cond = "!report_type.Vibration",
+        assert = "seq_info.is_some() != report_type.Vibration"
+    )]
     // Skip sequence info on vibration packets
     seq_info: Option<SequenceInfo>,
-    #[deku(cond = "report_type.Metadata")]
+    #[deku(
+        cond = "report_type.Metadata",
+        assert = "metadata_report.is_some() == report_type.Metadata"
+    )]
     metadata_report: Option<MetadataReport>,
-    #[deku(cond = "report_type.GamepadReport")]
+    #[deku(
+        cond = "report_type.GamepadReport",
+        assert = "gamepad_report.is_some() == report_type.GamepadReport"
+    )]
     gamepad_report: Option<GamepadReport>,
-    #[deku(cond = "report_type.ClientMetadata")]
+    #[deku(
+        cond = "report_type.ClientMetadata",
+        assert = "client_metadata_report.is_some() == report_type.ClientMetadata"
+    )]
     client_metadata_report: Option<ClientMetadataReport>,
-    #[deku(cond = "report_type.Vibration")]
+    // Server metadata, mouse and keyboard reports are not decoded, their
+    // layout is unverified and their length unknown, so this holds the
+    // rest of the packet. See `provisional_reports`.
+    #[deku(
+        cond = "report_type.ServerMetadata || report_type.Mouse || report_type.Keyboard",
+        reader = "read_remaining(deku::rest)",
+        assert = "provisional_data.is_empty() || report_type.ServerMetadata || report_type.Mouse || report_type.Keyboard"
+    )]
+    provisional_data: Vec<u8>,
+    #[deku(
+        cond = "report_type.Vibration",
+        assert = "vibration_report.is_some() == report_type.Vibration"
+    )]
     vibration_report: Option<VibrationReport>,
 }
 
@@ -175,25 +393,20 @@ impl InputPacket {
         gamepad_report: Option<GamepadReport>,
         client_metadata_report: Option<ClientMetadataReport>,
     ) -> Self {
-        let report_type = {
-            // Create initial report type with no bits set
-            let mut tmp_type: InputReportType = InputReportType::default();
-
-            // Check which data will be contained
-            if metadata_report.is_some() {
-                tmp_type.Metadata = true;
-            }
-            if gamepad_report.is_some() {
-                tmp_type.GamepadReport = true;
-            }
-            if client_metadata_report.is_some() {
-                tmp_type.ClientMetadata = true;
-            }
-            tmp_type
-        };
+        // Check which data will be contained
+        let mut kinds = vec![];
+        if metadata_report.is_some() {
+            kinds.push(ReportKind::Metadata);
+        }
+        if gamepad_report.is_some() {
+            kinds.push(ReportKind::GamepadReport);
+        }
+        if client_metadata_report.is_some() {
+            kinds.push(ReportKind::ClientMetadata);
+        }
 
         Self {
-            report_type,
+            report_type: InputReportType::from_reports(&kinds),
             seq_info: Some(SequenceInfo {
                 sequence_num,
                 timestamp,
@@ -201,7 +414,86 @@ impl InputPacket {
             metadata_report,
             gamepad_report,
             client_metadata_report,
+            provisional_data: vec![],
+            vibration_report: None,
+        }
+    }
+
+    pub fn sequence_num(&self) -> Option<u32> {
+        self.seq_info.as_ref().map(|info| info.sequence_num)
+    }
+
+    /// Timestamp in milliseconds
+    pub fn timestamp(&self) -> Option<f64> {
+        self.seq_info.as_ref().map(|info| info.timestamp)
+    }
+
+    /// Create a packet carrying mouse / keyboard input
+    ///
+    /// NOTE: Provisional, the reports are serialized with their
+    /// unverified layouts.
+    pub fn mouse_keyboard(
+        sequence_num: u32,
+        timestamp: f64,
+        mouse_report: Option<MouseReport>,
+        keyboard_report: Option<KeyboardReport>,
+    ) -> Result<Self, DekuError> {
+        let mut kinds = vec![];
+        let mut provisional_data = vec![];
+        if let Some(report) = &mouse_report {
+            kinds.push(ReportKind::Mouse);
+            provisional_data.extend(report.to_bytes()?);
+        }
+        if let Some(report) = &keyboard_report {
+            kinds.push(ReportKind::Keyboard);
+            provisional_data.extend(report.to_bytes()?);
+        }
+
+        Ok(Self {
+            report_type: InputReportType::from_reports(&kinds),
+            seq_info: Some(SequenceInfo {
+                sequence_num,
+                timestamp,
+            }),
+            metadata_report: None,
+            gamepad_report: None,
+            client_metadata_report: None,
+            provisional_data,
             vibration_report: None,
+        })
+    }
+
+    /// Undecoded server metadata, mouse and keyboard reports, including
+    /// anything following them
+    pub fn provisional_data(&self) -> &[u8] {
+        &self.provisional_data
+    }
+
+    /// Parse the server metadata, mouse and keyboard reports
+    ///
+    /// NOTE: Provisional, the layouts of these reports have not been
+    /// checked against captured traffic, which is why decoding an
+    /// `InputPacket` leaves them raw. Trailing data is ignored.
+    pub fn provisional_reports(&self) -> Result<ProvisionalReports, DekuError> {
+        let mut rest = (&self.provisional_data[..], 0);
+        Ok(ProvisionalReports {
+            server_metadata: read_report(self.report_type.ServerMetadata, &mut rest)?,
+            mouse: read_report(self.report_type.Mouse, &mut rest)?,
+            keyboard: read_report(self.report_type.Keyboard, &mut rest)?,
+        })
+    }
+
+    /// Create a vibration (rumble) packet.
+    /// Vibration packets carry no sequence info.
+    pub fn vibration(vibration_report: VibrationReport) -> Self {
+        Self {
+            report_type: InputReportType::from_reports(&[ReportKind::Vibration]),
+            seq_info: None,
+            metadata_report: None,
+            gamepad_report: None,
+            client_metadata_report: None,
+            provisional_data: vec![],
+            vibration_report: Some(vibration_report),
         }
     }
 }
@@ -234,6 +526,86 @@ mod tests {
         assert_eq!(parsed.repeat, 0x10);
     }
 
+    #[test]
+    fn report_sizes() {
+        let entry = InputMetadataEntry {
+            server_data_key: 0,
+            first_frame_packet_arrival_time_ms: 0,
+            frame_submitted_time_ms: 0,
+            frame_decoded_time_ms: 0,
+            frame_rendered_time_ms: 0,
+            frame_packet_time: 0,
+            frame_date_now: 0,
+        };
+        assert_eq!(entry.to_bytes().unwrap().len(), InputMetadataEntry::SIZE);
+        assert_eq!(
+            GamepadData::default().to_bytes().unwrap().len(),
+            GamepadData::SIZE
+        );
+    }
+
+    #[test]
+    fn metadata_latencies() {
+        let entry = InputMetadataEntry {
+            server_data_key: 1,
+            first_frame_packet_arrival_time_ms: 1000,
+            frame_submitted_time_ms: 1004,
+            frame_decoded_time_ms: 1012,
+            frame_rendered_time_ms: 1020,
+            frame_packet_time: 0,
+            frame_date_now: 0,
+        };
+        assert_eq!(entry.queue_latency_ms(), 4);
+        assert_eq!(entry.decode_latency_ms(), 8);
+        assert_eq!(entry.render_latency_ms(), 20);
+
+        // Counter wrapped around between arrival and rendering
+        let entry = InputMetadataEntry {
+            first_frame_packet_arrival_time_ms: u32::MAX - 4,
+            frame_submitted_time_ms: u32::MAX,
+            frame_decoded_time_ms: 5,
+            frame_rendered_time_ms: 10,
+            ..entry
+        };
+        assert_eq!(entry.queue_latency_ms(), 4);
+        assert_eq!(entry.decode_latency_ms(), 6);
+        assert_eq!(entry.render_latency_ms(), 15);
+    }
+
+    #[test]
+    fn deserialize_gamepad_report() {
+        let mut test_data = vec![0x01];
+        test_data.extend_from_slice(&GamepadData::default().to_bytes().unwrap());
+
+        let (rest, parsed) = GamepadReport::from_bytes((&test_data, 0))
+            .expect("Failed to deserialize gamepad report");
+
+        assert!(rest.0.is_empty());
+        assert_eq!(parsed.gamepad_data, vec![GamepadData::default()]);
+    }
+
+    #[test]
+    fn deserialize_inflated_queue_len() {
+        let test_data = [&[0xFF][..], &[0x00; GamepadData::SIZE][..]].concat();
+        match GamepadReport::from_bytes((&test_data, 0)) {
+            Err(DekuError::Parse(msg)) => {
+                assert_eq!(
+                    msg,
+                    "Declared count 255 needs 5865 bytes, only 23 available"
+                )
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let test_data = [0xFF, 0x00, 0x00, 0x00];
+        match MetadataReport::from_bytes((&test_data, 0)) {
+            Err(DekuError::Parse(msg)) => {
+                assert_eq!(msg, "Declared count 255 needs 7140 bytes, only 3 available")
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn deserialize_input_packet() {
         let test_data = vec![
@@ -265,6 +637,34 @@ mod tests {
         assert_eq!(vibration_payload.repeat, 0x10);
     }
 
+    #[test]
+    fn serialize_vibration_packet() {
+        let packet = InputPacket::vibration(VibrationReport {
+            rumble_type: 0x00,
+            gamepad_id: 0x00,
+            left_motor_percent: 0xF1,
+            right_motor_percent: 0xF2,
+            left_trigger_motor_percent: 0xF3,
+            right_trigger_motor_percent: 0xF4,
+            duration_ms: 0x150,
+            delay_ms: 0x1FF,
+            repeat: 0x10,
+        });
+
+        let serialized = packet
+            .to_bytes()
+            .expect("Failed to serialize vibration packet");
+
+        assert_eq!(
+            serialized,
+            vec![0x80, 0x00, 0x00, 0xF1, 0xF2, 0xF3, 0xF4, 0x50, 0x01, 0xFF, 0x01, 0x10]
+        );
+
+        let (_, parsed) = InputPacket::from_bytes((&serialized, 0))
+            .expect("Failed to deserialize vibration packet");
+        assert_eq!(parsed, packet);
+    }
+
     #[test]
     fn parse_input_report_type() {
         let data = [0x41u8];
@@ -282,6 +682,146 @@ mod tests {
         assert!(!parsed.Mouse);
     }
 
+    #[test]
+    fn input_report_type_from_reports() {
+        let report_type =
+            InputReportType::from_reports(&[ReportKind::Metadata, ReportKind::Keyboard]);
+        assert_eq!(report_type.to_bytes().unwrap(), vec![0x41]);
+        assert!(report_type.contains(ReportKind::Metadata));
+        assert!(report_type.contains(ReportKind::Keyboard));
+        assert!(!report_type.contains(ReportKind::GamepadReport));
+
+        assert_eq!(
+            InputReportType::from_reports(&[]),
+            InputReportType::default()
+        );
+    }
+
+    #[test]
+    fn serialize_mismatching_report_type() {
+        let packet = InputPacket::new(
+            1,
+            0.0,
+            None,
+            Some(GamepadReport {
+                queue_len: 0,
+                gamepad_data: vec![],
+            }),
+            None,
+        );
+        assert!(packet.to_bytes().is_ok());
+
+        // GamepadReport bit set, but no report
+        let packet = InputPacket {
+            report_type: InputReportType::from_reports(&[ReportKind::GamepadReport]),
+            gamepad_report: None,
+            ..packet
+        };
+        assert!(matches!(packet.to_bytes(), Err(DekuError::Assertion(_))));
+
+        // Report present, but bit not set
+        let packet = InputPacket {
+            report_type: InputReportType::default(),
+            client_metadata_report: Some(ClientMetadataReport::default()),
+            ..packet
+        };
+        assert!(matches!(packet.to_bytes(), Err(DekuError::Assertion(_))));
+    }
+
+    #[test]
+    fn deserialize_server_metadata_packet() {
+        let mut test_data = vec![0x10];
+        test_data.extend_from_slice(&7u32.to_le_bytes());
+        test_data.extend_from_slice(&1.5f64.to_le_bytes());
+        test_data.extend_from_slice(&[0x80, 0x07, 0x00, 0x00, 0x38, 0x04, 0x00, 0x00, 0x0A]);
+
+        let (rest, parsed) = InputPacket::from_bytes((&test_data, 0))
+            .expect("Failed to deserialize server metadata packet");
+
+        assert!(rest.0.is_empty());
+        assert_eq!(parsed.sequence_num(), Some(7));
+        // Not decoded by default
+        assert_eq!(parsed.provisional_data(), &test_data[13..]);
+        assert_eq!(
+            parsed.provisional_reports().unwrap(),
+            ProvisionalReports {
+                server_metadata: Some(ServerMetadataReport {
+                    width: 1920,
+                    height: 1080,
+                    max_touchpoints: 10,
+                }),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_provisional_report_length_mismatch() {
+        // Keyboard report longer than the guessed 3 bytes
+        let mut test_data = vec![0x40];
+        test_data.extend_from_slice(&1u32.to_le_bytes());
+        test_data.extend_from_slice(&0f64.to_le_bytes());
+        test_data.extend_from_slice(&[0x01, 0x41, 0x00, 0xAA, 0xBB]);
+
+        // Decoding keeps every byte and round-trips
+        let (rest, parsed) =
+            InputPacket::from_bytes((&test_data, 0)).expect("Failed to deserialize packet");
+        assert!(rest.0.is_empty());
+        assert_eq!(parsed.provisional_data(), &[0x01, 0x41, 0x00, 0xAA, 0xBB]);
+        assert_eq!(parsed.to_bytes().unwrap(), test_data);
+
+        // Data without a matching report type bit
+        let packet = InputPacket {
+            provisional_data: vec![0x01],
+            ..InputPacket::new(1, 0.0, None, None, None)
+        };
+        assert!(matches!(packet.to_bytes(), Err(DekuError::Assertion(_))));
+    }
+
+    #[test]
+    fn mouse_keyboard_packet_round_trip() {
+        let packet = InputPacket::mouse_keyboard(
+            3,
+            10.0,
+            Some(MouseReport {
+                x: -5,
+                y: 12,
+                wheel_x: 0,
+                wheel_y: -120,
+                buttons: MouseButton {
+                    Left: true,
+                    ..Default::default()
+                },
+                relative: 1,
+            }),
+            Some(KeyboardReport {
+                pressed: 1,
+                key_code: 0x41,
+            }),
+        )
+        .expect("Failed to create packet");
+
+        let serialized = packet.to_bytes().expect("Failed to serialize packet");
+        // Report type, sequence info, 14 bytes mouse, 3 bytes keyboard
+        assert_eq!(serialized.len(), 1 + 12 + 14 + 3);
+        assert_eq!(serialized[0], 0x60);
+        assert_eq!(&serialized[13..17], &(-5i32).to_le_bytes());
+        assert_eq!(serialized[25], 0x01);
+        assert_eq!(&serialized[27..], &[0x01, 0x41, 0x00]);
+
+        let (rest, parsed) =
+            InputPacket::from_bytes((&serialized, 0)).expect("Failed to deserialize packet");
+        assert!(rest.0.is_empty());
+        assert_eq!(parsed, packet);
+
+        let reports = parsed
+            .provisional_reports()
+            .expect("Failed to parse provisional reports");
+        assert!(reports.server_metadata.is_none());
+        assert!(reports.mouse.unwrap().buttons.Left);
+        assert_eq!(reports.keyboard.unwrap().key_code, 0x41);
+    }
+
     #[test]
     fn parse_gamepad_button() {
         // A, DPadRight, LeftThumb
@@ -0,0 +1,595 @@
+use crate::api::IceCandidate;
+
+/// `max-message-size` to assume if the attribute is absent, see RFC 8841
+const DEFAULT_MAX_MESSAGE_SIZE: u64 = 65536;
+
+/// SCTP parameters of the data channel media section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SctpParameters {
+    pub port: u16,
+    /// Largest message the remote accepts, 0 means no limit
+    pub max_message_size: u64,
+}
+
+impl SctpParameters {
+    /// Whether a message of `len` bytes may be sent
+    pub fn allows_message(&self, len: usize) -> bool {
+        self.max_message_size == 0 || len as u64 <= self.max_message_size
+    }
+}
+
+/// Bandwidth attribute (b=<modifier>:<value> line), see RFC 4566 / 3890
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandwidthLimit {
+    /// Media type of the section, `None` for a session level limit
+    pub media: Option<String>,
+    pub mid: Option<String>,
+    /// Bandwidth type, e.g. AS, CT, TIAS
+    pub modifier: String,
+    /// Limit in the unit of the modifier, kbps for AS / CT, bps for TIAS
+    pub value: u64,
+}
+
+impl BandwidthLimit {
+    /// Limit in bits per second, `None` for unknown modifiers
+    pub fn bits_per_second(&self) -> Option<u64> {
+        match self.modifier.as_str() {
+            "AS" | "CT" => self.value.checked_mul(1000),
+            "TIAS" => Some(self.value),
+            _ => None,
+        }
+    }
+}
+
+/// Codec of a media section, from its a=rtpmap / a=fmtp / a=rtcp-fb lines
+///
+/// Dynamic payload types are assigned per session, the ones of the SDP
+/// answer are the ones the server sends with.
+///
+/// Informational only, there is no need to register codecs at these
+/// payload types: webrtc-rs takes the payload types of the remote
+/// description when applying the answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiatedCodec {
+    /// Media type of the section, e.g. audio, video
+    pub media: String,
+    pub mid: Option<String>,
+    pub payload_type: u8,
+    /// Mime type, e.g. video/H264
+    pub mime_type: String,
+    pub clock_rate: u32,
+    /// Audio channels, 0 if not specified
+    pub channels: u16,
+    /// Format parameters, empty if there is no a=fmtp line
+    pub fmtp: String,
+    /// RTCP feedback, e.g. "nack pli"
+    pub rtcp_feedback: Vec<String>,
+}
+
+/// Media section (m-line) of a session description
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MediaSection {
+    /// Media type, e.g. audio, video, application
+    pub kind: String,
+    pub mid: Option<String>,
+    /// Payload types / formats listed in the m-line
+    pub formats: Vec<String>,
+}
+
+/// Collect the media sections of a session description
+pub(crate) fn media_sections(sdp: &str) -> Vec<MediaSection> {
+    let mut sections: Vec<MediaSection> = vec![];
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(media) = line.strip_prefix("m=") {
+            // m=<media> <port> <proto> <fmt> ...
+            let mut fields = media.split_whitespace();
+            let kind = fields.next().unwrap_or_default().to_owned();
+            let formats = fields.skip(2).map(str::to_owned).collect();
+
+            sections.push(MediaSection {
+                kind,
+                mid: None,
+                formats,
+            });
+        } else if let Some(mid) = line.strip_prefix("a=mid:") {
+            if let Some(section) = sections.last_mut() {
+                section.mid = Some(mid.to_owned());
+            }
+        }
+    }
+
+    sections
+}
+
+/// Collect the codecs of all media sections, in the order of the m-lines
+/// (most preferred first)
+///
+/// Formats without a (valid) a=rtpmap line are skipped.
+pub(crate) fn negotiated_codecs(sdp: &str) -> Vec<NegotiatedCodec> {
+    let mut codecs: Vec<NegotiatedCodec> = vec![];
+    let mut media = String::new();
+    let mut formats: Vec<u8> = vec![];
+    let mut mid: Option<String> = None;
+    let mut section_start = 0;
+
+    // a=rtpmap lines needn't follow the order of the m-line,
+    // sort the codecs of each section once it is complete
+    let finish_section =
+        |codecs: &mut Vec<NegotiatedCodec>, formats: &[u8], section_start: usize| {
+            codecs[section_start..].sort_by_key(|codec| {
+                formats
+                    .iter()
+                    .position(|format| *format == codec.payload_type)
+                    .unwrap_or(usize::MAX)
+            });
+        };
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("m=") {
+            finish_section(&mut codecs, &formats, section_start);
+
+            // m=<media> <port> <proto> <fmt> ...
+            let mut fields = value.split_whitespace();
+            media = fields.next().unwrap_or_default().to_owned();
+            formats = fields.skip(2).filter_map(|fmt| fmt.parse().ok()).collect();
+            mid = None;
+            section_start = codecs.len();
+        } else if let Some(value) = line.strip_prefix("a=mid:") {
+            mid = Some(value.to_owned());
+            for codec in &mut codecs[section_start..] {
+                codec.mid = mid.clone();
+            }
+        } else if let Some(value) = line.strip_prefix("a=rtpmap:") {
+            // a=rtpmap:<payload type> <encoding name>/<clock rate>[/<channels>]
+            let (payload_type, encoding) = match value.split_once(' ') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let payload_type = match payload_type.parse() {
+                Ok(payload_type) if formats.contains(&payload_type) => payload_type,
+                _ => continue,
+            };
+            let mut encoding = encoding.trim().split('/');
+            let name = encoding.next().unwrap_or_default();
+            let clock_rate = match encoding.next().map(str::parse::<u32>) {
+                Some(Ok(clock_rate)) => clock_rate,
+                _ => continue,
+            };
+            let channels = encoding.next().and_then(|ch| ch.parse().ok()).unwrap_or(0);
+
+            codecs.push(NegotiatedCodec {
+                media: media.clone(),
+                mid: mid.clone(),
+                payload_type,
+                mime_type: format!("{}/{}", media, name),
+                clock_rate,
+                channels,
+                fmtp: String::new(),
+                rtcp_feedback: vec![],
+            });
+        } else if let Some(value) = line.strip_prefix("a=fmtp:") {
+            if let Some((payload_type, params)) = value.split_once(' ') {
+                for codec in &mut codecs[section_start..] {
+                    if payload_type.parse() == Ok(codec.payload_type) {
+                        codec.fmtp = params.trim().to_owned();
+                    }
+                }
+            }
+        } else if let Some(value) = line.strip_prefix("a=rtcp-fb:") {
+            if let Some((payload_type, feedback)) = value.split_once(' ') {
+                for codec in &mut codecs[section_start..] {
+                    if payload_type.parse() == Ok(codec.payload_type) {
+                        codec.rtcp_feedback.push(feedback.trim().to_owned());
+                    }
+                }
+            }
+        }
+    }
+    finish_section(&mut codecs, &formats, section_start);
+
+    codecs
+}
+
+/// Collect the ICE candidates (a=candidate lines) of a session description,
+/// e.g. of a local description after gathering completed
+pub(crate) fn ice_candidates(sdp: &str) -> Vec<IceCandidate> {
+    let mut candidates: Vec<IceCandidate> = vec![];
+    let mut mline_index: Option<u16> = None;
+    let mut mid: Option<String> = None;
+    let mut ufrag: Option<String> = None;
+    let mut section_start = 0;
+
+    for line in sdp.lines().map(str::trim) {
+        if line.starts_with("m=") {
+            mline_index = Some(mline_index.map_or(0, |index| index + 1));
+            mid = None;
+            section_start = candidates.len();
+        } else if let Some(value) = line.strip_prefix("a=mid:") {
+            mid = Some(value.to_owned());
+            // a=mid may follow the candidates of its section
+            for candidate in &mut candidates[section_start..] {
+                candidate.sdp_mid = mid.clone();
+            }
+        } else if let Some(value) = line.strip_prefix("a=ice-ufrag:") {
+            ufrag = Some(value.to_owned());
+        } else if let Some(candidate) = line.strip_prefix("a=") {
+            if candidate.starts_with("candidate:") {
+                candidates.push(IceCandidate {
+                    candidate: candidate.to_owned(),
+                    sdp_mid: mid.clone(),
+                    sdp_mline_index: mline_index,
+                    username_fragment: ufrag.clone(),
+                });
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Collect the bandwidth limits (b= lines) of a session description,
+/// lines with an invalid value are skipped
+pub(crate) fn bandwidth_limits(sdp: &str) -> Vec<BandwidthLimit> {
+    let mut limits: Vec<BandwidthLimit> = vec![];
+    let mut media: Option<String> = None;
+    let mut section_start = 0;
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("m=") {
+            media = value.split_whitespace().next().map(str::to_owned);
+            section_start = limits.len();
+        } else if let Some(mid) = line.strip_prefix("a=mid:") {
+            // b= lines precede a=mid in a media section
+            if media.is_some() {
+                for limit in &mut limits[section_start..] {
+                    limit.mid = Some(mid.to_owned());
+                }
+            }
+        } else if let Some(bandwidth) = line.strip_prefix("b=") {
+            let (modifier, value) = match bandwidth.split_once(':') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if let Ok(value) = value.trim().parse() {
+                limits.push(BandwidthLimit {
+                    media: media.clone(),
+                    mid: None,
+                    modifier: modifier.to_owned(),
+                    value,
+                });
+            }
+        }
+    }
+
+    limits
+}
+
+/// Parse `a=sctp-port` / `a=max-message-size` of the application
+/// media section, `None` if there is none or it lacks a valid SCTP port
+pub(crate) fn sctp_parameters(sdp: &str) -> Option<SctpParameters> {
+    let mut in_application = false;
+    let mut port: Option<u16> = None;
+    let mut max_message_size = DEFAULT_MAX_MESSAGE_SIZE;
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(media) = line.strip_prefix("m=") {
+            in_application = media.starts_with("application");
+        } else if !in_application {
+            continue;
+        } else if let Some(value) = line.strip_prefix("a=sctp-port:") {
+            port = value.parse().ok();
+        } else if let Some(value) = line.strip_prefix("a=max-message-size:") {
+            max_message_size = value.parse().ok()?;
+        }
+    }
+
+    Some(SctpParameters {
+        port: port?,
+        max_message_size,
+    })
+}
+
+/// Check that the media sections of an SDP answer align with the offer
+///
+/// Returns a description of the first mismatch found.
+pub(crate) fn validate_answer(offer: &str, answer: &str) -> Result<(), String> {
+    let offer_sections = media_sections(offer);
+    let answer_sections = media_sections(answer);
+
+    if offer_sections.len() != answer_sections.len() {
+        return Err(format!(
+            "Answer has {} media sections, offer has {}",
+            answer_sections.len(),
+            offer_sections.len()
+        ));
+    }
+
+    for (index, (offered, answered)) in offer_sections.iter().zip(&answer_sections).enumerate() {
+        if offered.kind != answered.kind {
+            return Err(format!(
+                "Media section {} is {} in answer, but {} in offer",
+                index, answered.kind, offered.kind
+            ));
+        }
+
+        if offered.mid != answered.mid {
+            return Err(format!(
+                "Media section {} ({}) has mid {:?} in answer, but {:?} in offer",
+                index, answered.kind, answered.mid, offered.mid
+            ));
+        }
+
+        if let Some(format) = answered
+            .formats
+            .iter()
+            .find(|format| !offered.formats.contains(format))
+        {
+            return Err(format!(
+                "Media section {} ({}) answered with format {}, which was not offered",
+                index, answered.kind, format
+            ));
+        }
+    }
+
+    let has_data_channels = offer_sections
+        .iter()
+        .any(|section| section.kind == "application");
+    if has_data_channels && sctp_parameters(answer).is_none() {
+        return Err("Answer lacks valid SCTP parameters for the data channels".into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFER: &str = "v=0\r\n\
+        m=audio 9 UDP/TLS/RTP/SAVPF 111 63\r\n\
+        a=mid:0\r\n\
+        m=video 9 UDP/TLS/RTP/SAVPF 96 102\r\n\
+        a=mid:1\r\n\
+        m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+        a=mid:2\r\n";
+
+    #[test]
+    fn parse_media_sections() {
+        let sections = media_sections(OFFER);
+
+        assert_eq!(sections.len(), 3);
+        assert_eq!(
+            sections[1],
+            MediaSection {
+                kind: "video".into(),
+                mid: Some("1".into()),
+                formats: vec!["96".into(), "102".into()],
+            }
+        );
+        assert_eq!(sections[2].formats, vec!["webrtc-datachannel".to_owned()]);
+    }
+
+    #[test]
+    fn parse_negotiated_codecs() {
+        let answer = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111 110\r\n\
+            a=mid:0\r\n\
+            a=rtpmap:111 opus/48000/2\r\n\
+            a=fmtp:111 minptime=10;useinbandfec=1\r\n\
+            a=rtpmap:110 telephone-event/48000\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 127 121\r\n\
+            a=mid:1\r\n\
+            a=rtpmap:121 rtx/90000\r\n\
+            a=fmtp:121 apt=127\r\n\
+            a=rtpmap:127 H264/90000\r\n\
+            a=rtcp-fb:127 nack\r\n\
+            a=rtcp-fb:127 nack pli\r\n\
+            a=fmtp:127 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42002a\r\n\
+            a=rtpmap:96 VP8/90000\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:2\r\n";
+
+        let codecs = negotiated_codecs(answer);
+
+        let payload_types: Vec<u8> = codecs.iter().map(|codec| codec.payload_type).collect();
+        assert_eq!(payload_types, vec![111, 110, 127, 121]);
+        assert_eq!(
+            codecs[0],
+            NegotiatedCodec {
+                media: "audio".into(),
+                mid: Some("0".into()),
+                payload_type: 111,
+                mime_type: "audio/opus".into(),
+                clock_rate: 48000,
+                channels: 2,
+                fmtp: "minptime=10;useinbandfec=1".into(),
+                rtcp_feedback: vec![],
+            }
+        );
+        assert_eq!(
+            codecs[2],
+            NegotiatedCodec {
+                media: "video".into(),
+                mid: Some("1".into()),
+                payload_type: 127,
+                mime_type: "video/H264".into(),
+                clock_rate: 90000,
+                channels: 0,
+                fmtp: "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42002a"
+                    .into(),
+                rtcp_feedback: vec!["nack".into(), "nack pli".into()],
+            }
+        );
+        assert_eq!(codecs[3].fmtp, "apt=127");
+        assert!(negotiated_codecs(OFFER).is_empty());
+    }
+
+    #[test]
+    fn parse_ice_candidates() {
+        let sdp = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=ice-ufrag:bSbi\r\n\
+            a=mid:0\r\n\
+            a=candidate:3129489152 1 udp 2122260223 192.168.100.211 49254 typ host\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=candidate:1504293356 1 udp 1686052607 111.243.105.102 36059 typ srflx\r\n\
+            a=mid:1\r\n";
+
+        let candidates = ice_candidates(sdp);
+
+        assert_eq!(
+            candidates,
+            vec![
+                IceCandidate {
+                    candidate:
+                        "candidate:3129489152 1 udp 2122260223 192.168.100.211 49254 typ host"
+                            .into(),
+                    sdp_mid: Some("0".into()),
+                    sdp_mline_index: Some(0),
+                    username_fragment: Some("bSbi".into()),
+                },
+                IceCandidate {
+                    candidate:
+                        "candidate:1504293356 1 udp 1686052607 111.243.105.102 36059 typ srflx"
+                            .into(),
+                    sdp_mid: Some("1".into()),
+                    sdp_mline_index: Some(1),
+                    username_fragment: Some("bSbi".into()),
+                },
+            ]
+        );
+        assert!(ice_candidates(OFFER).is_empty());
+    }
+
+    #[test]
+    fn validate_matching_answer() {
+        let answer = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:0\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 102\r\n\
+            a=mid:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:2\r\n\
+            a=sctp-port:5000\r\n";
+
+        assert_eq!(validate_answer(OFFER, answer), Ok(()));
+    }
+
+    #[test]
+    fn parse_bandwidth_limits() {
+        let answer = "v=0\r\n\
+            b=CT:5000\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 127\r\n\
+            b=TIAS:20000000\r\n\
+            a=mid:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            c=IN IP4 0.0.0.0\r\n\
+            b=AS:30\r\n\
+            b=X-INVALID\r\n\
+            a=mid:2\r\n";
+
+        let limits = bandwidth_limits(answer);
+        assert_eq!(limits.len(), 3);
+        assert_eq!(limits[0].media, None);
+        assert_eq!(limits[0].bits_per_second(), Some(5_000_000));
+        assert_eq!(limits[1].mid.as_deref(), Some("1"));
+        assert_eq!(limits[1].bits_per_second(), Some(20_000_000));
+        assert_eq!(
+            limits[2],
+            BandwidthLimit {
+                media: Some("application".into()),
+                mid: Some("2".into()),
+                modifier: "AS".into(),
+                value: 30,
+            }
+        );
+        assert_eq!(limits[2].bits_per_second(), Some(30_000));
+        assert!(bandwidth_limits(OFFER).is_empty());
+    }
+
+    #[test]
+    fn parse_sctp_parameters() {
+        let answer = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=max-message-size:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=sctp-port:5000\r\n\
+            a=max-message-size:262144\r\n";
+
+        let params = sctp_parameters(answer).unwrap();
+        assert_eq!(
+            params,
+            SctpParameters {
+                port: 5000,
+                max_message_size: 262144,
+            }
+        );
+        assert!(params.allows_message(262144));
+        assert!(!params.allows_message(262145));
+
+        // Defaults to 64 KiB
+        let params = sctp_parameters(
+            "m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=sctp-port:5000\r\n",
+        );
+        assert_eq!(params.unwrap().max_message_size, 65536);
+        assert!(SctpParameters {
+            port: 5000,
+            max_message_size: 0
+        }
+        .allows_message(usize::MAX));
+
+        assert_eq!(sctp_parameters(OFFER), None);
+        assert_eq!(
+            sctp_parameters(
+                "m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=sctp-port:x\r\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn validate_answer_without_sctp() {
+        let answer = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:0\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 102\r\n\
+            a=mid:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:2\r\n";
+
+        assert_eq!(
+            validate_answer(OFFER, answer),
+            Err("Answer lacks valid SCTP parameters for the data channels".into())
+        );
+    }
+
+    #[test]
+    fn validate_mismatching_answer() {
+        let missing_section = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:0\r\n";
+        assert!(validate_answer(OFFER, missing_section).is_err());
+
+        let swapped_sections = "v=0\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 102\r\n\
+            a=mid:0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:2\r\n";
+        assert!(validate_answer(OFFER, swapped_sections).is_err());
+
+        let unoffered_codec = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=mid:0\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 104\r\n\
+            a=mid:1\r\n\
+            m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+            a=mid:2\r\n";
+        assert_eq!(
+            validate_answer(OFFER, unoffered_codec),
+            Err("Media section 1 (video) answered with format 104, which was not offered".into())
+        );
+    }
+}
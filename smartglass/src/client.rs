@@ -1,8 +1,8 @@
 use super::models;
 use reqwest;
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use serde::{de::DeserializeOwned, Serialize};
 use std::default::Default;
+use std::time::{Duration, Instant};
 use uuid;
 use xal::cvlib::CorrelationVector;
 use xal::models as xal_models;
@@ -12,7 +12,75 @@ use xal::request_signer::SigningReqwestBuilder;
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
+/// Interval of console status polling while waiting for wake up
+const WAKE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Base URI of the console command service
+pub const XCCS_BASE_URI: &str = "https://xccs.xboxlive.com";
+
+/// Polls the console status, yielding only changes of power
+/// state, focused app or playback state
+///
+/// Holds no reference to the client, it is passed on every call
+/// and stays usable for other requests in between.
+pub struct ConsoleStatusPoller {
+    console_live_id: String,
+    interval: Duration,
+    last: Option<models::SmartglassConsoleStatus>,
+}
+
+impl ConsoleStatusPoller {
+    /// Poll the status of `console_live_id` every `interval`
+    pub fn new(console_live_id: String, interval: Duration) -> Self {
+        Self {
+            console_live_id,
+            interval,
+            last: None,
+        }
+    }
+
+    /// Poll until the status changed, the first call returns the
+    /// current status right away.
+    ///
+    /// A failed status request is returned as error, polling
+    /// continues on the next call.
+    pub async fn next_change(
+        &mut self,
+        client: &mut SmartglassClient,
+    ) -> Result<models::SmartglassConsoleStatus> {
+        loop {
+            if self.last.is_some() {
+                tokio::time::sleep(self.interval).await;
+            }
+            let status = client
+                .get_console_status(self.console_live_id.clone())
+                .await?;
+
+            let changed = self
+                .last
+                .as_ref()
+                .map_or(true, |last| status.has_changed(last));
+            if changed {
+                self.last = Some(status.clone());
+                return Ok(status);
+            }
+        }
+    }
+}
+
+/// Parameters of `SmartglassClient::command_audio_volume`,
+/// changes the volume by one step unless `amount` is given
+fn audio_volume_parameters(
+    direction: models::VolumeDirection,
+    amount: Option<i32>,
+) -> models::CommandParameters {
+    models::CommandParameters::new()
+        .direction(direction)
+        .amount(amount.unwrap_or(1))
+}
+
 pub struct SmartglassClient {
+    base_uri: String,
     session_id: uuid::Uuid,
     request_signer: request_signer::RequestSigner,
     client: reqwest::Client,
@@ -20,6 +88,8 @@ pub struct SmartglassClient {
 }
 
 impl SmartglassClient {
+    /// `token` has to be issued for the Xbox Live relying party, e.g. via
+    /// `XalAuthenticator::get_smartglass_token`
     pub fn new(
         token: xal_models::response::XSTSResponse,
         session_id: Option<uuid::Uuid>,
@@ -43,6 +113,7 @@ impl SmartglassClient {
                 .build()?;
 
         Ok(Self {
+            base_uri: XCCS_BASE_URI.to_owned(),
             session_id: session_id.unwrap_or_else(uuid::Uuid::new_v4),
             request_signer: request_signer::RequestSigner::default(),
             ms_cv: CorrelationVector::default(),
@@ -50,33 +121,58 @@ impl SmartglassClient {
         })
     }
 
+    /// Send requests to `base_uri` instead of `XCCS_BASE_URI`,
+    /// e.g. a local server in tests
+    pub fn set_base_uri(&mut self, base_uri: impl Into<String>) {
+        self.base_uri = base_uri.into();
+    }
+
     fn next_cv(&mut self) -> String {
         self.ms_cv.increment();
         self.ms_cv.to_string()
     }
 
+    /// Add the next MS-CV and the `Signature` header to `request` and
+    /// build it, without sending. Allows logging exactly what is sent.
+    ///
+    /// NOTE: The client's default headers (Authorization, skillplatform,
+    /// x-xbl-contract-version, User-Agent) are only added when sending,
+    /// they are neither part of the returned request nor of the signature.
+    pub fn build_signed(&mut self, request: reqwest::RequestBuilder) -> Result<reqwest::Request> {
+        Ok(request
+            .header("MS-CV", self.next_cv())
+            .sign(&self.request_signer, None)?
+            .build()?)
+    }
+
+    async fn send_signed<T>(&mut self, request: reqwest::RequestBuilder) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let request = self.build_signed(request)?;
+
+        self.client
+            .execute(request)
+            .await?
+            .json::<T>()
+            .await
+            .map_err(|err| err.into())
+    }
+
     pub async fn fetch_operation_status(
         &mut self,
         operation_id: String,
         device_id: String,
     ) -> Result<models::OperationStatusResponse> {
-        let url = "https://xccs.xboxlive.com/opStatus";
+        let url = format!("{}/opStatus", self.base_uri);
 
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert("x-xbl-contract-version", "3".parse()?);
         headers.insert("x-xbl-opId", operation_id.parse()?);
         headers.insert("x-xbl-deviceId", device_id.parse()?);
 
-        self.client
-            .get(url)
-            .headers(headers)
-            .header("MS-CV", self.next_cv())
-            .sign(&self.request_signer, None)?
-            .send()
-            .await?
-            .json::<models::OperationStatusResponse>()
-            .await
-            .map_err(|err| err.into())
+        let request = self.client.get(&url).headers(headers);
+        self.send_signed(request).await
     }
 
     pub async fn get_console_status(
@@ -84,46 +180,41 @@ impl SmartglassClient {
         console_live_id: String,
     ) -> Result<models::SmartglassConsoleStatus> {
         let url = format!(
-            "https://xccs.xboxlive.com/consoles/{live_id}",
+            "{base_uri}/consoles/{live_id}",
+            base_uri = self.base_uri,
             live_id = console_live_id
         );
 
-        self.client
-            .get(&url)
-            .header("MS-CV", self.next_cv())
-            .sign(&self.request_signer, None)?
-            .send()
-            .await?
-            .json::<models::SmartglassConsoleStatus>()
-            .await
-            .map_err(|err| err.into())
+        let request = self.client.get(&url);
+        self.send_signed(request).await
     }
 
-    async fn fetch_list<T>(
-        &mut self,
-        list_name: String,
-        query_params: Option<HashMap<String, String>>,
-    ) -> Result<T>
+    /// Poll the console status every `interval`, yielding only changes
+    /// of power state, focused app or playback state.
+    ///
+    /// The poller doesn't borrow the client, pass it to
+    /// `ConsoleStatusPoller::next_change`.
+    pub fn watch_console_status(
+        &self,
+        console_live_id: String,
+        interval: Duration,
+    ) -> ConsoleStatusPoller {
+        ConsoleStatusPoller::new(console_live_id, interval)
+    }
+
+    async fn fetch_list<T, Q>(&mut self, list_name: String, query: &Q) -> Result<T>
     where
         T: DeserializeOwned,
+        Q: Serialize,
     {
         let url = format!(
-            "https://xccs.xboxlive.com/lists/{list_name}",
+            "{base_uri}/lists/{list_name}",
+            base_uri = self.base_uri,
             list_name = list_name
         );
 
-        let mut req_builder = self.client.get(&url);
-        if query_params.is_some() {
-            req_builder = req_builder.query(&query_params.unwrap())
-        }
-        req_builder
-            .header("MS-CV", self.next_cv())
-            .sign(&self.request_signer, None)?
-            .send()
-            .await?
-            .json::<T>()
-            .await
-            .map_err(|err| err.into())
+        let request = self.client.get(&url).query(query);
+        self.send_signed(request).await
     }
 
     async fn send_oneshot_command(
@@ -131,9 +222,9 @@ impl SmartglassClient {
         console_live_id: String,
         command_type: String,
         command: String,
-        parameters: Option<Vec<HashMap<String, String>>>,
+        parameters: Option<models::CommandParameters>,
     ) -> Result<models::CommandResponse> {
-        let url = "https://xccs.xboxlive.com/commands";
+        let url = format!("{}/commands", self.base_uri);
 
         let json_body = models::request::OneShotCommandRequest {
             destination: "Xbox".to_owned(),
@@ -141,51 +232,97 @@ impl SmartglassClient {
             command,
             session_id: self.session_id.hyphenated().to_string(),
             source_id: "com.microsoft.smartglass".to_owned(),
-            parameters,
+            parameters: parameters.map(Into::into),
             linked_xbox_id: console_live_id,
         };
 
-        self.client
-            .post(url)
-            .header("MS-CV", self.next_cv())
-            .json(&json_body)
-            .sign(&self.request_signer, None)?
-            .send()
-            .await?
-            .json::<models::CommandResponse>()
-            .await
-            .map_err(|err| err.into())
+        let request = self.client.post(&url).json(&json_body);
+        self.send_signed(request).await
+    }
+
+    /// Run `commands` in order, e.g. wake up followed by launching a game
+    ///
+    /// Stops at the first command that fails or is rejected by the
+    /// command service. Returns one result per executed command, so
+    /// the last entry holds the error if the sequence was cut short.
+    ///
+    /// NOTE: Every command is still a separate, signed request, the
+    /// sequence is not atomic: commands before a failure stay executed.
+    pub async fn command_sequence(
+        &mut self,
+        console_live_id: String,
+        commands: Vec<models::OneShotCommand>,
+    ) -> Vec<Result<models::CommandResponse>> {
+        let mut results = vec![];
+
+        for command in commands {
+            let models::OneShotCommand {
+                command_type,
+                command,
+                parameters,
+            } = command;
+            let name = format!("{}/{}", command_type, command);
+
+            let result = self
+                .send_oneshot_command(console_live_id.clone(), command_type, command, parameters)
+                .await
+                .and_then(|resp| {
+                    if resp.is_success() {
+                        Ok(resp)
+                    } else {
+                        Err(format!("Command {} failed: {:?}", name, resp).into())
+                    }
+                });
+
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+
+        results
     }
 
     pub async fn get_console_list(&mut self) -> Result<models::SmartglassConsoleList> {
-        let mut query_params: HashMap<String, String> = HashMap::new();
-        query_params.insert("queryCurrentDevice".to_owned(), "false".to_owned());
-        query_params.insert("includeStorageDevices".to_owned(), "true".to_owned());
+        self.list_devices(models::ListDevicesQuery::default()).await
+    }
 
-        self.fetch_list("devices".to_owned(), Some(query_params))
-            .await
+    pub async fn list_devices(
+        &mut self,
+        query: models::ListDevicesQuery,
+    ) -> Result<models::SmartglassConsoleList> {
+        self.fetch_list("devices".to_owned(), &query).await
     }
 
     pub async fn get_storage_devices(
         &mut self,
         device_id: String,
     ) -> Result<models::StorageDevicesList> {
-        let mut query_params: HashMap<String, String> = HashMap::new();
-        query_params.insert("deviceId".to_owned(), device_id);
+        let query = models::DeviceQuery { device_id };
 
-        self.fetch_list("storageDevices".to_owned(), Some(query_params))
-            .await
+        self.fetch_list("storageDevices".to_owned(), &query).await
     }
 
     pub async fn get_installed_apps(
         &mut self,
         device_id: String,
     ) -> Result<models::InstalledPackagesList> {
-        let mut query_params: HashMap<String, String> = HashMap::new();
-        query_params.insert("deviceId".to_owned(), device_id);
+        let query = models::DeviceQuery { device_id };
 
-        self.fetch_list("installedApps".to_owned(), Some(query_params))
-            .await
+        self.fetch_list("installedApps".to_owned(), &query).await
+    }
+
+    /// Look up the installed package of `title_id` on a console, e.g. to
+    /// pass its instance id to `command_shell_uninstall_package`
+    pub async fn find_installed_package(
+        &mut self,
+        device_id: String,
+        title_id: i32,
+    ) -> Result<Option<models::InstalledPackage>> {
+        let installed = self.get_installed_apps(device_id).await?;
+
+        Ok(installed.by_title_id(title_id).cloned())
     }
 
     pub async fn command_power_wake_up(
@@ -201,6 +338,36 @@ impl SmartglassClient {
         .await
     }
 
+    /// Wake up the console and wait until it is fully powered on
+    ///
+    /// Polls the console status until its power state is `On`, returns
+    /// the final status or an error if `timeout` elapses before.
+    pub async fn wake_and_wait(
+        &mut self,
+        console_live_id: String,
+        timeout: Duration,
+    ) -> Result<models::SmartglassConsoleStatus> {
+        let deadline = Instant::now() + timeout;
+        self.command_power_wake_up(console_live_id.clone()).await?;
+
+        loop {
+            let status = self.get_console_status(console_live_id.clone()).await?;
+            if status.power_state() == models::PowerState::On {
+                return Ok(status);
+            }
+
+            if Instant::now() >= deadline {
+                Err(format!(
+                    "Console did not power on within {:?}, power state: {:?}",
+                    timeout,
+                    status.power_state()
+                ))?
+            }
+
+            tokio::time::sleep(WAKE_POLL_INTERVAL).await;
+        }
+    }
+
     pub async fn command_power_turn_off(
         &mut self,
         console_live_id: String,
@@ -254,9 +421,7 @@ impl SmartglassClient {
         direction: models::VolumeDirection,
         amount: Option<i32>,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("direction".to_owned(), direction.to_string());
-        parameters[0].insert("amount".to_owned(), amount.unwrap_or(1).to_string());
+        let parameters = audio_volume_parameters(direction, amount);
 
         self.send_oneshot_command(
             console_live_id,
@@ -285,8 +450,7 @@ impl SmartglassClient {
         console_live_id: String,
         enable: bool,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("enabled".to_owned(), enable.to_string().to_lowercase());
+        let parameters = models::CommandParameters::new().enabled(enable);
 
         self.send_oneshot_command(
             console_live_id,
@@ -302,8 +466,7 @@ impl SmartglassClient {
         console_live_id: String,
         enable: bool,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("enabled".to_owned(), enable.to_string().to_lowercase());
+        let parameters = models::CommandParameters::new().enabled(enable);
 
         self.send_oneshot_command(
             console_live_id,
@@ -496,28 +659,37 @@ impl SmartglassClient {
         .await
     }
 
+    /// Search the store for `query`
     pub async fn command_marketplace_search_store(
         &mut self,
         console_live_id: String,
+        query: String,
     ) -> Result<models::CommandResponse> {
+        let parameters = models::CommandParameters::new().query_string(query);
+
         self.send_oneshot_command(
             console_live_id,
             "Marketplace".to_owned(),
             "SearchTheStore".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
 
+    /// Show the store page of the title with `one_store_product_id`
     pub async fn command_marketplace_show_title(
         &mut self,
         console_live_id: String,
+        one_store_product_id: String,
     ) -> Result<models::CommandResponse> {
+        let parameters =
+            models::CommandParameters::new().one_store_product_id(one_store_product_id);
+
         self.send_oneshot_command(
             console_live_id,
             "Marketplace".to_owned(),
             "ShowTitle".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
@@ -567,8 +739,8 @@ impl SmartglassClient {
         console_live_id: String,
         one_store_product_id: String,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("oneStoreProductId".to_owned(), one_store_product_id);
+        let parameters =
+            models::CommandParameters::new().one_store_product_id(one_store_product_id);
 
         self.send_oneshot_command(
             console_live_id,
@@ -595,12 +767,15 @@ impl SmartglassClient {
     pub async fn command_shell_change_view(
         &mut self,
         console_live_id: String,
+        view_name: String,
     ) -> Result<models::CommandResponse> {
+        let parameters = models::CommandParameters::new().view_name(view_name);
+
         self.send_oneshot_command(
             console_live_id,
             "Shell".to_owned(),
             "ChangeView".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
@@ -649,8 +824,7 @@ impl SmartglassClient {
         console_live_id: String,
         big_cat_ids: Vec<String>,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("bigCatIdList".to_owned(), big_cat_ids.join(","));
+        let parameters = models::CommandParameters::new().big_cat_id_list(&big_cat_ids);
 
         self.send_oneshot_command(
             console_live_id,
@@ -666,8 +840,7 @@ impl SmartglassClient {
         console_live_id: String,
         instance_id: String,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("instanceId".to_owned(), instance_id);
+        let parameters = models::CommandParameters::new().instance_id(instance_id);
 
         self.send_oneshot_command(
             console_live_id,
@@ -746,12 +919,18 @@ impl SmartglassClient {
     pub async fn command_shell_send_text_message(
         &mut self,
         console_live_id: String,
+        recipient_xuid: String,
+        message: String,
     ) -> Result<models::CommandResponse> {
+        let parameters = models::CommandParameters::new()
+            .xuid(recipient_xuid)
+            .message(message);
+
         self.send_oneshot_command(
             console_live_id,
             "Shell".to_owned(),
             "SendTextMessage".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
@@ -759,12 +938,15 @@ impl SmartglassClient {
     pub async fn command_shell_show_guide_tab(
         &mut self,
         console_live_id: String,
+        tab: models::GuideTab,
     ) -> Result<models::CommandResponse> {
+        let parameters = models::CommandParameters::new().tab_name(tab);
+
         self.send_oneshot_command(
             console_live_id,
             "Shell".to_owned(),
             "ShowGuideTab".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
@@ -826,8 +1008,7 @@ impl SmartglassClient {
         console_live_id: String,
         key_type: models::InputKeyType,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("keyType".to_owned(), key_type.to_string());
+        let parameters = models::CommandParameters::new().key_type(key_type);
 
         self.send_oneshot_command(
             console_live_id,
@@ -843,8 +1024,7 @@ impl SmartglassClient {
         console_live_id: String,
         text_input: String,
     ) -> Result<models::CommandResponse> {
-        let mut parameters: Vec<HashMap<String, String>> = vec![HashMap::new()];
-        parameters[0].insert("replacementString".to_owned(), text_input);
+        let parameters = models::CommandParameters::new().replacement_string(text_input);
 
         self.send_oneshot_command(
             console_live_id,
@@ -868,16 +1048,234 @@ impl SmartglassClient {
         .await
     }
 
+    /// Tune the TV to `channel_id`
     pub async fn command_tv_watch_channel(
         &mut self,
         console_live_id: String,
+        channel_id: String,
     ) -> Result<models::CommandResponse> {
+        let parameters = models::CommandParameters::new().channel_id(channel_id);
+
         self.send_oneshot_command(
             console_live_id,
             "TV".to_owned(),
             "WatchChannel".to_owned(),
-            None,
+            Some(parameters),
         )
         .await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Request as received by `serve`
+    struct RecordedRequest {
+        request_line: String,
+        body: Option<serde_json::Value>,
+    }
+
+    /// Serve one HTTP request per entry of `responses`, in order
+    ///
+    /// Returns the base URI of the server and a handle yielding the
+    /// received requests.
+    fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<RecordedRequest>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let base_uri = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for response in responses {
+                let (stream, _) = listener.accept().expect("Failed to accept connection");
+                let mut reader = BufReader::new(stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.parse().unwrap();
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+
+                requests.push(RecordedRequest {
+                    request_line: request_line.trim_end().to_owned(),
+                    body: serde_json::from_slice(&body).ok(),
+                });
+            }
+            requests
+        });
+
+        (base_uri, handle)
+    }
+
+    fn mock_client(base_uri: &str) -> SmartglassClient {
+        let token = serde_json::from_str(
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2020-12-16T00:00:00.0000000Z","Token":"eyToken","DisplayClaims":{"xui":[{"uhs":"1234567890"}]}}"#,
+        )
+        .unwrap();
+        let mut client = SmartglassClient::new(token, None, None).unwrap();
+        client.set_base_uri(base_uri);
+        client
+    }
+
+    fn console_status(power_state: &str, focus_app_aumid: &str) -> String {
+        json!({
+            "powerState": power_state,
+            "consoleStreamingEnabled": true,
+            "digitalAssistantRemoteControlEnabled": false,
+            "remoteManagementEnabled": true,
+            "focusAppAumid": focus_app_aumid,
+            "isTvConfigured": false,
+            "loginState": null,
+            "playbackState": "Unknown",
+            "storageDevices": null,
+            "status": {"errorCode": "OK", "errorMessage": null}
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn watch_console_status() {
+        let home = console_status(
+            "On",
+            "Xbox.Dashboard_8wekyb3d8bbwe!Xbox.Dashboard.Application",
+        );
+        let game = console_status("On", "Microsoft.SeaofThieves_8wekyb3d8bbwe!SeaofThieves");
+        let (base_uri, handle) = serve(vec![home.clone(), home, game]);
+        let mut client = mock_client(&base_uri);
+
+        let mut poller =
+            client.watch_console_status("F4000000000000".to_owned(), Duration::from_millis(1));
+        let first = poller
+            .next_change(&mut client)
+            .await
+            .expect("Failed to get console status");
+        assert_eq!(first.power_state(), models::PowerState::On);
+
+        // Unchanged second status is skipped
+        let second = poller
+            .next_change(&mut client)
+            .await
+            .expect("Failed to get console status");
+        assert!(second.has_changed(&first));
+        assert_eq!(
+            second.focus_app_aumid(),
+            "Microsoft.SeaofThieves_8wekyb3d8bbwe!SeaofThieves"
+        );
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in requests {
+            assert_eq!(
+                request.request_line,
+                "GET /consoles/F4000000000000 HTTP/1.1"
+            );
+            assert!(request.body.is_none());
+        }
+    }
+
+    fn command_response() -> String {
+        json!({
+            "result": null,
+            "uiText": null,
+            "destination": {
+                "id": "F4000000000000",
+                "name": "Xbox",
+                "powerState": "On",
+                "remoteManagementEnabled": true,
+                "consoleStreamingEnabled": true,
+                "consoleType": "XboxSeriesX",
+                "wirelessWarning": null,
+                "outOfHomeWarning": null
+            },
+            "userInfo": null,
+            "opId": "a0b1c2d3-2f4e-4a6b-9c8d-7e6f5a4b3c2d",
+            "status": {"errorCode": "OK", "errorMessage": null}
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn command_audio_volume() {
+        let (base_uri, handle) = serve(vec![command_response(), command_response()]);
+        let mut client = mock_client(&base_uri);
+
+        let response = client
+            .command_audio_volume(
+                "F4000000000000".to_owned(),
+                models::VolumeDirection::Down,
+                None,
+            )
+            .await
+            .expect("Failed to send volume command");
+        assert!(response.is_success());
+        client
+            .command_audio_volume(
+                "F4000000000000".to_owned(),
+                models::VolumeDirection::Up,
+                Some(5),
+            )
+            .await
+            .expect("Failed to send volume command");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].request_line, "POST /commands HTTP/1.1");
+        let body = requests[0].body.as_ref().expect("No JSON body");
+        assert_eq!(body["destination"], "Xbox");
+        assert_eq!(body["commandType"], "Audio");
+        assert_eq!(body["command"], "Volume");
+        assert_eq!(body["linkedXboxId"], "F4000000000000");
+        assert_eq!(
+            body["parameters"],
+            json!([{"direction": "Down", "amount": "1"}])
+        );
+
+        let body = requests[1].body.as_ref().expect("No JSON body");
+        assert_eq!(
+            body["parameters"],
+            json!([{"direction": "Up", "amount": "5"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn command_shell_show_guide_tab() {
+        let (base_uri, handle) = serve(vec![command_response()]);
+        let mut client = mock_client(&base_uri);
+
+        client
+            .command_shell_show_guide_tab("F4000000000000".to_owned(), models::GuideTab::Guide)
+            .await
+            .expect("Failed to send guide tab command");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].request_line, "POST /commands HTTP/1.1");
+        let body = requests[0].body.as_ref().expect("No JSON body");
+        assert_eq!(body["commandType"], "Shell");
+        assert_eq!(body["command"], "ShowGuideTab");
+        assert_eq!(body["parameters"], json!([{"tabName": "Guide"}]));
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::clone::Clone;
 use std::collections::HashMap;
 use std::fmt;
@@ -13,7 +13,7 @@ pub enum ConsoleType {
     XboxSeriesX,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PowerState {
     Unknown,
     On,
@@ -22,7 +22,7 @@ pub enum PowerState {
     SystemUpdate,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackState {
     Unknown,
     Playing,
@@ -96,6 +96,170 @@ impl fmt::Display for MediaCommand {
     }
 }
 
+/// Tab opened by `ShowGuideTab`
+///
+/// `Guide` is the only tab name known to be accepted, `Other` passes
+/// any other name through unchanged.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum GuideTab {
+    Guide,
+    Other(String),
+}
+
+impl From<String> for GuideTab {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "Guide" => GuideTab::Guide,
+            _ => GuideTab::Other(name),
+        }
+    }
+}
+
+impl From<GuideTab> for String {
+    fn from(tab: GuideTab) -> Self {
+        tab.to_string()
+    }
+}
+
+impl fmt::Display for GuideTab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuideTab::Guide => write!(f, "Guide"),
+            GuideTab::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Query parameters of the `lists/devices` endpoint
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDevicesQuery {
+    pub query_current_device: bool,
+    pub include_storage_devices: bool,
+}
+
+impl Default for ListDevicesQuery {
+    fn default() -> Self {
+        Self {
+            query_current_device: false,
+            include_storage_devices: true,
+        }
+    }
+}
+
+/// Query parameters of per-device lists, e.g.
+/// `lists/storageDevices` and `lists/installedApps`
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceQuery {
+    pub device_id: String,
+}
+
+/// Parameters of a one-shot command
+///
+/// Takes care of key naming and value formatting, serializes
+/// into the list-of-maps shape expected by the command service.
+#[derive(Debug, Default, Clone)]
+pub struct CommandParameters(HashMap<String, String>);
+
+impl CommandParameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set(mut self, key: &str, value: String) -> Self {
+        self.0.insert(key.to_owned(), value);
+        self
+    }
+
+    pub fn direction(self, direction: VolumeDirection) -> Self {
+        self.set("direction", direction.to_string())
+    }
+
+    pub fn amount(self, amount: i32) -> Self {
+        self.set("amount", amount.to_string())
+    }
+
+    pub fn enabled(self, enabled: bool) -> Self {
+        self.set("enabled", if enabled { "true" } else { "false" }.to_owned())
+    }
+
+    pub fn one_store_product_id(self, one_store_product_id: String) -> Self {
+        self.set("oneStoreProductId", one_store_product_id)
+    }
+
+    pub fn big_cat_id_list(self, big_cat_ids: &[String]) -> Self {
+        self.set("bigCatIdList", big_cat_ids.join(","))
+    }
+
+    pub fn instance_id(self, instance_id: String) -> Self {
+        self.set("instanceId", instance_id)
+    }
+
+    pub fn key_type(self, key_type: InputKeyType) -> Self {
+        self.set("keyType", key_type.to_string())
+    }
+
+    pub fn replacement_string(self, text: String) -> Self {
+        self.set("replacementString", text)
+    }
+
+    pub fn tab_name(self, tab: GuideTab) -> Self {
+        self.set("tabName", tab.to_string())
+    }
+
+    pub fn view_name(self, view_name: String) -> Self {
+        self.set("viewName", view_name)
+    }
+
+    pub fn xuid(self, xuid: String) -> Self {
+        self.set("xuid", xuid)
+    }
+
+    pub fn message(self, message: String) -> Self {
+        self.set("message", message)
+    }
+
+    pub fn query_string(self, query: String) -> Self {
+        self.set("queryString", query)
+    }
+
+    pub fn channel_id(self, channel_id: String) -> Self {
+        self.set("channelId", channel_id)
+    }
+}
+
+/// One-shot command, e.g. as step of `SmartglassClient::command_sequence`
+#[derive(Debug, Clone)]
+pub struct OneShotCommand {
+    /// Command group, e.g. Power, Shell
+    pub command_type: String,
+    pub command: String,
+    pub parameters: Option<CommandParameters>,
+}
+
+impl OneShotCommand {
+    pub fn new(command_type: &str, command: &str) -> Self {
+        Self {
+            command_type: command_type.to_owned(),
+            command: command.to_owned(),
+            parameters: None,
+        }
+    }
+
+    pub fn parameters(mut self, parameters: CommandParameters) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+}
+
+impl From<CommandParameters> for Vec<HashMap<String, String>> {
+    fn from(params: CommandParameters) -> Self {
+        vec![params.0]
+    }
+}
+
 pub mod request {
     use super::{Deserialize, HashMap, Serialize};
 
@@ -168,6 +332,27 @@ pub struct SmartglassConsoleStatus {
     status: SmartglassApiStatus,
 }
 
+impl SmartglassConsoleStatus {
+    pub fn power_state(&self) -> PowerState {
+        self.power_state
+    }
+
+    pub fn focus_app_aumid(&self) -> &str {
+        &self.focus_app_aumid
+    }
+
+    pub fn playback_state(&self) -> PlaybackState {
+        self.playback_state
+    }
+
+    /// Whether power state, focused app or playback state differ
+    pub fn has_changed(&self, previous: &Self) -> bool {
+        self.power_state != previous.power_state
+            || self.focus_app_aumid != previous.focus_app_aumid
+            || self.playback_state != previous.playback_state
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct InstalledPackage {
@@ -197,6 +382,54 @@ pub struct InstalledPackagesList {
     agent_user_id: Option<String>,
 }
 
+impl InstalledPackage {
+    pub fn title_id(&self) -> i32 {
+        self.title_id
+    }
+
+    /// Install instance, as taken by `command_shell_uninstall_package`
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Product id, as taken by
+    /// `command_shell_activate_app_with_onestore_product_id`
+    pub fn one_store_product_id(&self) -> Option<&str> {
+        self.one_store_product_id.as_deref()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_game(&self) -> bool {
+        self.is_game
+    }
+}
+
+impl InstalledPackagesList {
+    pub fn packages(&self) -> &[InstalledPackage] {
+        &self.result
+    }
+
+    /// Installed packages flagged as games, skipping apps
+    pub fn games_only(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.result.iter().filter(|package| package.is_game)
+    }
+
+    pub fn by_title_id(&self, title_id: i32) -> Option<&InstalledPackage> {
+        self.result
+            .iter()
+            .find(|package| package.title_id == title_id)
+    }
+
+    /// Resolve a title id to its install instance id
+    pub fn instance_id_for(&self, title_id: i32) -> Option<&str> {
+        self.by_title_id(title_id)
+            .map(|package| package.instance_id.as_str())
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StorageDevicesList {
@@ -246,6 +479,155 @@ pub struct CommandResponse {
     ui_text: Option<String>,
     destination: CommandDestination,
     user_info: Option<String>,
+    /// Empty if the command was not accepted, which the service
+    /// reports as missing or `null`
+    #[serde(default, deserialize_with = "null_as_default")]
     op_id: String,
     status: SmartglassApiStatus,
 }
+
+/// Deserialize `null` as the default value
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl CommandResponse {
+    /// Operation id for `fetch_operation_status`, `None` if the
+    /// response carries none
+    pub fn operation_id(&self) -> Option<&str> {
+        if self.op_id.is_empty() {
+            None
+        } else {
+            Some(&self.op_id)
+        }
+    }
+
+    /// Id of the console the command was sent to, the device id
+    /// for `fetch_operation_status`
+    pub fn device_id(&self) -> &str {
+        &self.destination.id
+    }
+
+    /// Whether the command service accepted the command
+    pub fn is_success(&self) -> bool {
+        self.status.error_code == "OK"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn serialize(parameters: CommandParameters) -> serde_json::Value {
+        let parameters: Vec<HashMap<String, String>> = parameters.into();
+        serde_json::to_value(parameters).unwrap()
+    }
+
+    #[test]
+    fn serialize_command_parameters() {
+        let parameters = CommandParameters::new()
+            .key_type(InputKeyType::Guide)
+            .replacement_string("text".to_owned());
+        assert_eq!(
+            serialize(parameters),
+            json!([{"keyType": "Guide", "replacementString": "text"}])
+        );
+
+        let ids = ["9NBLGGH4R315".to_owned(), "9WZDNCRFJ3TJ".to_owned()];
+        assert_eq!(
+            serialize(CommandParameters::new().big_cat_id_list(&ids)),
+            json!([{"bigCatIdList": "9NBLGGH4R315,9WZDNCRFJ3TJ"}])
+        );
+        assert_eq!(serialize(CommandParameters::new()), json!([{}]));
+    }
+
+    #[test]
+    fn guide_tab() {
+        assert_eq!(
+            serialize(CommandParameters::new().tab_name(GuideTab::Guide)),
+            json!([{"tabName": "Guide"}])
+        );
+        assert_eq!(
+            serialize(CommandParameters::new().tab_name(GuideTab::Other("Party".to_owned()))),
+            json!([{"tabName": "Party"}])
+        );
+
+        assert_eq!(
+            serde_json::from_value::<GuideTab>(json!("Guide")).unwrap(),
+            GuideTab::Guide
+        );
+        assert_eq!(
+            serde_json::to_value(GuideTab::Other("Party".to_owned())).unwrap(),
+            json!("Party")
+        );
+    }
+
+    #[test]
+    fn serialize_bool_parameters() {
+        assert_eq!(
+            serialize(CommandParameters::new().enabled(true)),
+            json!([{"enabled": "true"}])
+        );
+        assert_eq!(
+            serialize(CommandParameters::new().enabled(false)),
+            json!([{"enabled": "false"}])
+        );
+    }
+
+    const COMMAND_RESPONSE: &str = r#"{
+        "result": null,
+        "uiText": null,
+        "destination": {
+            "id": "F4000EE1E5CC5D6A",
+            "name": "XboxOne",
+            "powerState": "On",
+            "remoteManagementEnabled": true,
+            "consoleStreamingEnabled": true,
+            "consoleType": "XboxOneS",
+            "wirelessWarning": null,
+            "outOfHomeWarning": null
+        },
+        "userInfo": null,
+        "opId": "a0b1c2d3-2f4e-4a6b-9c8d-7e6f5a4b3c2d",
+        "status": {
+            "errorCode": "OK",
+            "errorMessage": null
+        }
+    }"#;
+
+    #[test]
+    fn deserialize_command_response() {
+        let response: CommandResponse = serde_json::from_str(COMMAND_RESPONSE).unwrap();
+        assert!(response.is_success());
+        assert_eq!(
+            response.operation_id(),
+            Some("a0b1c2d3-2f4e-4a6b-9c8d-7e6f5a4b3c2d")
+        );
+        assert_eq!(response.device_id(), "F4000EE1E5CC5D6A");
+    }
+
+    #[test]
+    fn deserialize_command_response_without_op_id() {
+        let mut json: serde_json::Value = serde_json::from_str(COMMAND_RESPONSE).unwrap();
+        json["status"]["errorCode"] = json!("RemoteManagementDisabled");
+
+        for op_id in [None, Some(json!(null)), Some(json!(""))] {
+            match op_id {
+                Some(op_id) => json["opId"] = op_id,
+                None => {
+                    json.as_object_mut().unwrap().remove("opId");
+                }
+            }
+
+            let response: CommandResponse = serde_json::from_value(json.clone()).unwrap();
+            assert!(!response.is_success());
+            assert_eq!(response.operation_id(), None);
+            assert_eq!(response.device_id(), "F4000EE1E5CC5D6A");
+        }
+    }
+}
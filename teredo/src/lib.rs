@@ -1,9 +1,31 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+use core::convert::{TryFrom, TryInto};
+use core::fmt;
+use core::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "pnet")]
 use pnet::packet::ipv6;
-use std::convert::{TryFrom, TryInto};
-use std::net::{Ipv4Addr, Ipv6Addr};
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeredoError {
+    NotTeredoAddress,
+    NotTeredoPacket,
+}
+
+impl fmt::Display for TeredoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TeredoError::NotTeredoAddress => write!(f, "Not a teredo address"),
+            TeredoError::NotTeredoPacket => write!(f, "Not a teredo packet"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TeredoError {}
+
+type Error = TeredoError;
+type Result<T> = core::result::Result<T, Error>;
 
 pub trait Teredo {
     fn is_teredo(&self) -> bool;
@@ -21,12 +43,14 @@ impl Teredo for Ipv6Addr {
     }
 }
 
+#[cfg(feature = "pnet")]
 impl Teredo for ipv6::Ipv6 {
     fn is_teredo(&self) -> bool {
         self.version == 6 && self.source.is_teredo() && self.destination.is_teredo()
     }
 }
 
+#[cfg(feature = "pnet")]
 impl<'a> Teredo for ipv6::Ipv6Packet<'a> {
     fn is_teredo(&self) -> bool {
         self.get_version() == 6
@@ -71,7 +95,7 @@ impl TryFrom<[u8; 16]> for TeredoEndpoint {
 
     fn try_from(value: [u8; 16]) -> Result<Self> {
         if !value.is_teredo() {
-            Err("Not a teredo address")?
+            return Err(TeredoError::NotTeredoAddress);
         }
 
         Ok(TeredoEndpoint {
@@ -81,8 +105,7 @@ impl TryFrom<[u8; 16]> for TeredoEndpoint {
             udp_port: u16::from_be_bytes(value[10..12].try_into().unwrap()) ^ 0xFFFF,
             teredo_client_ipv4: (u32::from_be_bytes(value[12..16].try_into().unwrap())
                 ^ 0xFFFF_FFFF)
-                .try_into()
-                .unwrap(),
+                .into(),
         })
     }
 }
@@ -92,31 +115,53 @@ impl TryFrom<Ipv6Addr> for TeredoEndpoint {
 
     fn try_from(value: Ipv6Addr) -> Result<Self> {
         if !value.octets().is_teredo() {
-            Err("Not a teredo address")?
+            return Err(TeredoError::NotTeredoAddress);
         }
 
         value.octets().try_into()
     }
 }
 
+impl From<TeredoEndpoint> for [u8; 16] {
+    fn from(value: TeredoEndpoint) -> Self {
+        let mut octets = [0u8; 16];
+        octets[0..4].copy_from_slice(&value.prefix.to_be_bytes());
+        octets[4..8].copy_from_slice(&value.teredo_server_ipv4.octets());
+        octets[8..10].copy_from_slice(&value.flags.to_be_bytes());
+        octets[10..12].copy_from_slice(&(value.udp_port ^ 0xFFFF).to_be_bytes());
+        octets[12..16]
+            .copy_from_slice(&(u32::from(value.teredo_client_ipv4) ^ 0xFFFF_FFFF).to_be_bytes());
+        octets
+    }
+}
+
+impl From<TeredoEndpoint> for Ipv6Addr {
+    fn from(value: TeredoEndpoint) -> Self {
+        <[u8; 16]>::from(value).into()
+    }
+}
+
+#[cfg(feature = "pnet")]
 pub trait TeredoHeader {
     fn get_teredo_endpoints(&self) -> Result<(TeredoEndpoint, TeredoEndpoint)>;
 }
 
+#[cfg(feature = "pnet")]
 impl TeredoHeader for ipv6::Ipv6 {
     fn get_teredo_endpoints(&self) -> Result<(TeredoEndpoint, TeredoEndpoint)> {
         if !self.is_teredo() {
-            Err("Not a teredo packet")?
+            return Err(TeredoError::NotTeredoPacket);
         }
 
         Ok((self.source.try_into()?, self.destination.try_into()?))
     }
 }
 
+#[cfg(feature = "pnet")]
 impl<'a> TeredoHeader for ipv6::Ipv6Packet<'a> {
     fn get_teredo_endpoints(&self) -> Result<(TeredoEndpoint, TeredoEndpoint)> {
         if !self.is_teredo() {
-            Err("Not a teredo packet")?
+            return Err(TeredoError::NotTeredoPacket);
         }
 
         Ok((
@@ -126,10 +171,45 @@ impl<'a> TeredoHeader for ipv6::Ipv6Packet<'a> {
     }
 }
 
+/// Unwrap a Teredo-encapsulated IPv6 packet (e.g. an UDP payload).
+///
+/// Validates the IPv6 header, extracts the Teredo endpoints of
+/// source and destination and returns the inner payload.
+///
+/// Returns `None` if the data is not a (complete) Teredo packet.
+#[cfg(feature = "pnet")]
+pub fn unwrap_teredo(payload: &[u8]) -> Option<(TeredoEndpoint, TeredoEndpoint, &[u8])> {
+    let packet = ipv6::Ipv6Packet::new(payload)?;
+    let (source, destination) = packet.get_teredo_endpoints().ok()?;
+
+    let start = ipv6::Ipv6Packet::minimum_packet_size();
+    let end = start + packet.get_payload_length() as usize;
+
+    Some((source, destination, payload.get(start..end)?))
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Ipv4Addr, Ipv6Addr, Teredo, TeredoEndpoint, TryInto};
-    use std::str::FromStr;
+    #[cfg(feature = "pnet")]
+    use super::unwrap_teredo;
+    use super::{Ipv4Addr, Ipv6Addr, Teredo, TeredoEndpoint, TeredoError, TryInto};
+    use core::str::FromStr;
+
+    #[cfg(feature = "pnet")]
+    fn teredo_packet(payload: &[u8]) -> Vec<u8> {
+        let source = Ipv6Addr::from_str("2001:0:338c:24f4:43b:30e3:d2f3:c93d").unwrap();
+        let destination = Ipv6Addr::from_str("2001:0:338c:24f4:3c66:fbff:cdef:fefe").unwrap();
+
+        let mut packet = vec![0x60, 0x00, 0x00, 0x00];
+        packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        // Next header: UDP, Hop limit
+        packet.extend_from_slice(&[0x11, 0x40]);
+        packet.extend_from_slice(&source.octets());
+        packet.extend_from_slice(&destination.octets());
+        packet.extend_from_slice(payload);
+
+        packet
+    }
 
     #[test]
     fn is_teredo_address() {
@@ -156,4 +236,113 @@ mod test {
         );
         assert_eq!(ep_teredo.udp_port, 53020);
     }
+
+    #[test]
+    fn from_octets_not_teredo() {
+        let ipv6 = Ipv6Addr::from_str("2019:0:338c:24f4:43b:30e3:d2f3:c93d").unwrap();
+        let result: Result<TeredoEndpoint, _> = ipv6.octets().try_into();
+
+        assert_eq!(result, Err(TeredoError::NotTeredoAddress));
+    }
+
+    #[test]
+    fn into_ipv6_endpoint() {
+        let ipv6 = Ipv6Addr::from_str("2001:0:338c:24f4:43b:30e3:d2f3:c93d").unwrap();
+        let ep_teredo: TeredoEndpoint = ipv6.try_into().unwrap();
+
+        assert_eq!(Ipv6Addr::from(ep_teredo), ipv6);
+    }
+
+    #[test]
+    #[cfg(feature = "pnet")]
+    fn unwrap_teredo_packet() {
+        let packet = teredo_packet(&[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        let (source, destination, payload) =
+            unwrap_teredo(&packet).expect("Failed to unwrap teredo packet");
+
+        assert_eq!(
+            source.teredo_client_ipv4,
+            Ipv4Addr::from_str("45.12.54.194").unwrap()
+        );
+        assert_eq!(source.udp_port, 53020);
+        assert_eq!(
+            destination.teredo_client_ipv4,
+            Ipv4Addr::from_str("50.16.1.1").unwrap()
+        );
+        assert_eq!(destination.udp_port, 1024);
+        assert_eq!(payload, &[0xDE, 0xAD, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    #[cfg(feature = "pnet")]
+    fn unwrap_teredo_invalid() {
+        let packet = teredo_packet(&[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        // Truncated
+        assert!(unwrap_teredo(&packet[..packet.len() - 1]).is_none());
+        assert!(unwrap_teredo(&packet[..20]).is_none());
+
+        // Not teredo
+        let mut not_teredo = packet;
+        not_teredo[8] = 0x20;
+        not_teredo[9] = 0x19;
+        assert!(unwrap_teredo(&not_teredo).is_none());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{Ipv4Addr, Ipv6Addr, TeredoEndpoint, TeredoError, TryInto};
+    use proptest::prelude::*;
+
+    const TEREDO_PREFIX: u32 = 0x2001_0000;
+
+    fn endpoint(prefix: u32, server: u32, flags: u16, port: u16, client: u32) -> TeredoEndpoint {
+        TeredoEndpoint {
+            prefix,
+            teredo_server_ipv4: Ipv4Addr::from(server),
+            teredo_client_ipv4: Ipv4Addr::from(client),
+            flags,
+            udp_port: port,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn octets_roundtrip(server: u32, flags: u16, port: u16, client: u32) {
+            let ep = endpoint(TEREDO_PREFIX, server, flags, port, client);
+            let octets: [u8; 16] = ep.into();
+
+            // Port and client address are stored bit-inverted, big endian
+            prop_assert_eq!(&octets[4..8], &server.to_be_bytes());
+            prop_assert_eq!(&octets[10..12], &(!port).to_be_bytes());
+            prop_assert_eq!(&octets[12..16], &(!client).to_be_bytes());
+
+            let parsed: TeredoEndpoint = octets.try_into().unwrap();
+            prop_assert_eq!(parsed, ep);
+        }
+
+        #[test]
+        fn ipv6_roundtrip(server: u32, flags: u16, port: u16, client: u32) {
+            let ep = endpoint(TEREDO_PREFIX, server, flags, port, client);
+            let parsed: TeredoEndpoint = Ipv6Addr::from(ep).try_into().unwrap();
+
+            prop_assert_eq!(parsed, ep);
+        }
+
+        #[test]
+        fn non_teredo_prefix_rejected(
+            prefix in any::<u32>().prop_filter("teredo prefix", |p| *p != TEREDO_PREFIX),
+            server: u32,
+            flags: u16,
+            port: u16,
+            client: u32,
+        ) {
+            let octets: [u8; 16] = endpoint(prefix, server, flags, port, client).into();
+            let result: Result<TeredoEndpoint, _> = octets.try_into();
+
+            prop_assert_eq!(result, Err(TeredoError::NotTeredoAddress));
+        }
+    }
 }
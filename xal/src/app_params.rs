@@ -126,6 +126,55 @@ impl Default for XalClientParameters {
     }
 }
 
+/// Base URIs of the services involved in authentication
+///
+/// Defaults to the production environment, non-production (or sovereign
+/// cloud) environments announce a different Windows Live auth base URI,
+/// see `XalEnvironment::from_cloud_environment`.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct XalEnvironment {
+    pub name: String,
+    /// Windows Live OAuth2 endpoints (authorize / token)
+    pub auth_base_uri: String,
+    pub title_mgt_base_uri: String,
+    pub device_auth_base_uri: String,
+    pub sisu_base_uri: String,
+    pub xsts_base_uri: String,
+}
+
+impl XalEnvironment {
+    pub fn prod() -> Self {
+        Self {
+            name: "Prod".into(),
+            auth_base_uri: "https://login.live.com".into(),
+            title_mgt_base_uri: "https://title.mgt.xboxlive.com".into(),
+            device_auth_base_uri: "https://device.auth.xboxlive.com".into(),
+            sisu_base_uri: "https://sisu.xboxlive.com".into(),
+            xsts_base_uri: "https://xsts.auth.xboxlive.com".into(),
+        }
+    }
+
+    /// Environment as announced by a cloud environment entry of the
+    /// gamestreaming login response (Name / AuthBaseUri).
+    ///
+    /// Only the Windows Live auth base URI is overridden, if provided.
+    pub fn from_cloud_environment(name: &str, auth_base_uri: Option<&str>) -> Self {
+        let mut environment = Self::prod();
+        environment.name = name.into();
+        if let Some(uri) = auth_base_uri {
+            environment.auth_base_uri = uri.trim_end_matches('/').into();
+        }
+
+        environment
+    }
+}
+
+impl Default for XalEnvironment {
+    fn default() -> Self {
+        Self::prod()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,4 +198,20 @@ mod tests {
         assert_eq!(DeviceType::from_str("ios").unwrap(), DeviceType::IOS);
         assert!(DeviceType::from_str("androidx").is_err());
     }
+
+    #[test]
+    fn environment_from_cloud_environment() {
+        let environment =
+            XalEnvironment::from_cloud_environment("Test", Some("https://login.live-int.com/"));
+
+        assert_eq!(environment.name, "Test");
+        assert_eq!(environment.auth_base_uri, "https://login.live-int.com");
+        assert_eq!(
+            environment.xsts_base_uri,
+            XalEnvironment::prod().xsts_base_uri
+        );
+
+        let environment = XalEnvironment::from_cloud_environment("Prod", None);
+        assert_eq!(environment, XalEnvironment::prod());
+    }
 }
@@ -1,10 +1,12 @@
 use crate::app_params::XalAppParameters;
 
 use super::{
-    app_params::{DeviceType, XalClientParameters},
+    app_params::{DeviceType, XalClientParameters, XalEnvironment},
     models::request,
     models::response,
-    request_signer::{self, SigningReqwestBuilder},
+    models::{self, RelyingPartyScope, SisuSessionId, XalError},
+    request_signer::{self, Clock, SigningReqwestBuilder},
+    utils::CachedDeviceToken,
 };
 use base64;
 use cvlib;
@@ -15,8 +17,8 @@ use oauth2::{
     },
     reqwest::async_http_client,
     url, AccessToken, AuthType, AuthUrl, AuthorizationCode, Client as OAuthClient, ClientId,
-    EmptyExtraTokenFields, ExtraTokenFields, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl,
-    RefreshToken, Scope, StandardRevocableToken, TokenResponse, TokenType, TokenUrl,
+    CsrfToken, EmptyExtraTokenFields, ExtraTokenFields, PkceCodeChallenge, PkceCodeVerifier,
+    RedirectUrl, RefreshToken, Scope, StandardRevocableToken, TokenResponse, TokenType, TokenUrl,
 };
 use reqwest;
 use std::time::Duration;
@@ -93,39 +95,81 @@ pub struct XalAuthenticator {
     device_id: uuid::Uuid,
     app_params: XalAppParameters,
     client_params: XalClientParameters,
+    environment: XalEnvironment,
     ms_cv: cvlib::CorrelationVector,
     client: reqwest::Client,
     client2: SpecialClient,
     request_signer: request_signer::RequestSigner,
+    device_token: Option<response::XADResponse>,
 }
 
 impl Default for XalAuthenticator {
     fn default() -> Self {
-        let client_params = XalClientParameters::default();
-        let app_params = XalAppParameters::default();
+        // Default endpoints and redirect uri are constants known to be valid
+        Self::new(
+            XalAppParameters::default(),
+            XalClientParameters::default(),
+            XalEnvironment::default(),
+        )
+        .expect("Invalid default parameters")
+    }
+}
+
+impl XalAuthenticator {
+    pub fn new(
+        app_params: XalAppParameters,
+        client_params: XalClientParameters,
+        environment: XalEnvironment,
+    ) -> Result<Self> {
+        Self::with_client(
+            app_params,
+            client_params,
+            environment,
+            reqwest::Client::new(),
+        )
+    }
+
+    /// Create an authenticator sending its Xbox Live requests through `client`
+    ///
+    /// Together with an [`XalEnvironment`] pointing at a local server, this
+    /// allows running the device / SISU / XSTS flow against recorded responses.
+    ///
+    /// NOTE: The Windows Live OAuth2 token exchange (`exchange_code_for_token`,
+    /// `refresh_token`) is done by the oauth2 crate and does not use `client`.
+    ///
+    /// Fails if the auth base uri of `environment` or the redirect uri of
+    /// `app_params` don't form valid URLs.
+    pub fn with_client(
+        app_params: XalAppParameters,
+        client_params: XalClientParameters,
+        environment: XalEnvironment,
+        client: reqwest::Client,
+    ) -> Result<Self> {
         let client_id = ClientId::new(app_params.app_id.clone());
         let client_secret = None;
 
-        let auth_url = AuthUrl::new("https://login.live.com/oauth20_authorize.srf".into())
-            .expect("Invalid authorization endpoint URL");
-        let token_url = TokenUrl::new("https://login.live.com/oauth20_token.srf".into())
-            .expect("Invalid token endpoint URL");
-        let redirect_url =
-            RedirectUrl::new(app_params.redirect_uri.clone()).expect("Invalid redirect URL");
+        let auth_url = AuthUrl::new(format!(
+            "{}/oauth20_authorize.srf",
+            environment.auth_base_uri
+        ))?;
+        let token_url = TokenUrl::new(format!("{}/oauth20_token.srf", environment.auth_base_uri))?;
+        let redirect_url = RedirectUrl::new(app_params.redirect_uri.clone())?;
 
         let client2 = OAuthClient::new(client_id, client_secret, auth_url, Some(token_url))
             .set_auth_type(AuthType::RequestBody)
             .set_redirect_uri(redirect_url);
 
-        Self {
+        Ok(Self {
             device_id: uuid::Uuid::new_v4(),
             app_params,
             client_params,
+            environment,
             ms_cv: cvlib::CorrelationVector::new(),
-            client: reqwest::Client::new(),
+            client,
             client2,
             request_signer: request_signer::RequestSigner::default(),
-        }
+            device_token: None,
+        })
     }
 }
 
@@ -150,28 +194,105 @@ impl XalAuthenticator {
         self.client_params.clone()
     }
 
+    pub fn environment(&self) -> XalEnvironment {
+        self.environment.clone()
+    }
+
+    pub fn device_id(&self) -> uuid::Uuid {
+        self.device_id
+    }
+
+    /// Device token fetched by `get_device_token`, along with the device id
+    /// and proof key it is bound to.
+    ///
+    /// To be persisted (e.g. in `TokenStore`) and restored via
+    /// `set_cached_device_token`.
+    pub fn cached_device_token(&self) -> Option<CachedDeviceToken> {
+        let token = self.device_token.as_ref()?;
+        let proof_key = String::from_utf8(self.request_signer.keypair.to_pem_private_key()).ok()?;
+
+        Some(CachedDeviceToken {
+            device_id: self.device_id.hyphenated().to_string(),
+            proof_key: Some(proof_key),
+            token: token.clone(),
+        })
+    }
+
+    /// Restore a device token along with its device id and proof key, so
+    /// `get_device_token` skips the round trip while it is valid.
+    ///
+    /// Tokens stored without proof key can't be used for signed requests
+    /// and are ignored.
+    pub fn set_cached_device_token(&mut self, cached: CachedDeviceToken) -> Result<()> {
+        let proof_key = match &cached.proof_key {
+            Some(proof_key) => proof_key,
+            None => return Ok(()),
+        };
+        let keypair = josekit::jws::ES256.key_pair_from_pem(proof_key)?;
+        let device_id = uuid::Uuid::parse_str(&cached.device_id)?;
+
+        self.device_id = device_id;
+        self.request_signer.keypair = keypair;
+        self.device_token = Some(cached.token);
+        Ok(())
+    }
+
     pub fn get_redirect_uri(&self) -> Url {
         self.client2.redirect_url().unwrap().url().to_owned()
     }
 
+    /// Build the authorization URL for the classic (non-SISU) OAuth2 flow
+    ///
+    /// The returned URL has to be opened in a browser, the authorization
+    /// code received on the redirect uri can then be exchanged via
+    /// [`XalAuthenticator::exchange_code_for_token`].
+    pub fn authorization_url(&self, challenge: PkceCodeChallenge, state: String) -> Url {
+        let (url, _) = self
+            .client2
+            .authorize_url(|| CsrfToken::new(state))
+            .add_scope(RelyingPartyScope::XboxLiveUserAuth.into())
+            .set_pkce_challenge(challenge)
+            .url();
+
+        url
+    }
+
     fn next_cv(&mut self) -> String {
         self.ms_cv.increment();
         self.ms_cv.to_string()
     }
 
+    /// Fail with a `XalError` on non-2xx responses, instead of attempting
+    /// to deserialize their (often non-JSON) body
+    async fn error_for_status(&self, resp: reqwest::Response) -> Result<reqwest::Response> {
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = models::retry_after(resp.headers(), self.request_signer.clock.now());
+            Err(XalError::RateLimited { retry_after })?
+        }
+
+        let body = resp.text().await.unwrap_or_default();
+        Err(XalError::Http { status, body }.into())
+    }
+
     pub async fn exchange_code_for_token(
         &mut self,
         authorization_code: &str,
         code_verifier: PkceCodeVerifier,
     ) -> Result<SpecialTokenResponse> {
         let code = AuthorizationCode::new(authorization_code.into());
-        let token = self
+        let mut token = self
             .client2
             .exchange_code(code)
             .set_pkce_verifier(code_verifier)
-            .add_extra_param("scope", "service::user.auth.xboxlive.com::MBI_SSL")
+            .add_extra_param("scope", RelyingPartyScope::XboxLiveUserAuth.as_str())
             .request_async(async_http_client)
             .await?;
+        token.issued_at = Some(self.request_signer.clock.now().timestamp());
 
         Ok(token)
     }
@@ -183,19 +304,25 @@ impl XalAuthenticator {
         let form_body = request::WindowsLiveTokenRequest {
             client_id: &self.app_params.app_id.clone(),
             grant_type: "refresh_token",
-            scope:
-                "service::http://Passport.NET/purpose::PURPOSE_XBOX_CLOUD_CONSOLE_TRANSFER_TOKEN",
+            scope: RelyingPartyScope::XCloudConsoleTransferToken.as_str(),
             refresh_token: Some(refresh_token.secret()),
             code: None,
             code_verifier: None,
             redirect_uri: None,
         };
 
-        self.client
-            .post("https://login.live.com/oauth20_token.srf")
+        let resp = self
+            .client
+            .post(format!(
+                "{}/oauth20_token.srf",
+                self.environment.auth_base_uri
+            ))
             .header("MS-CV", self.next_cv())
             .form(&form_body)
             .send()
+            .await?;
+
+        self.error_for_status(resp)
             .await?
             .json::<response::XCloudTokenResponse>()
             .await
@@ -206,14 +333,13 @@ impl XalAuthenticator {
         &mut self,
         refresh_token: &RefreshToken,
     ) -> Result<SpecialTokenResponse> {
-        let token = self
+        let mut token = self
             .client2
             .exchange_refresh_token(refresh_token)
-            .add_scope(Scope::new(
-                "service::user.auth.xboxlive.com::MBI_SSL".into(),
-            ))
+            .add_scope(RelyingPartyScope::XboxLiveUserAuth.into())
             .request_async(async_http_client)
             .await?;
+        token.issued_at = Some(self.request_signer.clock.now().timestamp());
 
         Ok(token)
     }
@@ -223,10 +349,17 @@ impl XalAuthenticator {
     pub async fn get_endpoints(&self) -> Result<response::TitleEndpointsResponse> {
         let resp = self
             .client
-            .get("https://title.mgt.xboxlive.com/titles/default/endpoints")
+            .get(format!(
+                "{}/titles/default/endpoints",
+                self.environment.title_mgt_base_uri
+            ))
             .header("x-xbl-contract-version", "1")
             .query(&[("type", 1)])
             .send()
+            .await?;
+
+        let resp = self
+            .error_for_status(resp)
             .await?
             .json::<response::TitleEndpointsResponse>()
             .await?;
@@ -234,7 +367,14 @@ impl XalAuthenticator {
         Ok(resp)
     }
 
+    /// Authenticate the device, returns the cached token while it is valid
     pub async fn get_device_token(&mut self) -> Result<response::XADResponse> {
+        if let Some(token) = &self.device_token {
+            if token.token_data.is_valid() {
+                return Ok(token.clone());
+            }
+        }
+
         let client_uuid: String = match self.client_params.device_type {
             // {decf45e4-945d-4379-b708-d4ee92c12d99}
             DeviceType::ANDROID => [
@@ -266,28 +406,57 @@ impl XalAuthenticator {
             },
         };
 
-        self.client
-            .post("https://device.auth.xboxlive.com/device/authenticate")
+        let resp = self
+            .client
+            .post(format!(
+                "{}/device/authenticate",
+                self.environment.device_auth_base_uri
+            ))
             .headers(headers)
             .json(&json_body)
             .sign(&self.request_signer, None)?
             .send()
+            .await?;
+
+        let resp = self
+            .error_for_status(resp)
             .await?
             .json::<response::XADResponse>()
-            .await
-            .map_err(|e| e.into())
+            .await?;
+
+        self.device_token = Some(resp.clone());
+        Ok(resp)
+    }
+
+    /// Use `clock` for the timestamps of signed requests, e.g. to pin
+    /// the time in tests
+    pub fn set_clock(&mut self, clock: impl request_signer::Clock + 'static) {
+        self.request_signer.set_clock(clock);
+    }
+
+    /// Rotate the proof-of-possession key and re-authenticate the device
+    /// with it, keeping the device id.
+    ///
+    /// Tokens obtained with the previous key (SISU / XSTS) have to be
+    /// requested again.
+    pub async fn rotate_device_key(&mut self) -> Result<response::XADResponse> {
+        self.request_signer.rotate_key();
+        self.device_token = None;
+
+        self.get_device_token().await
     }
 
     /// Sisu authentication
     /// Returns tuple:
     /// 1. Part: Response that contains authorization URL
-    /// 2. Part: Session ID from response headers (X-SessionId)
+    /// 2. Part: Session ID from response headers (X-SessionId),
+    ///    needed for `do_sisu_authorization`
     pub async fn do_sisu_authentication(
         &mut self,
         device_token: &str,
         code_challenge: PkceCodeChallenge,
         state: &str,
-    ) -> Result<(response::SisuAuthenticationResponse, String)> {
+    ) -> Result<(response::SisuAuthenticationResponse, SisuSessionId)> {
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert("x-xbl-contract-version", "1".parse()?);
         headers.insert("MS-CV", self.next_cv().parse()?);
@@ -299,7 +468,7 @@ impl XalAuthenticator {
             device_token,
             sandbox: "RETAIL",
             token_type: "code",
-            offers: vec!["service::user.auth.xboxlive.com::MBI_SSL"],
+            offers: vec![RelyingPartyScope::XboxLiveUserAuth.as_str()],
             query: request::SisuQuery {
                 display: &self.client_params.query_display,
                 code_challenge: code_challenge.as_str(),
@@ -310,19 +479,15 @@ impl XalAuthenticator {
 
         let resp = self
             .client
-            .post("https://sisu.xboxlive.com/authenticate")
+            .post(format!("{}/authenticate", self.environment.sisu_base_uri))
             .headers(headers)
             .json(&json_body)
             .sign(&self.request_signer, None)?
             .send()
             .await?;
+        let resp = self.error_for_status(resp).await?;
 
-        let session_id = resp
-            .headers()
-            .get("X-SessionId")
-            .ok_or("Failed to fetch session id")?
-            .to_str()?
-            .to_owned();
+        let session_id = SisuSessionId::from_headers(resp.headers())?;
 
         let resp_json = resp.json::<response::SisuAuthenticationResponse>().await?;
 
@@ -331,7 +496,7 @@ impl XalAuthenticator {
 
     pub async fn do_sisu_authorization(
         &mut self,
-        sisu_session_id: &str,
+        sisu_session_id: &SisuSessionId,
         access_token: &str,
         device_token: &str,
     ) -> Result<response::SisuAuthorizationResponse> {
@@ -341,16 +506,20 @@ impl XalAuthenticator {
             device_token,
             sandbox: "RETAIL",
             site_name: "user.auth.xboxlive.com",
-            session_id: sisu_session_id,
+            session_id: sisu_session_id.as_str(),
             proof_key: self.request_signer.get_proof_key(),
         };
 
-        self.client
-            .post("https://sisu.xboxlive.com/authorize")
+        let resp = self
+            .client
+            .post(format!("{}/authorize", self.environment.sisu_base_uri))
             .header("MS-CV", self.next_cv())
             .json(&json_body)
             .sign(&self.request_signer, None)?
             .send()
+            .await?;
+
+        self.error_for_status(resp)
             .await?
             .json::<response::SisuAuthorizationResponse>()
             .await
@@ -379,23 +548,445 @@ impl XalAuthenticator {
             },
         };
 
-        self.client
-            .post("https://xsts.auth.xboxlive.com/xsts/authorize")
+        let resp = self
+            .client
+            .post(format!("{}/xsts/authorize", self.environment.xsts_base_uri))
             .headers(headers)
             .json(&json_body)
             .sign(&self.request_signer, None)?
             .send()
+            .await?;
+
+        let mut resp = self
+            .error_for_status(resp)
             .await?
             .json::<response::XSTSResponse>()
-            .await
-            .map_err(|e| e.into())
+            .await?;
+
+        resp.relying_party = Some(relying_party.to_owned());
+        Ok(resp)
+    }
+
+    /// XSTS token for the Smartglass API (`XBOX_LIVE_RELYING_PARTY`),
+    /// requested with the tokens of a SISU authorization
+    pub async fn get_smartglass_token(
+        &mut self,
+        sisu_tokens: &response::SisuAuthorizationResponse,
+    ) -> Result<response::XSTSResponse> {
+        self.do_xsts_authorization(
+            &sisu_tokens.device_token,
+            &sisu_tokens.title_token.token_data.token,
+            &sisu_tokens.user_token.token_data.token,
+            models::XBOX_LIVE_RELYING_PARTY,
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::{CachedDeviceToken, RelyingPartyScope, XalAuthenticator, XalEnvironment, XalError};
+    use crate::request_signer::FixedClock;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Request as received by `serve_once`
+    struct RecordedRequest {
+        request_line: String,
+        headers: HashMap<String, String>,
+        body: serde_json::Value,
+    }
+
+    /// Serve a single HTTP request with a recorded JSON response
+    ///
+    /// Returns an environment with all base URIs pointing to the server and
+    /// a handle yielding the received request.
+    fn serve_once(
+        extra_headers: &'static str,
+        response_body: &'static str,
+    ) -> (XalEnvironment, JoinHandle<RecordedRequest>) {
+        serve_once_with_status("200 OK", extra_headers, response_body)
+    }
+
+    fn serve_once_with_status(
+        status: &'static str,
+        extra_headers: &'static str,
+        response_body: &'static str,
+    ) -> (XalEnvironment, JoinHandle<RecordedRequest>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let base_uri = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("Failed to accept connection");
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((name, value)) => {
+                        headers.insert(name.to_lowercase(), value.to_owned());
+                    }
+                    None => break,
+                }
+            }
+
+            let content_length = headers
+                .get("content-length")
+                .map_or(0, |len| len.parse().unwrap());
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                extra_headers,
+                response_body.len(),
+                response_body
+            )
+            .unwrap();
+
+            RecordedRequest {
+                request_line: request_line.trim_end().to_owned(),
+                headers,
+                body: serde_json::from_slice(&body).unwrap(),
+            }
+        });
+
+        let environment = XalEnvironment {
+            name: "Mock".into(),
+            auth_base_uri: base_uri.clone(),
+            title_mgt_base_uri: base_uri.clone(),
+            device_auth_base_uri: base_uri.clone(),
+            sisu_base_uri: base_uri.clone(),
+            xsts_base_uri: base_uri,
+        };
+
+        (environment, handle)
+    }
+
+    fn mock_authenticator(environment: XalEnvironment) -> XalAuthenticator {
+        XalAuthenticator::with_client(
+            Default::default(),
+            Default::default(),
+            environment,
+            reqwest::Client::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_device_token() {
+        let (environment, handle) = serve_once(
+            "",
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2021-01-15T00:00:00.0000000Z","Token":"eyDeviceToken","DisplayClaims":{"xdi":{"did":"F7000000000000","dcs":"0"}}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment);
+
+        let resp = authenticator
+            .get_device_token()
+            .await
+            .expect("Failed to get device token");
+        assert_eq!(resp.token_data.token, "eyDeviceToken");
+        assert_eq!(resp.display_claims.xdi["did"], "F7000000000000");
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.request_line, "POST /device/authenticate HTTP/1.1");
+        assert_eq!(req.headers["x-xbl-contract-version"], "1");
+        assert!(req.headers.contains_key("ms-cv"));
+        assert!(req.headers.contains_key("signature"));
+        assert_eq!(req.body["RelyingParty"], "http://auth.xboxlive.com");
+        assert_eq!(req.body["Properties"]["AuthMethod"], "ProofOfPossession");
+    }
+
+    #[tokio::test]
+    async fn get_device_token_cached() {
+        let (environment, handle) = serve_once(
+            "",
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2999-01-15T00:00:00.0000000Z","Token":"eyDeviceToken","DisplayClaims":{"xdi":{"did":"F7000000000000","dcs":"0"}}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment.clone());
+        assert!(authenticator.cached_device_token().is_none());
+
+        authenticator
+            .get_device_token()
+            .await
+            .expect("Failed to get device token");
+        handle.join().unwrap();
+
+        // Server is gone, second call has to be served from cache
+        let resp = authenticator
+            .get_device_token()
+            .await
+            .expect("Device token not cached");
+        assert_eq!(resp.token_data.token, "eyDeviceToken");
+
+        let cached = authenticator.cached_device_token().unwrap();
+        assert_eq!(
+            cached.device_id,
+            authenticator.device_id().hyphenated().to_string()
+        );
+
+        // Restoring (e.g. on the next launch) brings back device id and
+        // proof key along with the token
+        let stored = serde_json::to_string(&cached).unwrap();
+        let mut other = mock_authenticator(environment.clone());
+        other
+            .set_cached_device_token(serde_json::from_str(&stored).unwrap())
+            .expect("Failed to restore device token");
+        assert_eq!(other.device_id(), authenticator.device_id());
+        assert_eq!(
+            other.request_signer.get_proof_key(),
+            authenticator.request_signer.get_proof_key()
+        );
+        let resp = other
+            .get_device_token()
+            .await
+            .expect("Restored device token not used");
+        assert_eq!(resp.token_data.token, "eyDeviceToken");
+
+        // Tokens stored without proof key are ignored
+        let mut legacy = mock_authenticator(environment);
+        let device_id = legacy.device_id();
+        legacy
+            .set_cached_device_token(CachedDeviceToken {
+                proof_key: None,
+                ..cached
+            })
+            .unwrap();
+        assert!(legacy.cached_device_token().is_none());
+        assert_eq!(legacy.device_id(), device_id);
+    }
+
+    #[tokio::test]
+    async fn rotate_device_key() {
+        let (environment, handle) = serve_once(
+            "",
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2999-01-15T00:00:00.0000000Z","Token":"eyRotatedToken","DisplayClaims":{"xdi":{"did":"F7000000000000","dcs":"0"}}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment);
+        let old_proof_key = authenticator.request_signer.get_proof_key();
+
+        // Valid cached token must not prevent re-authentication
+        authenticator
+            .set_cached_device_token(CachedDeviceToken {
+                device_id: authenticator.device_id().hyphenated().to_string(),
+                proof_key: Some(
+                    String::from_utf8(authenticator.request_signer.keypair.to_pem_private_key())
+                        .unwrap(),
+                ),
+                token: serde_json::from_str(
+                    r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2999-01-15T00:00:00.0000000Z","Token":"eyOldToken","DisplayClaims":{"xdi":{}}}"#,
+                )
+                .unwrap(),
+            })
+            .unwrap();
+        let device_id = authenticator.device_id();
+
+        let resp = authenticator
+            .rotate_device_key()
+            .await
+            .expect("Failed to rotate device key");
+        assert_eq!(resp.token_data.token, "eyRotatedToken");
+        assert_eq!(authenticator.device_id(), device_id);
+
+        let new_proof_key = authenticator.request_signer.get_proof_key();
+        assert_ne!(new_proof_key, old_proof_key);
+
+        let req = handle.join().unwrap();
+        assert_eq!(
+            req.body["Properties"]["ProofKey"]["x"],
+            new_proof_key.parameter("x").unwrap().clone()
+        );
+    }
+
+    #[tokio::test]
+    async fn do_sisu_authentication() {
+        let (environment, handle) = serve_once(
+            "X-SessionId: a1b2c3d4e5f6\r\n",
+            r#"{"MsaOauthRedirect":"https://login.live.com/oauth20_authorize.srf?foo=bar","MsaRequestParameters":{}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment);
+        let (challenge, _) = XalAuthenticator::get_code_challenge();
+
+        let (resp, session_id) = authenticator
+            .do_sisu_authentication("eyDeviceToken", challenge, "somestate")
+            .await
+            .expect("Failed to do sisu authentication");
+        assert_eq!(
+            resp.msa_oauth_redirect,
+            "https://login.live.com/oauth20_authorize.srf?foo=bar"
+        );
+        assert_eq!(session_id.as_str(), "a1b2c3d4e5f6");
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.request_line, "POST /authenticate HTTP/1.1");
+        assert!(req.headers.contains_key("signature"));
+        assert_eq!(req.body["DeviceToken"], "eyDeviceToken");
+        assert_eq!(req.body["Query"]["state"], "somestate");
+    }
+
+    #[tokio::test]
+    async fn do_xsts_authorization() {
+        let (environment, handle) = serve_once(
+            "",
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2020-12-16T00:00:00.0000000Z","Token":"eyXstsToken","DisplayClaims":{"xui":[{"uhs":"1234567890","gtg":"Gamertag"}]}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment);
+
+        let resp = authenticator
+            .do_xsts_authorization(
+                "eyDeviceToken",
+                "eyTitleToken",
+                "eyUserToken",
+                "http://gssv.xboxlive.com/",
+            )
+            .await
+            .expect("Failed to do xsts authorization");
+        assert_eq!(resp.token_data.token, "eyXstsToken");
+        assert_eq!(resp.relying_party(), Some("http://gssv.xboxlive.com/"));
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.request_line, "POST /xsts/authorize HTTP/1.1");
+        assert!(req.headers.contains_key("signature"));
+        assert_eq!(req.body["RelyingParty"], "http://gssv.xboxlive.com/");
+        assert_eq!(req.body["Properties"]["UserTokens"][0], "eyUserToken");
+    }
+
+    #[tokio::test]
+    async fn get_smartglass_token() {
+        let (environment, handle) = serve_once(
+            "",
+            r#"{"IssueInstant":"2020-12-15T00:00:00.0000000Z","NotAfter":"2020-12-16T00:00:00.0000000Z","Token":"eySmartglassToken","DisplayClaims":{"xui":[{"uhs":"1234567890"}]}}"#,
+        );
+        let mut authenticator = mock_authenticator(environment);
+        let sisu_tokens = serde_json::from_str(
+            r#"{
+                "DeviceToken": "eyDeviceToken",
+                "TitleToken": {"IssueInstant":"","NotAfter":"","Token":"eyTitleToken","DisplayClaims":{"xti":{}}},
+                "UserToken": {"IssueInstant":"","NotAfter":"","Token":"eyUserToken","DisplayClaims":{"xui":[]}},
+                "AuthorizationToken": {"IssueInstant":"","NotAfter":"","Token":"eyAuthToken","DisplayClaims":{"xui":[]}},
+                "WebPage": "",
+                "Sandbox": "RETAIL"
+            }"#,
+        )
+        .unwrap();
+
+        let resp = authenticator
+            .get_smartglass_token(&sisu_tokens)
+            .await
+            .expect("Failed to get smartglass token");
+        assert_eq!(resp.token_data.token, "eySmartglassToken");
+        assert_eq!(resp.relying_party(), Some("http://xboxlive.com"));
+
+        let req = handle.join().unwrap();
+        assert_eq!(req.body["RelyingParty"], "http://xboxlive.com");
+        assert_eq!(req.body["Properties"]["DeviceToken"], "eyDeviceToken");
+        assert_eq!(req.body["Properties"]["UserTokens"][0], "eyUserToken");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let (environment, handle) = serve_once_with_status(
+            "429 Too Many Requests",
+            "Retry-After: Tue, 15 Dec 2020 00:01:00 GMT\r\n",
+            "Too many requests",
+        );
+        let mut authenticator = mock_authenticator(environment);
+        authenticator.set_clock(FixedClock(
+            chrono::DateTime::parse_from_rfc3339("2020-12-15T00:00:00Z")
+                .unwrap()
+                .into(),
+        ));
+
+        let err = authenticator
+            .do_xsts_authorization("eyDeviceToken", "eyTitleToken", "eyUserToken", "rp")
+            .await
+            .expect_err("Rate limited request succeeded");
+        assert_eq!(
+            err.downcast_ref::<XalError>(),
+            Some(&XalError::RateLimited {
+                retry_after: Some(std::time::Duration::from_secs(60))
+            })
+        );
+        handle.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn http_error() {
+        let (environment, handle) =
+            serve_once_with_status("403 Forbidden", "", r#"{"XErr":2148916233}"#);
+        let mut authenticator = mock_authenticator(environment);
+
+        let err = authenticator
+            .get_device_token()
+            .await
+            .expect_err("Forbidden request succeeded");
+        match err.downcast_ref::<XalError>() {
+            Some(XalError::Http { status, body }) => {
+                assert_eq!(status.as_u16(), 403);
+                assert_eq!(body, r#"{"XErr":2148916233}"#);
+            }
+            _ => panic!("Unexpected error: {}", err),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn authorization_url() {
+        let authenticator = XalAuthenticator::default();
+        let (challenge, _) = XalAuthenticator::get_code_challenge();
+        let challenge_str = challenge.as_str().to_owned();
+
+        let url = authenticator.authorization_url(challenge, "somestate".into());
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.host_str(), Some("login.live.com"));
+        assert_eq!(url.path(), "/oauth20_authorize.srf");
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], authenticator.app_params().app_id);
+        assert_eq!(
+            query["redirect_uri"],
+            authenticator.app_params().redirect_uri
+        );
+        assert_eq!(query["state"], "somestate");
+        assert_eq!(query["scope"], RelyingPartyScope::XboxLiveUserAuth.as_str());
+        assert_eq!(query["code_challenge"], challenge_str);
+        assert_eq!(query["code_challenge_method"], "S256");
+    }
+
+    #[test]
+    fn authorization_url_environment() {
+        let authenticator = XalAuthenticator::new(
+            Default::default(),
+            Default::default(),
+            XalEnvironment::from_cloud_environment("Test", Some("https://login.live-int.com")),
+        )
+        .unwrap();
+        let (challenge, _) = XalAuthenticator::get_code_challenge();
+
+        let url = authenticator.authorization_url(challenge, "somestate".into());
+
+        assert_eq!(url.host_str(), Some("login.live-int.com"));
+        assert_eq!(url.path(), "/oauth20_authorize.srf");
+        assert_eq!(authenticator.environment().name, "Test");
+    }
+
     #[test]
-    fn test() {
-        assert_eq!(true, true);
+    fn invalid_environment() {
+        let result = XalAuthenticator::new(
+            Default::default(),
+            Default::default(),
+            XalEnvironment::from_cloud_environment("Test", Some("not a url")),
+        );
+
+        assert!(result.is_err());
     }
 }
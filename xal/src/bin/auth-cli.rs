@@ -2,7 +2,8 @@ use chrono::Utc;
 use std::io;
 use url::Url;
 use xal::authenticator::XalAuthenticator;
-use xal::oauth2::PkceCodeVerifier;
+use xal::models::GSSV_RELYING_PARTY;
+use xal::oauth2::{PkceCodeVerifier, RefreshToken};
 use xal::utils::TokenStore;
 
 const TOKENS_FILEPATH: &str = "tokens.json";
@@ -12,16 +13,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut xal = XalAuthenticator::default();
 
     if let Ok(mut ts) = TokenStore::load(TOKENS_FILEPATH) {
-        let refreshed_xcoud = xal
-            .exchange_refresh_token_for_xcloud_transfer_token(&ts.xcloud_transfer_token.into())
-            .await?;
-        println!("{:?}", refreshed_xcoud);
+        // Reused by the full authentication, should the refresh fail
+        if let Some(device_token) = ts.device_token.clone() {
+            xal.set_cached_device_token(device_token)?;
+        }
 
-        ts.xcloud_transfer_token = refreshed_xcoud;
-        ts.updated = Utc::now();
-        ts.save(TOKENS_FILEPATH)?;
+        match xal
+            .exchange_refresh_token_for_xcloud_transfer_token(&RefreshToken::new(
+                ts.xcloud_transfer_token.refresh_token.clone(),
+            ))
+            .await
+        {
+            Ok(refreshed_xcoud) => {
+                println!("{:?}", refreshed_xcoud);
 
-        return Ok(());
+                ts.xcloud_transfer_token = refreshed_xcoud;
+                ts.updated = Utc::now();
+                ts.save(TOKENS_FILEPATH)?;
+
+                return Ok(());
+            }
+            Err(err) => println!("Failed to refresh XCloud transfer token: {}", err),
+        }
     }
 
     let (code_challenge, code_verifier) = XalAuthenticator::get_code_challenge();
@@ -46,6 +59,7 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
 
     let mut redirect_uri = String::new();
     let _ = io::stdin().read_line(&mut redirect_uri)?;
+    let redirect_uri = redirect_uri.trim();
 
     // Check if redirect URI has expected scheme
     println!("Checking redirect URI...");
@@ -59,7 +73,7 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
     }
 
     // Parse redirect URI
-    let parsed_url = Url::parse(&redirect_uri)?;
+    let parsed_url = Url::parse(redirect_uri)?;
     // Extract query parameters {code, state}
     let mut code_query: Option<String> = None;
     let mut state_query: Option<String> = None;
@@ -73,14 +87,16 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
     }
 
     println!("Verifying state...");
-    if let Some(returned_state) = &state_query {
-        let valid_state = &state == returned_state;
-        println!(
-            "State valid: {} ({} vs. {})",
-            valid_state, state, returned_state
-        );
-    } else {
-        println!("WARN: No state query returned!");
+    match &state_query {
+        Some(returned_state) if returned_state == &state => println!("State valid"),
+        Some(returned_state) => {
+            return Err(format!(
+                "State mismatch, expected: {}, got: {}",
+                state, returned_state
+            )
+            .into());
+        }
+        None => println!("WARN: No state query returned!"),
     }
 
     if let Some(authorization_code) = code_query {
@@ -90,8 +106,7 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
         println!("Getting WL tokens...");
         let wl_token = xal
             .exchange_code_for_token(&authorization_code, local_code_verifier)
-            .await
-            .expect("Failed exchanging code for token");
+            .await?;
         let wl_token_clone = wl_token.clone();
         println!("WL={:?}", wl_token);
 
@@ -112,18 +127,23 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
                 &auth_response.device_token,
                 &auth_response.title_token.token_data.token,
                 &auth_response.user_token.token_data.token,
-                "http://gssv.xboxlive.com/",
+                GSSV_RELYING_PARTY,
             )
             .await?;
         println!("GSSV={:?}", gssv_token);
 
+        println!("Getting Smartglass token...");
+        // Fetch Smartglass (Xbox Live) token
+        let smartglass_token = xal.get_smartglass_token(&auth_response).await?;
+        println!("Smartglass={:?}", smartglass_token);
+
         println!("Getting XCloud transfer token...");
         // Fetch XCloud transfer token
         let transfer_token = xal
             .exchange_refresh_token_for_xcloud_transfer_token(
                 &wl_token
                     .refresh_token
-                    .expect("Failed to unwrap refresh token"),
+                    .ok_or("WL token response contains no refresh token")?,
             )
             .await?;
         println!("Transfer token={:?}", transfer_token);
@@ -134,12 +154,15 @@ When finished, paste the Redirect URL and hit [ENTER]"#,
             wl_token: wl_token_clone,
             sisu_tokens: auth_response,
             gssv_token,
+            smartglass_token: Some(smartglass_token),
             xcloud_transfer_token: transfer_token,
+            device_token: xal.cached_device_token(),
             updated: Utc::now(),
         };
-        ts.save(TOKENS_FILEPATH)?
+        ts.save(TOKENS_FILEPATH)?;
+        println!("Tokens saved to {}", TOKENS_FILEPATH);
     } else {
-        println!("No authorization code fetched :(");
+        return Err("Redirect URL contains no authorization code".into());
     }
 
     Ok(())
@@ -12,8 +12,12 @@ use wry::{
     },
     webview::{Url, WebViewBuilder},
 };
-use xal::oauth2::PkceCodeVerifier;
-use xal::{authenticator::XalAuthenticator, utils::TokenStore};
+use xal::oauth2::{PkceCodeVerifier, RefreshToken};
+use xal::{
+    authenticator::XalAuthenticator,
+    models::{SisuSessionId, GSSV_RELYING_PARTY},
+    utils::TokenStore,
+};
 
 const TOKENS_FILEPATH: &str = "tokens.json";
 
@@ -21,7 +25,7 @@ async fn continue_auth(
     xal: &mut XalAuthenticator,
     code_verifier: &PkceCodeVerifier,
     authorization_code: &str,
-    sisu_session_id: &str,
+    sisu_session_id: &SisuSessionId,
     device_token: &str,
 ) -> std::result::Result<(), Box<dyn std::error::Error>> {
     println!("Authorization Code: {}", &authorization_code);
@@ -48,11 +52,15 @@ async fn continue_auth(
             &auth_response.device_token,
             &auth_response.title_token.token_data.token,
             &auth_response.user_token.token_data.token,
-            "http://gssv.xboxlive.com/",
+            GSSV_RELYING_PARTY,
         )
         .await?;
     println!("GSSV={:?}", gssv_token);
 
+    // Fetch Smartglass (Xbox Live) token
+    let smartglass_token = xal.get_smartglass_token(&auth_response).await?;
+    println!("Smartglass={:?}", smartglass_token);
+
     // Fetch XCloud transfer token
     let transfer_token = xal
         .exchange_refresh_token_for_xcloud_transfer_token(
@@ -69,7 +77,9 @@ async fn continue_auth(
         wl_token: wl_token_clone,
         sisu_tokens: auth_response,
         gssv_token,
+        smartglass_token: Some(smartglass_token),
         xcloud_transfer_token: transfer_token,
+        device_token: xal.cached_device_token(),
         updated: Utc::now(),
     };
     ts.save(TOKENS_FILEPATH)
@@ -83,18 +93,26 @@ fn main() -> wry::Result<()> {
     let mut xal = XalAuthenticator::default();
 
     if let Ok(mut ts) = TokenStore::load(TOKENS_FILEPATH) {
-        let refreshed_xcoud = async_runtime::block_on(
-            xal.exchange_refresh_token_for_xcloud_transfer_token(&ts.xcloud_transfer_token.into()),
-        )
-        .expect("Failed to exchange refresh token for fresh XCloud transfer token");
-
-        println!("{:?}", refreshed_xcoud);
-        ts.xcloud_transfer_token = refreshed_xcoud;
-        ts.updated = Utc::now();
-        ts.save(TOKENS_FILEPATH)
-            .expect("Failed to save refreshed XCloud token");
+        // Reused by the full authentication, should the refresh fail
+        if let Some(device_token) = ts.device_token.clone() {
+            xal.set_cached_device_token(device_token)
+                .expect("Failed to restore device token");
+        }
 
-        return Ok(());
+        match async_runtime::block_on(xal.exchange_refresh_token_for_xcloud_transfer_token(
+            &RefreshToken::new(ts.xcloud_transfer_token.refresh_token.clone()),
+        )) {
+            Ok(refreshed_xcoud) => {
+                println!("{:?}", refreshed_xcoud);
+                ts.xcloud_transfer_token = refreshed_xcoud;
+                ts.updated = Utc::now();
+                ts.save(TOKENS_FILEPATH)
+                    .expect("Failed to save refreshed XCloud token");
+
+                return Ok(());
+            }
+            Err(err) => println!("Failed to refresh XCloud transfer token: {}", err),
+        }
     }
 
     let (code_challenge, code_verifier) = XalAuthenticator::get_code_challenge();
@@ -19,27 +19,23 @@ pub trait FileTime<Utc> {
 impl FileTime<Utc> for DateTime<Utc> {
     /// Return FILETIME epoch as DateTime<Utc>
     fn filetime_epoch() -> Self {
-        let rel_to_linux_epoch = EPOCH_AS_FILETIME;
-
-        let secs: i64 = rel_to_linux_epoch / HUNDREDS_OF_NANOSECONDS;
-        let nsecs: i64 = rel_to_linux_epoch % HUNDREDS_OF_NANOSECONDS;
-
-        Utc.timestamp(secs, nsecs.try_into().unwrap())
+        Self::from_filetime(0)
     }
 
     /// Example
     /// ```
     /// use chrono::{DateTime, TimeZone, Utc};
     /// use xal::filetime::FileTime;
-    /// let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 1000);
+    /// let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 100_000);
     /// let ft = DateTime::<Utc>::from_filetime(128930364000001000);
     /// assert_eq!(dt, ft);
     /// ```
     fn from_filetime(filetime: i64) -> DateTime<Utc> {
         let rel_to_linux_epoch = filetime - EPOCH_AS_FILETIME;
 
-        let secs: i64 = rel_to_linux_epoch / HUNDREDS_OF_NANOSECONDS;
-        let nsecs: i64 = rel_to_linux_epoch % HUNDREDS_OF_NANOSECONDS;
+        // FILETIME counts in 100ns intervals
+        let secs: i64 = rel_to_linux_epoch.div_euclid(HUNDREDS_OF_NANOSECONDS);
+        let nsecs: i64 = rel_to_linux_epoch.rem_euclid(HUNDREDS_OF_NANOSECONDS) * 100;
 
         Utc.timestamp(secs, nsecs.try_into().unwrap())
     }
@@ -48,12 +44,12 @@ impl FileTime<Utc> for DateTime<Utc> {
     /// ```
     /// use chrono::{TimeZone, Utc};
     /// use xal::filetime::FileTime;
-    /// let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 1000);
+    /// let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 100_000);
     /// assert_eq!(dt.to_filetime(), 128930364000001000);
     /// ```
     fn to_filetime(&self) -> i64 {
         let nsecs = EPOCH_AS_FILETIME + (self.timestamp() * HUNDREDS_OF_NANOSECONDS);
-        let remainder: i64 = self.timestamp_subsec_nanos().try_into().unwrap();
+        let remainder: i64 = (self.timestamp_subsec_nanos() / 100).into();
 
         nsecs + remainder
     }
@@ -66,14 +62,46 @@ mod test {
 
     #[test]
     fn to_filetime() {
-        let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 1000);
+        let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 100_000);
         assert_eq!(dt.to_filetime(), 128930364000001000);
     }
 
     #[test]
     fn from_filetime() {
-        let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 1000);
+        let dt = Utc.ymd(2009, 7, 25).and_hms_nano(23, 0, 0, 100_000);
         let ft = DateTime::<Utc>::from_filetime(128930364000001000);
         assert_eq!(dt, ft);
     }
+
+    #[test]
+    fn filetime_epoch() {
+        let dt = Utc.ymd(1601, 1, 1).and_hms(0, 0, 0);
+        assert_eq!(DateTime::<Utc>::filetime_epoch(), dt);
+        assert_eq!(dt.to_filetime(), 0);
+    }
+
+    #[test]
+    fn unix_epoch() {
+        let dt = Utc.timestamp(0, 0);
+        assert_eq!(dt.to_filetime(), 116444736000000000);
+        assert_eq!(DateTime::<Utc>::from_filetime(116444736000000000), dt);
+    }
+
+    #[test]
+    fn signing_timestamp() {
+        let dt = Utc.timestamp(1586999965, 0);
+        assert_eq!(dt.to_filetime(), 0x01d6138d10f7cc80);
+        assert_eq!(DateTime::<Utc>::from_filetime(0x01d6138d10f7cc80), dt);
+    }
+
+    #[test]
+    fn sub_second_precision() {
+        // FILETIME resolution is 100ns, anything below gets truncated
+        let dt = Utc.timestamp(1586999965, 123_456_789);
+        assert_eq!(dt.to_filetime(), 0x01d6138d10f7cc80 + 1_234_567);
+        assert_eq!(
+            DateTime::<Utc>::from_filetime(dt.to_filetime()),
+            Utc.timestamp(1586999965, 123_456_700)
+        );
+    }
 }
@@ -1,5 +1,11 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 pub enum SigningAlgorithm {
@@ -8,7 +14,7 @@ pub enum SigningAlgorithm {
     ES521,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub struct SigningPolicy {
     pub version: i32,
@@ -26,6 +32,161 @@ impl Default for SigningPolicy {
     }
 }
 
+/// XSTS relying party of the Xbox Live APIs, e.g. Smartglass
+/// (skill platform RemoteManagement)
+pub const XBOX_LIVE_RELYING_PARTY: &str = "http://xboxlive.com";
+/// XSTS relying party of game streaming (xCloud / xHome)
+pub const GSSV_RELYING_PARTY: &str = "http://gssv.xboxlive.com/";
+
+/// Scopes requested from the Windows Live OAuth2 endpoints
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RelyingPartyScope {
+    /// Xbox Live user authentication (`MBI_SSL`), used for SISU and classic OAuth2
+    XboxLiveUserAuth,
+    /// Transfer token for xCloud console streaming
+    XCloudConsoleTransferToken,
+}
+
+impl RelyingPartyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::XboxLiveUserAuth => "service::user.auth.xboxlive.com::MBI_SSL",
+            Self::XCloudConsoleTransferToken => {
+                "service::http://Passport.NET/purpose::PURPOSE_XBOX_CLOUD_CONSOLE_TRANSFER_TOKEN"
+            }
+        }
+    }
+}
+
+impl fmt::Display for RelyingPartyScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<RelyingPartyScope> for oauth2::Scope {
+    fn from(scope: RelyingPartyScope) -> Self {
+        Self::new(scope.as_str().to_owned())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SisuSessionIdError {
+    /// Response carries no session id header
+    Missing,
+    /// Session id is empty or contains non-printable characters
+    Invalid,
+}
+
+impl fmt::Display for SisuSessionIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "Missing {} header", SisuSessionId::HEADER_NAME),
+            Self::Invalid => write!(f, "Invalid SISU session id"),
+        }
+    }
+}
+
+impl std::error::Error for SisuSessionIdError {}
+
+/// Required MSA request parameter is absent, holds its key
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MissingMsaParameter(pub &'static str);
+
+impl fmt::Display for MissingMsaParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Missing MSA request parameter {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingMsaParameter {}
+
+/// Session id of a SISU authentication
+///
+/// Returned by `do_sisu_authentication`, has to be passed
+/// to the subsequent `do_sisu_authorization`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SisuSessionId(String);
+
+impl SisuSessionId {
+    pub const HEADER_NAME: &'static str = "X-SessionId";
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Extract the session id from the response headers of a SISU authentication
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, SisuSessionIdError> {
+        headers
+            .get(Self::HEADER_NAME)
+            .ok_or(SisuSessionIdError::Missing)?
+            .to_str()
+            .map_err(|_| SisuSessionIdError::Invalid)?
+            .parse()
+    }
+}
+
+impl FromStr for SisuSessionId {
+    type Err = SisuSessionIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_graphic()) {
+            return Err(SisuSessionIdError::Invalid);
+        }
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl fmt::Display for SisuSessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Non-success response of an Xbox Live auth endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XalError {
+    /// HTTP 429, the request should be retried after `retry_after`,
+    /// if the server sent a (valid) `Retry-After` header
+    RateLimited { retry_after: Option<Duration> },
+    /// Any other non-2xx status
+    Http { status: StatusCode, body: String },
+}
+
+impl fmt::Display for XalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(f, "Rate limited, retry after {}s", retry_after.as_secs()),
+            Self::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            Self::Http { status, body } => write!(f, "HTTP error {}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for XalError {}
+
+/// Parse the `Retry-After` header, either delay-seconds or an HTTP date
+///
+/// Dates in the past yield a zero delay.
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.with_timezone(&Utc)
+            .signed_duration_since(now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
 pub mod request {
     use josekit::jwk::Jwk;
 
@@ -116,9 +277,14 @@ pub mod response {
         basic::BasicTokenType, helpers, AccessToken, ExtraTokenFields, RefreshToken, Scope,
     };
 
-    use super::{Deserialize, HashMap, Serialize, SigningPolicy};
+    use chrono::{DateTime, TimeZone, Utc};
+    use std::borrow::Cow;
+    use std::convert::TryFrom;
+    use url::Url;
 
-    #[derive(Debug, Serialize, Deserialize)]
+    use super::{Deserialize, HashMap, MissingMsaParameter, Serialize, SigningPolicy};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(rename_all = "PascalCase")]
     pub struct TokenData {
         pub issue_instant: String,
@@ -126,13 +292,28 @@ pub mod response {
         pub token: String,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    impl TokenData {
+        /// Expiry time, `None` if `not_after` is not a valid RFC 3339 timestamp
+        pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+            DateTime::parse_from_rfc3339(&self.not_after)
+                .ok()
+                .map(|dt| dt.with_timezone(&Utc))
+        }
+
+        /// Whether the token has not expired yet
+        pub fn is_valid(&self) -> bool {
+            self.expires_at()
+                .map_or(false, |expiry| expiry > Utc::now())
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct XADDisplayClaims {
         /// {"xdi": {"did": "F.....", "dcs": "0"}}
         pub xdi: HashMap<String, String>,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(rename_all = "PascalCase")]
     pub struct XADResponse {
         #[serde(flatten)]
@@ -177,15 +358,38 @@ pub mod response {
         #[serde(flatten)]
         pub token_data: TokenData,
         pub display_claims: XSTSDisplayClaims,
+        /// Relying party the token was requested for.
+        /// Not part of the service response, filled in by
+        /// `XalAuthenticator::do_xsts_authorization`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub relying_party: Option<String>,
     }
 
     impl XSTSResponse {
+        fn xui_claim(&self, key: &str) -> Option<String> {
+            self.display_claims.xui.first()?.get(key).cloned()
+        }
+
         pub fn userhash(&self) -> String {
             self.display_claims.xui[0]["uhs"].clone()
         }
+        pub fn xuid(&self) -> Option<String> {
+            self.xui_claim("xid")
+        }
+        pub fn gamertag(&self) -> Option<String> {
+            self.xui_claim("gtg")
+        }
+        pub fn age_group(&self) -> Option<String> {
+            self.xui_claim("agg")
+        }
         pub fn authorization_header_value(&self) -> String {
             format!("XBL3.0 x={};{}", self.userhash(), self.token_data.token)
         }
+        /// Relying party the token is valid for, `None` if unknown
+        /// (e.g. the SISU authorization token)
+        pub fn relying_party(&self) -> Option<&str> {
+            self.relying_party.as_deref()
+        }
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -195,6 +399,95 @@ pub mod response {
         pub msa_request_parameters: HashMap<String, String>,
     }
 
+    impl SisuAuthenticationResponse {
+        /// Typed view over `msa_request_parameters`
+        ///
+        /// SISU may answer with an empty parameter map, the parameters
+        /// are then taken from the query of `msa_oauth_redirect`.
+        ///
+        /// Fails if one of the parameters the MSA authorize page
+        /// requires is absent.
+        pub fn msa_parameters(&self) -> Result<MsaRequestParameters<'_>, MissingMsaParameter> {
+            if !self.msa_request_parameters.is_empty() {
+                return MsaRequestParameters::new(&self.msa_request_parameters);
+            }
+
+            let params = Url::parse(&self.msa_oauth_redirect)
+                .map(|url| url.query_pairs().into_owned().collect())
+                .unwrap_or_default();
+            MsaRequestParameters::from_params(Cow::Owned(params))
+        }
+    }
+
+    /// OAuth2 parameters SISU expects the MSA authorize request to use
+    #[derive(Debug, Clone)]
+    pub struct MsaRequestParameters<'a> {
+        params: Cow<'a, HashMap<String, String>>,
+    }
+
+    impl<'a> MsaRequestParameters<'a> {
+        pub const REQUIRED: [&'static str; 4] =
+            ["client_id", "scope", "redirect_uri", "response_type"];
+
+        pub fn new(params: &'a HashMap<String, String>) -> Result<Self, MissingMsaParameter> {
+            Self::from_params(Cow::Borrowed(params))
+        }
+
+        fn from_params(
+            params: Cow<'a, HashMap<String, String>>,
+        ) -> Result<Self, MissingMsaParameter> {
+            if let Some(key) = Self::REQUIRED
+                .iter()
+                .find(|key| !params.contains_key(**key))
+            {
+                return Err(MissingMsaParameter(*key));
+            }
+
+            Ok(Self { params })
+        }
+
+        fn required(&self, key: &str) -> &str {
+            self.params
+                .get(key)
+                .map(String::as_str)
+                .expect("Required parameter checked on construction")
+        }
+
+        pub fn client_id(&self) -> &str {
+            self.required("client_id")
+        }
+
+        pub fn scope(&self) -> &str {
+            self.required("scope")
+        }
+
+        pub fn redirect_uri(&self) -> &str {
+            self.required("redirect_uri")
+        }
+
+        pub fn response_type(&self) -> &str {
+            self.required("response_type")
+        }
+
+        /// PKCE challenge, if SISU generated one
+        pub fn code_challenge(&self) -> Option<&str> {
+            self.get("code_challenge")
+        }
+
+        pub fn code_challenge_method(&self) -> Option<&str> {
+            self.get("code_challenge_method")
+        }
+
+        pub fn state(&self) -> Option<&str> {
+            self.get("state")
+        }
+
+        /// Any parameter, including ones without a dedicated accessor
+        pub fn get(&self, key: &str) -> Option<&str> {
+            self.params.get(key).map(String::as_str)
+        }
+    }
+
     #[derive(Debug, Serialize, Deserialize)]
     #[serde(rename_all = "PascalCase")]
     pub struct SisuAuthorizationResponse {
@@ -222,12 +515,41 @@ pub mod response {
         #[serde(skip_serializing_if = "Option::is_none")]
         pub refresh_token: Option<RefreshToken>,
         pub user_id: String,
+        /// Unix timestamp (seconds) the token was received at.
+        /// Not sent by the server, set by the authenticator once the
+        /// response arrived. `None` for tokens stored before it was tracked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub issued_at: Option<i64>,
 
         #[serde(bound = "EF: ExtraTokenFields")]
         #[serde(flatten)]
         pub extra_fields: EF,
     }
 
+    impl<EF: ExtraTokenFields> WindowsLiveTokenResponse<EF> {
+        /// Expiry time, `None` if the server did not send `expires_in`
+        /// or the issue time is unknown
+        pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+            let expires_in = i64::try_from(self.expires_in?).ok()?;
+            Utc.timestamp_opt(self.issued_at?.checked_add(expires_in)?, 0)
+                .single()
+        }
+
+        /// Whether the access token has expired
+        ///
+        /// Tokens with unknown issue time are treated as expired, tokens
+        /// the server sent without `expires_in` are assumed valid.
+        pub fn is_expired(&self) -> bool {
+            match (self.issued_at, self.expires_in) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                _ => self
+                    .expires_at()
+                    .map_or(true, |expiry| expiry <= Utc::now()),
+            }
+        }
+    }
+
     #[derive(Debug, Serialize, Deserialize)]
     pub struct XCloudTokenResponse {
         pub lpt: String,
@@ -271,11 +593,74 @@ pub mod response {
         pub signature_policy_index: Option<i32>,
         pub server_cert_index: Option<Vec<i32>>,
     }
+
+    impl TitleEndpoint {
+        /// Whether requests to `url` are covered by this endpoint
+        ///
+        /// `host_type` is either 'fqdn' (exact host) or 'wildcard'
+        /// (host and all its subdomains).
+        pub fn matches(&self, url: &Url) -> bool {
+            let host = match url.host_str() {
+                Some(host) => host.to_lowercase(),
+                None => return false,
+            };
+            let endpoint_host = self.host.to_lowercase();
+
+            if !self.protocol.eq_ignore_ascii_case(url.scheme()) {
+                return false;
+            }
+            if let Some(path) = &self.path {
+                if !url.path().starts_with(path.as_str()) {
+                    return false;
+                }
+            }
+
+            match self.host_type.to_lowercase().as_ref() {
+                "wildcard" => {
+                    host == endpoint_host || host.ends_with(&format!(".{}", endpoint_host))
+                }
+                _ => host == endpoint_host,
+            }
+        }
+    }
+
+    impl TitleEndpointsResponse {
+        /// Most specific endpoint covering `url`
+        ///
+        /// Exact host matches win over wildcards, longer wildcard
+        /// hosts over shorter ones.
+        pub fn endpoint_for(&self, url: &Url) -> Option<&TitleEndpoint> {
+            self.end_points
+                .iter()
+                .filter(|endpoint| endpoint.matches(url))
+                .max_by_key(|endpoint| {
+                    (
+                        endpoint.host_type.eq_ignore_ascii_case("fqdn"),
+                        endpoint.host.len(),
+                    )
+                })
+        }
+
+        /// Signing policy referenced by `endpoint`, `None` if the endpoint
+        /// does not require signing
+        pub fn signing_policy(&self, endpoint: &TitleEndpoint) -> Option<&SigningPolicy> {
+            let index = usize::try_from(endpoint.signature_policy_index?).ok()?;
+            self.signature_policies.get(index)
+        }
+
+        /// Signing policy for requests to `url`
+        pub fn signing_policy_for(&self, url: &Url) -> Option<&SigningPolicy> {
+            self.signing_policy(self.endpoint_for(url)?)
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{response, SigningAlgorithm, SigningPolicy};
+    use super::{
+        response, retry_after, DateTime, Duration, HeaderMap, MissingMsaParameter,
+        RelyingPartyScope, SigningAlgorithm, SigningPolicy, SisuSessionId, SisuSessionIdError, Utc,
+    };
     use serde_json;
 
     #[test]
@@ -310,6 +695,11 @@ mod test {
             "XBL3.0 x=abcdefg;123456789"
         );
         assert_eq!(bla.token_data.token, "123456789".to_owned());
+        assert!(bla.token_data.is_valid());
+        assert_eq!(
+            bla.token_data.expires_at().unwrap().to_rfc3339(),
+            "2999-10-10T19:06:35.525115500+00:00"
+        );
         assert_eq!(bla.display_claims.xui[0].get("gtg"), Some(&"e".to_owned()));
         assert_ne!(
             bla.display_claims.xui[0].get("uhs"),
@@ -317,6 +707,104 @@ mod test {
         );
     }
 
+    #[test]
+    fn xsts_display_claims() {
+        let data = r#"
+        {
+            "IssueInstant": "2010-10-10T03:06:35.5251155Z",
+            "NotAfter": "2999-10-10T19:06:35.5251155Z",
+            "Token": "123456789",
+            "DisplayClaims": {
+              "xui": [
+                {
+                  "gtg": "e",
+                  "xid": "2669321029139235",
+                  "uhs": "abcdefg",
+                  "agg": "Adult"
+                }
+              ]
+            }
+        }
+        "#;
+
+        let xsts: response::XSTSResponse =
+            serde_json::from_str(data).expect("BUG: Failed to deserialize XSTS response");
+
+        assert_eq!(xsts.xuid(), Some("2669321029139235".to_owned()));
+        assert_eq!(xsts.gamertag(), Some("e".to_owned()));
+        assert_eq!(xsts.age_group(), Some("Adult".to_owned()));
+    }
+
+    #[test]
+    fn xsts_display_claims_missing() {
+        let data = r#"
+        {
+            "IssueInstant": "2010-10-10T03:06:35.5251155Z",
+            "NotAfter": "2999-10-10T19:06:35.5251155Z",
+            "Token": "123456789",
+            "DisplayClaims": {
+              "xui": [
+                {
+                  "uhs": "abcdefg"
+                }
+              ]
+            }
+        }
+        "#;
+
+        let xsts: response::XSTSResponse =
+            serde_json::from_str(data).expect("BUG: Failed to deserialize XSTS response");
+
+        assert_eq!(xsts.userhash(), "abcdefg");
+        assert_eq!(xsts.relying_party(), None);
+        assert_eq!(xsts.xuid(), None);
+        assert_eq!(xsts.gamertag(), None);
+        assert_eq!(xsts.age_group(), None);
+    }
+
+    #[test]
+    fn xsts_relying_party_round_trip() {
+        let data = r#"
+        {
+            "IssueInstant": "2010-10-10T03:06:35.5251155Z",
+            "NotAfter": "2999-10-10T19:06:35.5251155Z",
+            "Token": "123456789",
+            "DisplayClaims": {
+              "xui": [
+                {
+                  "uhs": "abcdefg"
+                }
+              ]
+            }
+        }
+        "#;
+
+        let mut xsts: response::XSTSResponse =
+            serde_json::from_str(data).expect("BUG: Failed to deserialize XSTS response");
+        xsts.relying_party = Some("http://gssv.xboxlive.com/".into());
+
+        let serialized = serde_json::to_string(&xsts).expect("Failed to serialize XSTS response");
+        let xsts: response::XSTSResponse =
+            serde_json::from_str(&serialized).expect("Failed to deserialize XSTS response");
+
+        assert_eq!(xsts.relying_party(), Some("http://gssv.xboxlive.com/"));
+    }
+
+    #[test]
+    fn relying_party_scope() {
+        assert_eq!(
+            RelyingPartyScope::XboxLiveUserAuth.to_string(),
+            "service::user.auth.xboxlive.com::MBI_SSL"
+        );
+        assert_eq!(
+            RelyingPartyScope::XCloudConsoleTransferToken.as_str(),
+            "service::http://Passport.NET/purpose::PURPOSE_XBOX_CLOUD_CONSOLE_TRANSFER_TOKEN"
+        );
+
+        let scope: oauth2::Scope = RelyingPartyScope::XboxLiveUserAuth.into();
+        assert_eq!(scope.as_str(), "service::user.auth.xboxlive.com::MBI_SSL");
+    }
+
     #[test]
     fn deserialize_signing_policy() {
         let json_resp = r#"{
@@ -335,4 +823,242 @@ mod test {
             vec![SigningAlgorithm::ES521]
         )
     }
+
+    fn title_endpoints() -> response::TitleEndpointsResponse {
+        let json_resp = r#"{
+            "EndPoints": [
+                {
+                    "Protocol": "https",
+                    "Host": "xboxlive.com",
+                    "HostType": "wildcard",
+                    "RelyingParty": "http://xboxlive.com",
+                    "TokenType": "JWT",
+                    "SignaturePolicyIndex": 0
+                },
+                {
+                    "Protocol": "https",
+                    "Host": "gssv.xboxlive.com",
+                    "HostType": "wildcard",
+                    "RelyingParty": "http://gssv.xboxlive.com",
+                    "TokenType": "JWT",
+                    "SignaturePolicyIndex": 1
+                },
+                {
+                    "Protocol": "https",
+                    "Host": "title.mgt.xboxlive.com",
+                    "HostType": "fqdn",
+                    "RelyingParty": "http://xboxlive.com"
+                }
+            ],
+            "SignaturePolicies": [
+                {"Version": 1, "SupportedAlgorithms": ["ES256"], "MaxBodyBytes": 8192},
+                {"Version": 2, "SupportedAlgorithms": ["ES256"], "MaxBodyBytes": 4294967295}
+            ],
+            "Certs": [],
+            "RootCerts": []
+        }"#;
+
+        serde_json::from_str(json_resp).expect("Failed to deserialize TitleEndpointsResponse")
+    }
+
+    #[test]
+    fn title_endpoint_signing_policy() {
+        let endpoints = title_endpoints();
+        let policy_for = |url: &str| endpoints.signing_policy_for(&url.parse().unwrap());
+
+        assert_eq!(
+            policy_for("https://userpresence.xboxlive.com/users").map(|p| p.version),
+            Some(1)
+        );
+        assert_eq!(
+            policy_for("https://uks.gssv-play-prodxhome.gssv.xboxlive.com/v5").map(|p| p.version),
+            Some(2)
+        );
+        // Exact host without signing policy wins over the wildcard
+        assert_eq!(policy_for("https://title.mgt.xboxlive.com/titles"), None);
+        // Protocol mismatch / unrelated host
+        assert_eq!(policy_for("http://userpresence.xboxlive.com/users"), None);
+        assert_eq!(policy_for("https://notxboxlive.com/"), None);
+    }
+
+    #[test]
+    fn sisu_session_id_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-sessionid", "a1b2c3d4e5f6".parse().unwrap());
+
+        let session_id = SisuSessionId::from_headers(&headers).expect("Failed to get session id");
+        assert_eq!(session_id.as_str(), "a1b2c3d4e5f6");
+    }
+
+    #[test]
+    fn sisu_session_id_missing_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-other", "a1b2c3d4e5f6".parse().unwrap());
+
+        assert_eq!(
+            SisuSessionId::from_headers(&headers),
+            Err(SisuSessionIdError::Missing)
+        );
+    }
+
+    #[test]
+    fn sisu_session_id_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-sessionid", "".parse().unwrap());
+        assert_eq!(
+            SisuSessionId::from_headers(&headers),
+            Err(SisuSessionIdError::Invalid)
+        );
+
+        assert_eq!(
+            "abc def".parse::<SisuSessionId>(),
+            Err(SisuSessionIdError::Invalid)
+        );
+    }
+
+    #[test]
+    fn windows_live_token_expiry() {
+        type WlToken = response::WindowsLiveTokenResponse<oauth2::EmptyExtraTokenFields>;
+
+        let data = r#"
+        {
+            "token_type": "bearer",
+            "expires_in": 3600,
+            "scope": "service::user.auth.xboxlive.com::MBI_SSL",
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "user_id": "123"
+        }
+        "#;
+
+        let mut token: WlToken = serde_json::from_str(data).expect("Failed to deserialize token");
+        token.issued_at = Some(chrono::Utc::now().timestamp());
+        assert!(!token.is_expired());
+
+        // Issued-at is kept across a store / reload
+        let mut stored = serde_json::to_value(&token).unwrap();
+        stored["issued_at"] = serde_json::json!(1_000_000_000);
+        let reloaded: WlToken = serde_json::from_value(stored).unwrap();
+        assert_eq!(reloaded.issued_at, Some(1_000_000_000));
+        assert_eq!(reloaded.expires_at().unwrap().timestamp(), 1_000_003_600);
+        assert!(reloaded.is_expired());
+
+        // Without expires_in the token is assumed valid
+        token.expires_in = None;
+        assert_eq!(token.expires_at(), None);
+        assert!(!token.is_expired());
+    }
+
+    #[test]
+    fn windows_live_token_without_issued_at() {
+        type WlToken = response::WindowsLiveTokenResponse<oauth2::EmptyExtraTokenFields>;
+
+        // Stored before issued_at was tracked
+        let data = r#"
+        {
+            "token_type": "bearer",
+            "expires_in": 86400,
+            "scope": "service::user.auth.xboxlive.com::MBI_SSL",
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "user_id": "123"
+        }
+        "#;
+
+        let token: WlToken = serde_json::from_str(data).expect("Failed to deserialize token");
+        assert_eq!(token.issued_at, None);
+        assert_eq!(token.expires_at(), None);
+        assert!(token.is_expired());
+
+        let stored = serde_json::to_value(&token).unwrap();
+        assert!(stored.get("issued_at").is_none());
+    }
+
+    #[test]
+    fn parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let parse = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("Retry-After", value.parse().unwrap());
+            retry_after(&headers, now)
+        };
+
+        assert_eq!(parse("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:29:30 GMT"),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(parse("Wed, 21 Oct 2015 07:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("soon"), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn msa_request_parameters() {
+        let data = r#"
+        {
+            "MsaOauthRedirect": "https://login.live.com/oauth20_authorize.srf?foo=bar",
+            "MsaRequestParameters": {
+                "client_id": "000000004c20a908",
+                "scope": "service::user.auth.xboxlive.com::MBI_SSL",
+                "redirect_uri": "ms-xal-000000004c20a908://auth",
+                "response_type": "code",
+                "display": "android_phone",
+                "code_challenge": "abc",
+                "code_challenge_method": "S256",
+                "state": "xyz"
+            }
+        }
+        "#;
+
+        let mut resp: response::SisuAuthenticationResponse =
+            serde_json::from_str(data).expect("Failed to deserialize SISU authentication response");
+
+        let params = resp.msa_parameters().expect("Parameters incomplete");
+        assert_eq!(params.client_id(), "000000004c20a908");
+        assert_eq!(params.scope(), "service::user.auth.xboxlive.com::MBI_SSL");
+        assert_eq!(params.redirect_uri(), "ms-xal-000000004c20a908://auth");
+        assert_eq!(params.response_type(), "code");
+        assert_eq!(params.code_challenge(), Some("abc"));
+        assert_eq!(params.code_challenge_method(), Some("S256"));
+        assert_eq!(params.state(), Some("xyz"));
+        assert_eq!(params.get("display"), Some("android_phone"));
+
+        resp.msa_request_parameters.remove("redirect_uri");
+        assert_eq!(
+            resp.msa_parameters().unwrap_err(),
+            MissingMsaParameter("redirect_uri")
+        );
+    }
+
+    #[test]
+    fn msa_request_parameters_from_redirect() {
+        let data = r#"
+        {
+            "MsaOauthRedirect": "https://login.live.com/oauth20_authorize.srf?lw=1&fl=dob,easi2&xsup=1&code_challenge=abc&code_challenge_method=S256&state=xyz&client_id=000000004c20a908&response_type=code&scope=service%3A%3Auser.auth.xboxlive.com%3A%3AMBI_SSL&redirect_uri=ms-xal-000000004c20a908%3A%2F%2Fauth",
+            "MsaRequestParameters": {}
+        }
+        "#;
+
+        let mut resp: response::SisuAuthenticationResponse =
+            serde_json::from_str(data).expect("Failed to deserialize SISU authentication response");
+
+        let params = resp.msa_parameters().expect("Parameters incomplete");
+        assert_eq!(params.client_id(), "000000004c20a908");
+        assert_eq!(params.scope(), "service::user.auth.xboxlive.com::MBI_SSL");
+        assert_eq!(params.redirect_uri(), "ms-xal-000000004c20a908://auth");
+        assert_eq!(params.response_type(), "code");
+        assert_eq!(params.code_challenge(), Some("abc"));
+        assert_eq!(params.state(), Some("xyz"));
+        assert_eq!(params.get("lw"), Some("1"));
+
+        // As returned in authenticator tests, no usable parameters at all
+        resp.msa_oauth_redirect = "https://login.live.com/oauth20_authorize.srf?foo=bar".into();
+        assert_eq!(
+            resp.msa_parameters().unwrap_err(),
+            MissingMsaParameter("client_id")
+        );
+    }
 }
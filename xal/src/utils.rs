@@ -5,9 +5,20 @@ use std::fs;
 use crate::authenticator::SpecialTokenResponse;
 use crate::{
     app_params::{XalAppParameters, XalClientParameters},
-    models::response::{SisuAuthorizationResponse, XCloudTokenResponse, XSTSResponse},
+    models::response::{SisuAuthorizationResponse, XADResponse, XCloudTokenResponse, XSTSResponse},
 };
 
+/// Device token, along with the device id and proof key it was issued for
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedDeviceToken {
+    pub device_id: String,
+    /// Private proof key (PEM) the token is bound to, missing in token
+    /// files written before it was stored
+    #[serde(default)]
+    pub proof_key: Option<String>,
+    pub token: XADResponse,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenStore {
     pub app_params: XalAppParameters,
@@ -15,7 +26,13 @@ pub struct TokenStore {
     pub wl_token: SpecialTokenResponse,
     pub sisu_tokens: SisuAuthorizationResponse,
     pub gssv_token: XSTSResponse,
+    /// XSTS token for the Smartglass API, missing in token files
+    /// written before it was fetched
+    #[serde(default)]
+    pub smartglass_token: Option<XSTSResponse>,
     pub xcloud_transfer_token: XCloudTokenResponse,
+    #[serde(default)]
+    pub device_token: Option<CachedDeviceToken>,
     pub updated: DateTime<Utc>,
 }
 